@@ -3,10 +3,12 @@ use std::fs::{self, File};
 use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::process::Command;
+use crate::com::rules;
 
 const MOUNT_POINT: &str = "/tmp/rex_mount";
 
-pub fn run(target: &str, all: bool, only_deleted: bool, output_base: &str) -> Result<()> {
+pub fn run(target: &str, all: bool, only_deleted: bool, output_base: &str, rules_path: Option<&str>) -> Result<()> {
+    let rule_set = rules_path.map(rules::load).transpose()?;
     println!("[*] Carving from: {}", target);
     let session_id = uuid::Uuid::new_v4();
     let output_dir = format!("{}/{}", output_base, session_id);
@@ -17,6 +19,9 @@ pub fn run(target: &str, all: bool, only_deleted: bool, output_base: &str) -> Re
     if !only_deleted {
         extract_live_files(target, &output_dir)?;
     }
+    if let Some(rule_set) = &rule_set {
+        rules::scan_output(rule_set, &output_dir, &[])?;
+    }
     Ok(())
 }
 
@@ -1,23 +1,35 @@
-use anyhow::{Result, Context};
-use lopdf::{Document, Object};
+use anyhow::{bail, Result, Context};
+use lopdf::Document;
+use std::collections::HashMap;
 use std::fs::{create_dir_all, File};
-use std::io::Write;
+use std::io::{BufWriter, Read, Write};
+use crate::com::rules;
+use crate::helper::{archive, email, exe, export, hashes, file_info, fuzzy, ioc, lnk, office, pdf_attach, pdf_decode, pdf_image, pdf_ioc, pdf_load, pdf_meta, pdf_triage, strings};
 
-pub fn extract_pdf(path: &str, output_dir: &str) -> Result<()> {
+pub fn extract_pdf(path: &str, output_dir: &str, password: Option<&str>, rules_path: Option<&str>) -> Result<()> {
+    // Load rules first so a broken rules directory fails before anything is written.
+    let rule_set = rules_path.map(rules::load).transpose()?;
     create_dir_all(format!("{}/images", output_dir))
         .context("Failed to create images output directory")?;
     create_dir_all(format!("{}/js", output_dir))
         .context("Failed to create js output directory")?;
     create_dir_all(format!("{}/raw_objects", output_dir))
         .context("Failed to create raw_objects output directory")?;
+    create_dir_all(format!("{}/streams", output_dir))
+        .context("Failed to create streams output directory")?;
+    create_dir_all(format!("{}/attachments", output_dir))
+        .context("Failed to create attachments output directory")?;
 
-    let doc = Document::load(path)
-        .with_context(|| format!("Failed to load PDF document: {}", path))?;
+    let loaded = pdf_load::load(path, password)?;
+    let structure = pdf_load::structure(&loaded.raw, &loaded.doc);
+    pdf_load::print_structure(&loaded, &structure);
+    let doc = loaded.doc;
 
     extract_metadata(&doc, output_dir)?;
 
     let mut image_id = 0;
     let mut js_id = 0;
+    let mut decode_notes = String::new();
 
     for (id, object) in &doc.objects {
         let raw_path = format!("{}/raw_objects/obj_{}.txt", output_dir, id.0);
@@ -27,15 +39,26 @@ pub fn extract_pdf(path: &str, output_dir: &str) -> Result<()> {
             .with_context(|| format!("Failed to write to {}", raw_path))?;
 
         if let Ok(stream) = object.as_stream() {
+            let decoded = pdf_decode::decode_stream(&doc, stream);
+            write_stream(output_dir, id.0, &stream.content, &decoded)?;
+            for note in decoded.error.iter().chain(decoded.warnings.iter()) {
+                println!("Warning: stream {}: {}", id.0, note);
+                decode_notes.push_str(&format!("obj {}: {}\n", id.0, note));
+            }
+
             // JS extraction
             if let Ok(type_obj) = stream.dict.get(b"Type") {
                 if let Ok(name) = type_obj.as_name() {
                     let type_name = String::from_utf8_lossy(name);
                     if type_name == "JavaScript" {
-                        let js_content = String::from_utf8_lossy(&stream.content);
                         let js_path = format!("{}/js/script_{}.js", output_dir, js_id);
-                        std::fs::write(&js_path, js_content.as_bytes())
+                        std::fs::write(&js_path, &decoded.data)
                             .with_context(|| format!("Failed to write JS file {}", js_path))?;
+                        if !decoded.applied.is_empty() {
+                            let raw_js_path = format!("{}.raw", js_path);
+                            std::fs::write(&raw_js_path, &stream.content)
+                                .with_context(|| format!("Failed to write JS file {}", raw_js_path))?;
+                        }
                         js_id += 1;
                     } else if type_name == "XObject" {
                         println!("Found XObject stream with ID: {}", id.0);
@@ -51,14 +74,16 @@ pub fn extract_pdf(path: &str, output_dir: &str) -> Result<()> {
                     let subtype_name = String::from_utf8_lossy(name);
                     if subtype_name == "Image" {
                         println!("Found image stream with ID: {}", id.0);
-                        let raw_img_path = format!("{}/images/image_{}.raw", output_dir, image_id);
-                        std::fs::write(&raw_img_path, format!("{:?}", stream.dict))
-                            .with_context(|| format!("Failed to write {}", raw_img_path))?;
-                        let image_path = format!("{}/images/image_{}.unknowext.png", output_dir, image_id);
-                        let mut image_file = File::create(&image_path)
-                            .with_context(|| format!("Failed to create {}", image_path))?;
-                        image_file.write_all(&stream.content)
+                        let image = pdf_image::export(&doc, stream, &decoded);
+                        let image_path = format!("{}/images/image_{}.{}", output_dir, image_id, image.extension);
+                        std::fs::write(&image_path, &image.data)
                             .with_context(|| format!("Failed to write {}", image_path))?;
+                        let info_path = format!("{}/images/image_{}.txt", output_dir, image_id);
+                        std::fs::write(&info_path, pdf_image::describe(&doc, id.0, stream, &decoded, &image))
+                            .with_context(|| format!("Failed to write {}", info_path))?;
+                        if let Some(note) = &image.note {
+                            println!("Warning: image {} (object {}): {}", image_id, id.0, note);
+                        }
                         image_id += 1;
                     } else if subtype_name == "Form" {
                         println!("Found Form stream with ID: {}", id.0);
@@ -69,33 +94,472 @@ pub fn extract_pdf(path: &str, output_dir: &str) -> Result<()> {
             }
         }
     }
+    if !decode_notes.is_empty() {
+        std::fs::write(format!("{}/streams/decode_notes.txt", output_dir), decode_notes)
+            .context("Failed to write decode_notes.txt")?;
+    }
+    extract_attachments(&doc, output_dir)?;
+
+    let iocs = pdf_ioc::collect(&doc);
+    if !iocs.is_empty() {
+        println!("Found {} indicator(s) (URLs, IPs, domains, emails, launch targets)", iocs.len());
+    }
+    std::fs::write(format!("{}/iocs.json", output_dir), serde_json::to_string_pretty(&iocs)?)
+        .context("Failed to write iocs.json")?;
+    if let Some(rule_set) = &rule_set {
+        // raw_objects only holds debug dumps of the objects already scanned as streams.
+        rules::scan_output(rule_set, output_dir, &["raw_objects"])?;
+    }
     println!("Finished extracting PDF, output saved to '{}'", output_dir);
     Ok(())
 }
 
-fn extract_metadata(doc: &Document, output_dir: &str) -> Result<()> {
-    let info_ref = doc.trailer.get(b"Info")
-        .and_then(|obj| obj.as_reference())
-        .ok();
+pub fn triage_pdf(path: &str, password: Option<&str>) -> Result<()> {
+    let loaded = pdf_load::load(path, password)?;
+    let structure = pdf_load::structure(&loaded.raw, &loaded.doc);
+
+    let mut report = pdf_triage::analyze(&loaded.doc, &loaded.raw);
+    pdf_triage::add_structure_reasons(&mut report, &loaded, &structure);
+    pdf_load::print_structure(&loaded, &structure);
+    println!();
+    pdf_triage::print_report(path, &report);
+    Ok(())
+}
+
+pub fn office(path: &str, output_dir: &str) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let report = office::analyze(&data)?;
+
+    if !report.modules.is_empty() {
+        create_dir_all(format!("{}/vba", output_dir))
+            .context("Failed to create vba output directory")?;
+    }
+    for (i, module) in report.modules.iter().enumerate() {
+        let module_path = format!("{}/vba/{}", output_dir, office::module_file_name(i, module));
+        std::fs::write(&module_path, &module.code)
+            .with_context(|| format!("Failed to write {}", module_path))?;
+    }
 
-    let info_ref = match info_ref {
-        Some(ref_obj) => ref_obj,
-        None => {
-            eprintln!("No metadata found in the PDF.");
-            return Ok(());
+    if !report.embedded.is_empty() {
+        create_dir_all(format!("{}/embedded", output_dir))
+            .context("Failed to create embedded output directory")?;
+        let mut index = String::new();
+        for (i, object) in report.embedded.iter().enumerate() {
+            let name = object.label.as_deref().unwrap_or(&object.location);
+            let file_name = format!("{}_{}", i, pdf_attach::safe_filename(name));
+            std::fs::write(format!("{}/embedded/{}", output_dir, file_name), &object.data)
+                .with_context(|| format!("Failed to write embedded object {}", file_name))?;
+
+            index.push_str(&format!("[{}] {}\n", file_name, object.location));
+            index.push_str(&format!("  Class:   {}\n", object.class.as_deref().unwrap_or("-")));
+            if let Some(source) = &object.source_path {
+                index.push_str(&format!("  Path:    {}\n", source));
+            }
+            index.push_str(&format!("  Size:    {} bytes\n", object.data.len()));
+            index.push_str(&format!("  MD5:     {}\n", object.hashes.md5));
+            index.push_str(&format!("  SHA1:    {}\n", object.hashes.sha1));
+            index.push_str(&format!("  SHA256:  {}\n", object.hashes.sha256));
+            if let Some(note) = &object.note {
+                index.push_str(&format!("  Note:    {}\n", note));
+            }
+            index.push('\n');
         }
+        std::fs::write(format!("{}/embedded/embedded.txt", output_dir), index)
+            .context("Failed to write embedded.txt")?;
+    }
+
+    office::print_report(path, &report);
+    Ok(())
+}
+
+pub fn exe(path: &str) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let report = exe::analyze(&data)?;
+    exe::print_report(path, &report);
+    Ok(())
+}
+
+pub fn lnk(path: &str) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let link = lnk::parse(&data)?;
+    lnk::print_report(path, &link);
+    Ok(())
+}
+
+pub fn email(path: &str, output_dir: &str) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let email = email::parse(&data)?;
+
+    create_dir_all(output_dir).context("Failed to create email output directory")?;
+    for (i, body) in email.bodies.iter().enumerate() {
+        let body_path = format!("{}/body_{}.{}", output_dir, i, if body.kind == "html" { "html" } else { "txt" });
+        std::fs::write(&body_path, &body.content)
+            .with_context(|| format!("Failed to write {}", body_path))?;
+    }
+    if !email.urls.is_empty() {
+        std::fs::write(format!("{}/urls.txt", output_dir), email.urls.join("\n") + "\n")
+            .context("Failed to write urls.txt")?;
+    }
+
+    if !email.attachments.is_empty() {
+        create_dir_all(format!("{}/attachments", output_dir))
+            .context("Failed to create attachments output directory")?;
+        let mut index = String::new();
+        for (i, att) in email.attachments.iter().enumerate() {
+            let file_name = format!("{}_{}", i, pdf_attach::safe_filename(&att.filename));
+            std::fs::write(format!("{}/attachments/{}", output_dir, file_name), &att.data)
+                .with_context(|| format!("Failed to write attachment {}", file_name))?;
+
+            index.push_str(&format!("[{}]\n", file_name));
+            index.push_str(&format!("  Filename:      {}\n", att.filename));
+            index.push_str(&format!("  Content type:  {}\n", att.content_type));
+            index.push_str(&format!("  Size:          {} bytes\n", att.data.len()));
+            index.push_str(&format!("  MD5:           {}\n", att.hashes.md5));
+            index.push_str(&format!("  SHA1:          {}\n", att.hashes.sha1));
+            index.push_str(&format!("  SHA256:        {}\n", att.hashes.sha256));
+            if let Some(note) = &att.note {
+                index.push_str(&format!("  Note:          {}\n", note));
+            }
+            index.push('\n');
+        }
+        std::fs::write(format!("{}/attachments/attachments.txt", output_dir), index)
+            .context("Failed to write attachments.txt")?;
+    }
+
+    email::print_report(path, &email);
+    Ok(())
+}
+
+pub fn info(path: &str, format: &str, output: Option<&str>) -> Result<()> {
+    let format = export::Format::parse(format)?;
+    let mut infos = vec![];
+    for file in file_info::collect_files(std::path::Path::new(path))? {
+        match file_info::analyze(&file) {
+            Ok(info) => infos.push(info),
+            Err(e) => println!("Warning: {:#}", e),
+        }
+    }
+
+    let content = if format == export::Format::Text {
+        file_info::render_text(&infos)
+    } else {
+        export::render(format, &infos)?
     };
+    export::write_output(output, &content)
+}
+
+// Stop extracting once this much has been written, whatever the entries declare.
+const MAX_EXTRACTED: u64 = 4 * 1024 * 1024 * 1024;
+
+pub fn archive(path: &str, output_dir: &str, extract: bool, password: Option<&str>) -> Result<()> {
+    let data = std::fs::read(path).with_context(|| format!("Failed to read {}", path))?;
+    let report = archive::analyze(&data)?;
+    archive::print_report(path, &report);
+    if !extract {
+        return Ok(());
+    }
+
+    if !report.extractable() {
+        bail!("--extract supports ZIP, tar and gzip; {} entries can only be listed", report.format);
+    }
+
+    // Entries are written flat under an index prefix, so no name can leave the output directory.
+    let entries_dir = format!("{}/entries", output_dir);
+    create_dir_all(&entries_dir).context("Failed to create entries output directory")?;
+    let mut index = String::new();
+    let mut written = 0u64;
+    let mut extracted = 0;
+    println!("\n=== Extraction ===");
+    for (i, item) in report.items.iter().enumerate() {
+        if !item.has_content() {
+            continue;
+        }
+        let content = match archive::extract(&data, &report, item, password) {
+            Ok(content) => content,
+            Err(e) => {
+                println!("Warning: {:#}", e);
+                continue;
+            }
+        };
+        // Declared sizes are attacker-controlled; the budget counts what was actually inflated.
+        if written + content.len() as u64 > MAX_EXTRACTED {
+            println!("Warning: stopping at '{}', {} bytes already extracted", item.name, written);
+            break;
+        }
+        let mut notes = item.notes.clone();
+        notes.extend(archive::verify(item, &content));
 
-    let info_obj = doc.get_object(info_ref)
-        .context("Failed to get PDF info object")?;
+        let file_name = format!("{}_{}", i, pdf_attach::safe_filename(&item.name));
+        std::fs::write(format!("{}/{}", entries_dir, file_name), &content)
+            .with_context(|| format!("Failed to write entry {}", file_name))?;
+        written += content.len() as u64;
+        extracted += 1;
 
-    if let Object::Dictionary(info_dict) = info_obj {
-        let mut metadata = String::new();
-        for (k, v) in info_dict.iter() {
-            metadata.push_str(&format!("{}: {:?}\n", String::from_utf8_lossy(k), v));
+        let hashes = hashes::compute(&content);
+        index.push_str(&format!("[{}]\n", file_name));
+        index.push_str(&format!("  Name:          {}\n", item.name));
+        index.push_str(&format!("  Size:          {} bytes\n", content.len()));
+        index.push_str(&format!("  MD5:           {}\n", hashes.md5));
+        index.push_str(&format!("  SHA1:          {}\n", hashes.sha1));
+        index.push_str(&format!("  SHA256:        {}\n", hashes.sha256));
+        for note in &notes {
+            index.push_str(&format!("  Note:          {}\n", note));
+        }
+        index.push('\n');
+        println!("{}  {}", hashes.sha256, item.name);
+        for note in notes.iter().filter(|n| !item.notes.contains(n)) {
+            println!("Warning: '{}': {}", item.name, note);
         }
-        std::fs::write(format!("{}/metadata.txt", output_dir), metadata)
-            .context("Failed to write metadata.txt")?;
     }
+    std::fs::write(format!("{}/entries.txt", entries_dir), index).context("Failed to write entries.txt")?;
+    println!("Extracted {} entr{} to '{}'", extracted, if extracted == 1 { "y" } else { "ies" }, entries_dir);
+    Ok(())
+}
+
+/// Indicator kinds in the order the summary lists them.
+const INDICATOR_KINDS: &[&str] = &["url", "ip", "domain", "email", "registry", "path", "user_agent", "wallet", "base64"];
+
+pub fn strings(
+    path: &str,
+    min_len: usize,
+    encoding: &str,
+    radix: &str,
+    iocs_only: bool,
+    format: &str,
+    output: Option<&str>,
+) -> Result<()> {
+    let format = export::Format::parse(format)?;
+    let (ascii, wide) = match encoding {
+        "all" => (true, true),
+        "ascii" => (true, false),
+        "utf16le" => (false, true),
+        other => bail!("Unknown encoding '{}' (expected all, ascii or utf16le)", other),
+    };
+    if !matches!(radix, "x" | "d" | "o" | "none") {
+        bail!("Unknown radix '{}' (expected x, d, o or none)", radix);
+    }
+    let offset = |value: u64| match radix {
+        "d" => format!("{:>9} ", value),
+        "o" => format!("{:>11o} ", value),
+        "none" => String::new(),
+        _ => format!("{:>8x} ", value),
+    };
+
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path))?;
+    // Text and NDJSON are streamed, a disk image can yield millions of strings.
+    let streamed = matches!(format, export::Format::Text | export::Format::Ndjson);
+    let mut writer: Box<dyn Write> = match output {
+        Some(out) if streamed => Box::new(BufWriter::new(
+            File::create(out).with_context(|| format!("Failed to create {}", out))?,
+        )),
+        _ => Box::new(BufWriter::new(std::io::stdout().lock())),
+    };
+    let mut rows = vec![];
+    let mut summary: Vec<(ioc::Indicator, u64, usize)> = vec![];
+    let mut seen: HashMap<ioc::Indicator, usize> = HashMap::new();
+    let mut write_error = None;
+    let mut total = 0u64;
+
+    let mut handle = |found: strings::Found| {
+        total += 1;
+        let indicators = ioc::classify(&found.value);
+        for indicator in &indicators {
+            match seen.get(indicator) {
+                Some(&i) => summary[i].2 += 1,
+                None => {
+                    seen.insert(indicator.clone(), summary.len());
+                    summary.push((indicator.clone(), found.offset, 1));
+                }
+            }
+        }
+        if iocs_only && indicators.is_empty() {
+            return;
+        }
+        let row = strings::Row { found, indicators };
+        let line = match format {
+            export::Format::Text => {
+                let kinds: Vec<&str> = row.indicators.iter().map(|i| i.kind).collect();
+                let kinds = if kinds.is_empty() { String::new() } else { format!("  [{}]", kinds.join(", ")) };
+                let enc = if row.found.encoding == "ascii" { "a" } else { "w" };
+                format!("{}{} {}{}", offset(row.found.offset), enc, row.found.value, kinds)
+            }
+            export::Format::Ndjson => serde_json::to_string(&row).unwrap_or_default(),
+            _ => {
+                rows.push(row);
+                return;
+            }
+        };
+        if let Err(e) = writeln!(writer, "{}", line) {
+            write_error.get_or_insert(e);
+        }
+    };
+
+    let mut scanner = strings::Scanner::new(min_len, ascii, wide);
+    let mut buf = vec![0u8; 1 << 20];
+    loop {
+        let n = file.read(&mut buf).with_context(|| format!("Failed to read {}", path))?;
+        if n == 0 {
+            break;
+        }
+        scanner.feed(&buf[..n], &mut handle);
+    }
+    scanner.finish(&mut handle);
+    if let Some(e) = write_error {
+        return Err(e).context("Failed to write strings");
+    }
+
+    if !streamed {
+        drop(writer);
+        return export::write_output(output, &export::render(format, &rows)?);
+    }
+    if format == export::Format::Text {
+        writeln!(writer, "\n=== Indicators ({} strings) ===", total)?;
+        if summary.is_empty() {
+            writeln!(writer, "None.")?;
+        }
+        for kind in INDICATOR_KINDS {
+            let entries: Vec<_> = summary.iter().filter(|(i, _, _)| i.kind == *kind).collect();
+            if entries.is_empty() {
+                continue;
+            }
+            writeln!(writer, "{} ({}):", kind, entries.len())?;
+            for (indicator, first, count) in entries {
+                let mut notes = vec![];
+                if radix != "none" {
+                    notes.push(format!("first at {}", offset(*first).trim()));
+                }
+                if *count > 1 {
+                    notes.push(format!("x{}", count));
+                }
+                let notes = if notes.is_empty() { String::new() } else { format!(" ({})", notes.join(", ")) };
+                writeln!(writer, "  {}{}", indicator.value, notes)?;
+            }
+        }
+    }
+    writer.flush().context("Failed to write strings")?;
+    if let Some(out) = output {
+        eprintln!("Output saved to '{}'", out);
+    }
+    Ok(())
+}
+
+pub fn similar(path: &str, min_score: u32, max_distance: u32) -> Result<()> {
+    let files = file_info::collect_files(std::path::Path::new(path))?;
+    let mut names = vec![];
+    let mut digests = vec![];
+    for file in files {
+        // Same limit as `file info`: the digests need the whole file in memory.
+        let too_large = || println!("Warning: skipping {}, larger than the {} byte fuzzy hashing limit",
+            file.display(), file_info::FUZZY_LIMIT);
+        if std::fs::metadata(&file).is_ok_and(|m| m.len() > file_info::FUZZY_LIMIT) {
+            too_large();
+            continue;
+        }
+        let mut data = vec![];
+        let read = File::open(&file)
+            .and_then(|f| f.take(file_info::FUZZY_LIMIT + 1).read_to_end(&mut data));
+        match read {
+            Ok(_) if data.len() as u64 > file_info::FUZZY_LIMIT => too_large(),
+            Ok(_) if !data.is_empty() => {
+                digests.push(fuzzy::digests(&data));
+                names.push(file.display().to_string());
+            }
+            Ok(_) => {}
+            Err(e) => println!("Warning: cannot read {}: {}", file.display(), e),
+        }
+    }
+
+    let (groups, matches) = fuzzy::cluster(&digests, min_score, max_distance);
+    println!("Similarity Report for '{}': {} files, {} group(s)", path, names.len(), groups.len());
+    for (i, group) in groups.iter().enumerate() {
+        println!("\n=== Group {} ({} files) ===", i + 1, group.len());
+        for &member in group {
+            println!("  {}", names[member]);
+            println!("      CTPH: {}", digests[member].ctph);
+            if let Some(tlsh) = &digests[member].tlsh {
+                println!("      TLSH: {}", tlsh);
+            }
+        }
+        println!("  Matches:");
+        for m in matches.iter().filter(|m| group.contains(&m.a)) {
+            let tlsh = m.tlsh.map(|d| d.to_string()).unwrap_or_else(|| "-".to_string());
+            println!("    {} <-> {}  CTPH {:>3}  TLSH {:>4}", names[m.a], names[m.b], m.ctph, tlsh);
+        }
+    }
+    let grouped: usize = groups.iter().map(|g| g.len()).sum();
+    println!("\n{} file(s) not similar to any other", names.len() - grouped);
+    Ok(())
+}
+
+fn extract_attachments(doc: &Document, output_dir: &str) -> Result<()> {
+    let attachments = pdf_attach::collect(doc);
+    if attachments.is_empty() {
+        return Ok(());
+    }
+
+    let mut index = String::new();
+    for (i, att) in attachments.iter().enumerate() {
+        let file_name = format!("{}_{}", i, pdf_attach::safe_filename(&att.filename));
+        let path = format!("{}/attachments/{}", output_dir, file_name);
+        std::fs::write(&path, &att.data)
+            .with_context(|| format!("Failed to write {}", path))?;
+        println!("Found embedded file '{}' ({} bytes) in stream {}", att.filename, att.data.len(), att.stream);
+
+        index.push_str(&format!("[{}] {}\n", file_name, att.source));
+        index.push_str(&format!("  Filename:      {}\n", att.filename));
+        if let Some(desc) = &att.description {
+            index.push_str(&format!("  Description:   {}\n", desc));
+        }
+        index.push_str(&format!("  Stream object: {}\n", att.stream));
+        index.push_str(&format!("  MIME type:     {}\n", att.mime.as_deref().unwrap_or("-")));
+        index.push_str(&format!("  Size:          {} bytes\n", att.data.len()));
+        if let Some(declared) = att.declared_size {
+            if declared != att.data.len() as i64 {
+                index.push_str(&format!("  Declared size: {} bytes (does not match)\n", declared));
+                println!("Warning: embedded file '{}' declares {} bytes but holds {}", att.filename, declared, att.data.len());
+            }
+        }
+        index.push_str(&format!("  MD5:           {}\n", att.hashes.md5));
+        index.push_str(&format!("  SHA1:          {}\n", att.hashes.sha1));
+        index.push_str(&format!("  SHA256:        {}\n", att.hashes.sha256));
+        if let Some(note) = &att.note {
+            index.push_str(&format!("  Note:          {}\n", note));
+            println!("Warning: embedded file '{}': {}", att.filename, note);
+        }
+        index.push('\n');
+    }
+    std::fs::write(format!("{}/attachments/attachments.txt", output_dir), index)
+        .context("Failed to write attachments.txt")?;
+    Ok(())
+}
+
+fn write_stream(output_dir: &str, id: u32, raw: &[u8], decoded: &pdf_decode::Decoded) -> Result<()> {
+    let raw_path = format!("{}/streams/obj_{}.raw", output_dir, id);
+    std::fs::write(&raw_path, raw)
+        .with_context(|| format!("Failed to write {}", raw_path))?;
+    if !decoded.applied.is_empty() {
+        let decoded_path = format!("{}/streams/obj_{}.decoded", output_dir, id);
+        std::fs::write(&decoded_path, &decoded.data)
+            .with_context(|| format!("Failed to write {}", decoded_path))?;
+    }
+    Ok(())
+}
+
+fn extract_metadata(doc: &Document, output_dir: &str) -> Result<()> {
+    let meta = pdf_meta::read(doc);
+    if meta.info.is_empty() && meta.xmp.is_none() {
+        eprintln!("No metadata found in the PDF.");
+        return Ok(());
+    }
+
+    if let Some(xmp) = &meta.xmp {
+        std::fs::write(format!("{}/metadata.xmp", output_dir), &xmp.raw)
+            .context("Failed to write metadata.xmp")?;
+    }
+    for issue in pdf_meta::inconsistencies(&meta) {
+        println!("Warning: metadata: {}", issue);
+    }
+    std::fs::write(format!("{}/metadata.txt", output_dir), pdf_meta::render(&meta))
+        .context("Failed to write metadata.txt")?;
     Ok(())
 }
@@ -7,3 +7,4 @@ pub mod diskinfo;
 pub mod carve;
 pub mod hash;
 pub mod bruteforce;
+pub mod rules;
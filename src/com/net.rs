@@ -1,5 +1,6 @@
 use anyhow::{Result, Context};
-use pcap::{Capture, Device};
+use chrono::DateTime;
+use pcap::{Capture, Device, Linktype, Offline};
 
 pub fn logs_network() -> Result<()> {
     let device = Device::lookup()
@@ -12,96 +13,255 @@ pub fn logs_network() -> Result<()> {
         .promisc(true)
         .open()
         .context("Failed to open capture")?;
+    let linktype = cap.get_datalink();
 
     println!("Listening for packets...");
 
     while let Ok(packet) = cap.next_packet() {
-        let data = packet.data;
-
-        if data.len() < 14 {
-            continue;
+        if let Some(line) = describe(linktype, packet.data) {
+            println!("{}", line);
         }
+    }
+
+    Ok(())
+}
 
-        let ethertype = u16::from_be_bytes([data[12], data[13]]);
+/// Decodes a .pcap or .pcapng file with the same per-packet output as `logs_network`.
+pub fn read_capture(path: &str) -> Result<()> {
+    let mut cap = Capture::from_file(path)
+        .with_context(|| format!("Failed to open capture file {}", path))?;
+    let linktype = cap.get_datalink();
 
-        match ethertype {
-            0x0806 => {
-                println!("ARP packet detected");
-            }
-            0x0800 => {
-                let ip_header_start = 14;
-                if data.len() < ip_header_start + 20 {
-                    continue;
-                }
-
-                let protocol = data[ip_header_start + 9];
-                let src_ip = format!(
-                    "{}.{}.{}.{}",
-                    data[ip_header_start + 12],
-                    data[ip_header_start + 13],
-                    data[ip_header_start + 14],
-                    data[ip_header_start + 15]
-                );
-                let dst_ip = format!(
-                    "{}.{}.{}.{}",
-                    data[ip_header_start + 16],
-                    data[ip_header_start + 17],
-                    data[ip_header_start + 18],
-                    data[ip_header_start + 19]
-                );
-
-                let ip_header_length = (data[ip_header_start] & 0x0F) * 4;
-                let transport_start = ip_header_start + ip_header_length as usize;
-
-                match protocol {
-                    1 => {
-                        println!("ICMP packet from {} to {}", src_ip, dst_ip);
-                    }
-                    6 => {
-                        if data.len() >= transport_start + 4 {
-                            let src_port = u16::from_be_bytes([
-                                data[transport_start],
-                                data[transport_start + 1],
-                            ]);
-                            let dst_port = u16::from_be_bytes([
-                                data[transport_start + 2],
-                                data[transport_start + 3],
-                            ]);
-                            println!(
-                                "TCP packet from {}:{} to {}:{}",
-                                src_ip, src_port, dst_ip, dst_port
-                            );
-                        }
-                    }
-                    17 => {
-                        if data.len() >= transport_start + 4 {
-                            let src_port = u16::from_be_bytes([
-                                data[transport_start],
-                                data[transport_start + 1],
-                            ]);
-                            let dst_port = u16::from_be_bytes([
-                                data[transport_start + 2],
-                                data[transport_start + 3],
-                            ]);
-                            println!(
-                                "UDP packet from {}:{} to {}:{}",
-                                src_ip, src_port, dst_ip, dst_port
-                            );
-                        }
-                    }
-                    _ => {
-                        println!("IPv4 packet with unknown protocol ({})", protocol);
-                    }
-                }
+    println!("Capture Report for '{}':", path);
+    println!("{:<18}{}", "Link type:", linktype.get_name().unwrap_or_else(|_| linktype.0.to_string()));
+    if !is_supported(linktype) {
+        println!("Warning: unsupported link type, packets will be counted but not decoded");
+    }
+    println!();
+
+    let (total, decoded) = decode_packets(&mut cap, linktype, |line| println!("{}", line));
+    println!("\n{} packet(s) read, {} decoded", total, decoded);
+    Ok(())
+}
+
+/// Hands one timestamped line per decoded packet to `line`; returns the packets read and decoded.
+fn decode_packets(cap: &mut Capture<Offline>, linktype: Linktype, mut line: impl FnMut(String)) -> (u64, u64) {
+    let mut total = 0u64;
+    let mut decoded = 0u64;
+    loop {
+        let packet = match cap.next_packet() {
+            Ok(packet) => packet,
+            Err(pcap::Error::NoMorePackets) => break,
+            Err(e) => {
+                println!("Warning: stopped reading after {} packets: {}", total, e);
+                break;
             }
-            0x86DD => {
-                println!("IPv6 packet (not parsed in detail)");
+        };
+        total += 1;
+        if let Some(description) = describe(linktype, packet.data) {
+            decoded += 1;
+            let ts = &packet.header.ts;
+            line(format!("{} {}", packet_time(ts.tv_sec as i64, ts.tv_usec as i64), description));
+        }
+    }
+    (total, decoded)
+}
+
+/// Record timestamps come from the file; corrupt ones print as "-".
+fn packet_time(secs: i64, micros: i64) -> String {
+    u32::try_from(micros).ok()
+        .filter(|&us| us < 1_000_000)
+        .and_then(|us| DateTime::from_timestamp(secs, us * 1000))
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S%.6f").to_string())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn is_supported(linktype: Linktype) -> bool {
+    matches!(linktype.0, 0 | 1 | 12 | 101 | 113 | 228 | 229)
+}
+
+/// Strips the link-layer header and returns the EtherType and the network-layer bytes.
+fn link_payload(linktype: Linktype, data: &[u8]) -> Option<(u16, &[u8])> {
+    let ethertype_at = |at: usize, start: usize| {
+        data.get(start..).map(|rest| (u16::from_be_bytes([data[at], data[at + 1]]), rest))
+    };
+    match linktype.0 {
+        // Ethernet
+        1 if data.len() >= 14 => ethertype_at(12, 14),
+        // Linux cooked capture (tcpdump -i any)
+        113 if data.len() >= 16 => ethertype_at(14, 16),
+        // BSD loopback, the family is in host byte order
+        0 if data.len() >= 4 => {
+            let family = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+            let family = if family > 0xFFFF { family.swap_bytes() } else { family };
+            let ethertype = if family == 2 { 0x0800 } else { 0x86DD };
+            Some((ethertype, &data[4..]))
+        }
+        // Raw IP, the version nibble tells IPv4 from IPv6
+        12 | 101 | 228 | 229 if !data.is_empty() => {
+            let ethertype = if data[0] >> 4 == 6 { 0x86DD } else { 0x0800 };
+            Some((ethertype, data))
+        }
+        _ => None,
+    }
+}
+
+/// One line describing the packet, or None when it is too short to decode.
+fn describe(linktype: Linktype, data: &[u8]) -> Option<String> {
+    let (ethertype, data) = link_payload(linktype, data)?;
+
+    match ethertype {
+        0x0806 => Some("ARP packet detected".to_string()),
+        0x0800 => {
+            if data.len() < 20 {
+                return None;
             }
-            _ => {
-                println!("Unknown EtherType: 0x{:04x}", ethertype);
+
+            let protocol = data[9];
+            let src_ip = format!("{}.{}.{}.{}", data[12], data[13], data[14], data[15]);
+            let dst_ip = format!("{}.{}.{}.{}", data[16], data[17], data[18], data[19]);
+
+            let ip_header_length = (data[0] & 0x0F) * 4;
+            let transport_start = ip_header_length as usize;
+            let ports = || {
+                let t = data.get(transport_start..transport_start + 4)?;
+                Some((u16::from_be_bytes([t[0], t[1]]), u16::from_be_bytes([t[2], t[3]])))
+            };
+
+            match protocol {
+                1 => Some(format!("ICMP packet from {} to {}", src_ip, dst_ip)),
+                6 => ports().map(|(src_port, dst_port)| {
+                    format!("TCP packet from {}:{} to {}:{}", src_ip, src_port, dst_ip, dst_port)
+                }),
+                17 => ports().map(|(src_port, dst_port)| {
+                    format!("UDP packet from {}:{} to {}:{}", src_ip, src_port, dst_ip, dst_port)
+                }),
+                _ => Some(format!("IPv4 packet with unknown protocol ({})", protocol)),
             }
         }
+        0x86DD => Some("IPv6 packet (not parsed in detail)".to_string()),
+        _ => Some(format!("Unknown EtherType: 0x{:04x}", ethertype)),
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/samples/sample.pcap");
+
+    fn ipv4(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0, 192, 168, 1, 10, 10, 0, 0, 1];
+        packet.extend_from_slice(payload);
+        packet
+    }
+
+    fn tcp() -> Vec<u8> {
+        ipv4(6, &[0xC7, 0x38, 0x01, 0xBB, 0, 0, 0, 0])
+    }
+
+    fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0; 12];
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[test]
+    fn ethernet_frames() {
+        let ethernet_type = Linktype(1);
+        assert_eq!(describe(ethernet_type, &ethernet(0x0800, &tcp())).unwrap(), "TCP packet from 192.168.1.10:51000 to 10.0.0.1:443");
+        assert_eq!(describe(ethernet_type, &ethernet(0x0800, &ipv4(17, &[0, 53, 0xCF, 0x08]))).unwrap(), "UDP packet from 192.168.1.10:53 to 10.0.0.1:53000");
+        assert_eq!(describe(ethernet_type, &ethernet(0x0800, &ipv4(1, &[8, 0]))).unwrap(), "ICMP packet from 192.168.1.10 to 10.0.0.1");
+        assert_eq!(describe(ethernet_type, &ethernet(0x0800, &ipv4(47, &[]))).unwrap(), "IPv4 packet with unknown protocol (47)");
+        assert_eq!(describe(ethernet_type, &ethernet(0x0806, &[0; 28])).unwrap(), "ARP packet detected");
+        assert_eq!(describe(ethernet_type, &ethernet(0x86DD, &[0x60; 40])).unwrap(), "IPv6 packet (not parsed in detail)");
+        assert_eq!(describe(ethernet_type, &ethernet(0x88CC, &[])).unwrap(), "Unknown EtherType: 0x88cc");
+    }
+
+    #[test]
+    fn linux_cooked_capture() {
+        let mut frame = vec![0, 0, 0, 1, 0, 6, 0, 0x11, 0x22, 0x33, 0x44, 0x55, 0, 0, 0x08, 0x00];
+        frame.extend_from_slice(&tcp());
+        assert_eq!(describe(Linktype(113), &frame).unwrap(), "TCP packet from 192.168.1.10:51000 to 10.0.0.1:443");
+    }
+
+    #[test]
+    fn bsd_loopback_either_byte_order() {
+        for family in [[2, 0, 0, 0], [0, 0, 0, 2]] {
+            let mut frame = family.to_vec();
+            frame.extend_from_slice(&tcp());
+            assert_eq!(describe(Linktype(0), &frame).unwrap(), "TCP packet from 192.168.1.10:51000 to 10.0.0.1:443");
+        }
+        // AF_INET6 differs between BSDs (24, 28, 30); anything but AF_INET is treated as IPv6.
+        assert_eq!(link_payload(Linktype(0), &[30, 0, 0, 0, 0x60]).unwrap().0, 0x86DD);
+    }
+
+    #[test]
+    fn raw_ip() {
+        for linktype in [12, 101, 228, 229] {
+            assert_eq!(describe(Linktype(linktype), &tcp()).unwrap(), "TCP packet from 192.168.1.10:51000 to 10.0.0.1:443");
+            assert_eq!(describe(Linktype(linktype), &[0x60; 40]).unwrap(), "IPv6 packet (not parsed in detail)");
+        }
+    }
+
+    #[test]
+    fn truncated_packets() {
+        assert!(describe(Linktype(1), &[0; 13]).is_none());
+        assert!(describe(Linktype(113), &[0; 15]).is_none());
+        assert!(describe(Linktype(0), &[2, 0, 0]).is_none());
+        assert!(describe(Linktype(12), &[]).is_none());
+        // IPv4 header cut short, then a TCP header without its ports.
+        assert!(describe(Linktype(1), &ethernet(0x0800, &tcp()[..19])).is_none());
+        assert!(describe(Linktype(1), &ethernet(0x0800, &tcp()[..22])).is_none());
+        assert!(describe(Linktype(147), &tcp()).is_none());
+    }
+
+    fn decode_file(path: &str) -> (Vec<String>, u64, u64) {
+        let mut cap = Capture::from_file(path).unwrap();
+        let linktype = cap.get_datalink();
+        let mut lines = vec![];
+        let (total, decoded) = decode_packets(&mut cap, linktype, |line| lines.push(line));
+        (lines, total, decoded)
+    }
+
+    #[test]
+    fn decodes_sample_capture() {
+        let (lines, total, decoded) = decode_file(SAMPLE);
+        assert_eq!(lines, [
+            "2023-11-14 22:13:20.000000 TCP packet from 192.168.1.10:51000 to 10.0.0.1:443",
+            "2023-11-14 22:13:21.001000 UDP packet from 192.168.1.10:53000 to 10.0.0.1:53",
+            "2023-11-14 22:13:22.002000 ICMP packet from 192.168.1.10 to 10.0.0.1",
+            "2023-11-14 22:13:23.003000 ARP packet detected",
+            "2023-11-14 22:13:24.004000 IPv6 packet (not parsed in detail)",
+        ]);
+        // The last packet carries a truncated IPv4 header.
+        assert_eq!((total, decoded), (6, 5));
+        read_capture(SAMPLE).unwrap();
+    }
+
+    #[test]
+    fn truncated_capture_keeps_complete_packets() {
+        let data = std::fs::read(SAMPLE).unwrap();
+        let path = std::env::temp_dir().join(format!("rex-truncated-{}.pcap", std::process::id()));
+        std::fs::write(&path, &data[..data.len() - 10]).unwrap();
+        let (lines, total, decoded) = decode_file(path.to_str().unwrap());
+        let result = read_capture(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!((lines.len(), total, decoded), (5, 5, 5));
+        result.unwrap();
+    }
+
+    #[test]
+    fn corrupt_timestamps() {
+        assert_eq!(packet_time(1_700_000_000, 999_999), "2023-11-14 22:13:20.999999");
+        assert_eq!(packet_time(1_700_000_000, 5_000_000), "-");
+        assert_eq!(packet_time(1_700_000_000, -1), "-");
+        assert_eq!(packet_time(i64::MAX, 0), "-");
+    }
+
+    #[test]
+    fn missing_capture_fails() {
+        assert!(read_capture("samples/missing.pcap").is_err());
+    }
 }
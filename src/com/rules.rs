@@ -0,0 +1,157 @@
+use anyhow::{bail, Context, Result};
+use std::path::Path;
+use crate::helper::{file_info, yara_eval, yara_parse};
+
+// Only the first hits of each string are printed, the count covers the rest.
+const SHOWN_HITS: usize = 3;
+
+pub struct RuleSet {
+    pub source: String,
+    pub namespaces: Vec<yara_eval::Namespace>,
+}
+
+impl RuleSet {
+    pub fn rule_count(&self) -> usize {
+        self.namespaces.iter().map(|ns| ns.rules.len()).sum()
+    }
+}
+
+/// Loads every .yar/.yara file under `path` (or `path` itself when it is a file).
+pub fn load(path: &str) -> Result<RuleSet> {
+    let root = Path::new(path);
+    let files = file_info::collect_files(root)?;
+    let mut namespaces = vec![];
+    for file in files {
+        let is_rule_file = file.extension()
+            .map(|e| e.eq_ignore_ascii_case("yar") || e.eq_ignore_ascii_case("yara"))
+            .unwrap_or(false);
+        if !is_rule_file && file != root {
+            continue;
+        }
+        let name = file.display().to_string();
+        let source = match std::fs::read(&file) {
+            Ok(data) => String::from_utf8_lossy(&data).to_string(),
+            Err(e) => {
+                println!("Warning: cannot read {}: {}", name, e);
+                continue;
+            }
+        };
+        let parsed = match yara_parse::parse(&source) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                println!("Warning: skipping {}: {:#}", name, e);
+                continue;
+            }
+        };
+        let (namespace, warnings) = yara_eval::compile(&name, parsed);
+        for warning in warnings {
+            println!("Warning: {}", warning);
+        }
+        namespaces.push(namespace);
+    }
+
+    let set = RuleSet { source: path.to_string(), namespaces };
+    if set.rule_count() == 0 {
+        bail!("No usable rules found in {}", path);
+    }
+    Ok(set)
+}
+
+pub fn scan(rules_path: &str, path: &str) -> Result<()> {
+    let rules = load(rules_path)?;
+    let files = file_info::collect_files(Path::new(path))?;
+    println!(
+        "Rule Scan Report for '{}': {} rule(s) from '{}', {} file(s)",
+        path, rules.rule_count(), rules.source, files.len()
+    );
+    let report = scan_files(&rules, &files);
+    print!("{}", report);
+    Ok(())
+}
+
+/// Scans what a carve or PDF extraction wrote and saves the report next to it as rule_matches.txt.
+pub fn scan_output(rules: &RuleSet, output_dir: &str, skip_dirs: &[&str]) -> Result<()> {
+    let root = Path::new(output_dir);
+    let files: Vec<_> = file_info::collect_files(root)?
+        .into_iter()
+        .filter(|f| {
+            let top = f.strip_prefix(root).ok().and_then(|rel| rel.iter().next());
+            top.is_some_and(|top| top != "rule_matches.txt" && !skip_dirs.iter().any(|d| top == *d))
+        })
+        .collect();
+    println!("\nScanning {} extracted file(s) with {} rule(s) from '{}'", files.len(), rules.rule_count(), rules.source);
+    let report = scan_files(rules, &files);
+    print!("{}", report);
+    let report_path = format!("{}/rule_matches.txt", output_dir);
+    std::fs::write(&report_path, report).with_context(|| format!("Failed to write {}", report_path))?;
+    Ok(())
+}
+
+fn scan_files(rules: &RuleSet, files: &[std::path::PathBuf]) -> String {
+    let mut out = String::new();
+    let mut matched_files = 0;
+    for file in files {
+        let data = match std::fs::read(file) {
+            Ok(data) => data,
+            Err(e) => {
+                println!("Warning: cannot read {}: {}", file.display(), e);
+                continue;
+            }
+        };
+        let matches = yara_eval::scan(&rules.namespaces, &data);
+        if matches.is_empty() {
+            continue;
+        }
+        matched_files += 1;
+        out.push_str(&format!("\n=== {} ===\n", file.display()));
+        for m in &matches {
+            out.push_str(&render_match(m));
+        }
+    }
+    out.push_str(&format!("\n{} of {} file(s) matched\n", matched_files, files.len()));
+    out
+}
+
+fn render_match(m: &yara_eval::RuleMatch) -> String {
+    let mut out = String::new();
+    let tags = if m.tags.is_empty() { String::new() } else { format!(" [{}]", m.tags.join(", ")) };
+    out.push_str(&format!("  {}{} ({})\n", m.rule, tags, m.namespace));
+    if let Some((_, description)) = m.meta.iter().find(|(k, _)| k == "description") {
+        out.push_str(&format!("    {}\n", description));
+    }
+
+    let mut ids: Vec<&str> = m.hits.iter().map(|h| h.id.as_str()).collect();
+    ids.dedup();
+    for id in ids {
+        let hits: Vec<_> = m.hits.iter().filter(|h| h.id == id).collect();
+        for hit in hits.iter().take(SHOWN_HITS) {
+            out.push_str(&format!("    {} at 0x{:x}: {}\n", hit.id, hit.offset, preview(&hit.data, hit.length)));
+        }
+        if hits.len() > SHOWN_HITS {
+            out.push_str(&format!("    ... {} more match(es) for {}\n", hits.len() - SHOWN_HITS, id));
+        }
+    }
+    out
+}
+
+/// Quoted text when the match is printable (UTF-16LE included), hex bytes otherwise.
+fn preview(data: &[u8], length: usize) -> String {
+    let printable = |b: &u8| (0x20..0x7F).contains(b);
+    let wide = data.len() >= 2 && data.chunks(2).all(|c| printable(&c[0]) && c.get(1).is_none_or(|&b| b == 0));
+    let text: Option<String> = if data.iter().all(printable) {
+        Some(data.iter().map(|&b| b as char).collect())
+    } else if wide {
+        Some(data.iter().step_by(2).map(|&b| b as char).collect())
+    } else {
+        None
+    };
+    let more = if length > data.len() { "..." } else { "" };
+    match text {
+        Some(text) => format!("\"{}\"{}", text, more),
+        None => {
+            let hex: Vec<String> = data.iter().take(16).map(|b| format!("{:02x}", b)).collect();
+            let more = if length > 16 { " ..." } else { "" };
+            format!("{}{}", hex.join(" "), more)
+        }
+    }
+}
@@ -1,10 +1,18 @@
-use anyhow::{Result, Context};
+use anyhow::{bail, Result, Context};
 use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use indicatif::{ProgressBar, ProgressStyle};
-use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::IpAddr;
+use trust_dns_resolver::TokioAsyncResolver;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use crate::helper::{export, ssl_cert, ssl_check, ssl_ciphers, ssl_file, ssl_live, ui};
+use crate::helper::export::Format;
+use crate::helper::ssl_starttls::Starttls;
 
-#[derive(Debug, Deserialize)]
+const CRT_SH_URL: &str = "https://crt.sh";
+
+#[derive(Debug, Deserialize, Serialize)]
 pub struct CertificateEntry {
     pub common_name: Option<String>,
     pub entry_timestamp: String,
@@ -18,7 +26,20 @@ pub struct CertificateEntry {
     pub serial_number: String,
 }
 
-pub async fn dump(domain: &str) -> Result<()> {
+pub async fn dump(
+    domain: &str,
+    format: &str,
+    output: Option<&str>,
+    issuer: Option<&str>,
+    since: Option<&str>,
+    exclude_expired: bool,
+) -> Result<()> {
+    let format = Format::parse(format)?;
+    let since = since
+        .map(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .with_context(|| format!("Invalid --since date '{}' (expected YYYY-MM-DD)", s)))
+        .transpose()?;
+
     let spinner_style = ProgressStyle::with_template("{prefix:.bold.dim} {spinner} {wide_msg}")
         .expect("static progress template")
         .tick_chars("⠁⠂⠄⡀⢀⠠⠐⠈ ");
@@ -27,40 +48,508 @@ pub async fn dump(domain: &str) -> Result<()> {
     pb.set_prefix(format!("[{}/?]", 1));
     pb.set_message(format!("Loading certificates for {}", domain));
 
-    let certs = fetch_certificates(domain).await
+    let mut certs = fetch_certificates(domain).await
         .context("Failed to fetch certificates")?;
 
     pb.finish_with_message("done");
-    println!("Found {} certificates:", certs.len());
+
+    let now = Utc::now().naive_utc();
+    let issuer = issuer.map(|i| i.to_lowercase());
+    certs.retain(|cert| {
+        if let Some(issuer) = &issuer {
+            if !cert.issuer_name.to_lowercase().contains(issuer) {
+                return false;
+            }
+        }
+        if let Some(since) = since {
+            match parse_ct_time(&cert.entry_timestamp) {
+                Some(ts) if ts.date() >= since => {}
+                _ => return false,
+            }
+        }
+        if exclude_expired {
+            match parse_ct_time(&cert.not_after) {
+                Some(ts) if ts >= now => {}
+                _ => return false,
+            }
+        }
+        true
+    });
+
+    // Structured exports keep every CT entry; the text view collapses identical name sets.
+    let content = if format == Format::Text {
+        certs.sort_by(|a, b| a.name_value.cmp(&b.name_value));
+        certs.dedup_by(|a, b| a.name_value == b.name_value);
+        let mut out = format!("Found {} certificates:\n", certs.len());
+        for cert in &certs {
+            out.push_str(&format!(
+                "- [{}] {}\n",
+                cert.entry_timestamp,
+                cert.name_value.replace("\n", ", ")
+            ));
+        }
+        out
+    } else {
+        certs.sort_by(|a, b| a.entry_timestamp.cmp(&b.entry_timestamp));
+        export::render(format, &certs)?
+    };
+
+    export::write_output(output, &content)
+}
+
+pub fn dump_live(host: &str, port: Option<u16>, starttls: Option<&str>) -> Result<()> {
+    let (port, starttls) = live_target(port, starttls)?;
+    println!("Connecting to {}:{}...", host, port);
+    let handshake = ssl_live::fetch_chain(host, port, starttls)?;
+
+    println!("Protocol: {}", handshake.protocol);
+    println!("Cipher:   {}", handshake.cipher);
+    println!("Server presented {} certificate(s):\n", handshake.chain.len());
+    for (i, der) in handshake.chain.iter().enumerate() {
+        match ssl_cert::parse_der(der) {
+            Ok(cert) => ssl_cert::print_cert(i, &cert),
+            Err(e) => eprintln!("Certificate #{}: {}", i, e),
+        }
+        println!();
+    }
+    Ok(())
+}
+
+pub fn check(host: &str, port: Option<u16>, starttls: Option<&str>, expiry_days: i64) -> Result<()> {
+    let (port, starttls) = live_target(port, starttls)?;
+    println!("Checking certificate chain served by {}:{}\n", host, port);
+    let handshake = ssl_live::fetch_chain(host, port, starttls)?;
+
+    println!("Chain:");
+    for (i, der) in handshake.chain.iter().enumerate() {
+        match ssl_cert::parse_der(der) {
+            Ok(cert) => println!("  #{} {} (issuer: {})", i, cert.subject, cert.issuer),
+            Err(e) => println!("  #{} <unparseable: {}>", i, e),
+        }
+    }
+    println!();
+
+    let findings = ssl_check::check_chain(host, &handshake.chain, expiry_days)?;
+    ssl_check::print_findings(host, &findings);
+    Ok(())
+}
+
+fn live_target(port: Option<u16>, starttls: Option<&str>) -> Result<(u16, Option<Starttls>)> {
+    let starttls = starttls.map(Starttls::parse).transpose()?;
+    let port = port.unwrap_or_else(|| starttls.map(|s| s.default_port()).unwrap_or(443));
+    Ok((port, starttls))
+}
+
+pub fn ciphers(host: &str, port: u16) -> Result<()> {
+    let pb = ui::create_loading_bar();
+    pb.set_message(format!("Enumerating protocols and cipher suites on {}:{}", host, port));
+    let results = ssl_ciphers::scan(host, port)?;
+    pb.finish_with_message("done");
+
+    println!("Protocols:");
+    for r in &results {
+        let status = match (&r.error, r.suites.is_empty()) {
+            (Some(error), true) => format!("error: {}", error),
+            (Some(error), false) => format!("accepted (probing stopped early: {})", error),
+            (None, true) => "not accepted".to_string(),
+            (None, false) => "accepted".to_string(),
+        };
+        println!("  {:<8} {}", r.protocol.name(), status);
+    }
+    println!();
+
+    for r in results.iter().filter(|r| !r.suites.is_empty()) {
+        println!("{} cipher suites (server order):", r.protocol.name());
+        for suite in &r.suites {
+            let fs = if suite.forward_secrecy() { "" } else { "  (no forward secrecy)" };
+            println!("  [{:<8}] 0x{:04x} {}{}", suite.grade().label(), suite.id, suite.name, fs);
+        }
+        println!();
+    }
+
+    let findings = ssl_ciphers::findings(&results);
+    ssl_check::print_findings(&format!("{}:{}", host, port), &findings);
+    Ok(())
+}
+
+pub fn inspect(path: &str, password: &str) -> Result<()> {
+    let data = std::fs::read(path)
+        .with_context(|| format!("Failed to read {}", path))?;
+    let decoded = ssl_file::decode(&data, password)?;
+
+    println!("File:   {}", path);
+    println!("Format: {}", decoded.format);
+    if decoded.has_private_key {
+        println!("Warning: file also contains a private key.");
+    }
+    println!("Found {} certificate(s):\n", decoded.certs.len());
+    for (i, der) in decoded.certs.iter().enumerate() {
+        match ssl_cert::parse_der(der) {
+            Ok(cert) => ssl_cert::print_cert(i, &cert),
+            Err(e) => eprintln!("Certificate #{}: {}", i, e),
+        }
+        println!();
+    }
+    Ok(())
+}
+
+struct Asset {
+    wildcard: bool,
+    issuers: BTreeSet<String>,
+    first_seen: String,
+    last_seen: String,
+    certs: usize,
+    addresses: Vec<IpAddr>,
+}
+
+pub async fn subdomains(domain: &str, resolve: bool) -> Result<()> {
+    let pb = ui::create_loading_bar();
+    pb.set_message(format!("Loading certificates for {}", domain));
+    let certs = fetch_certificates(domain).await
+        .context("Failed to fetch certificates")?;
+    pb.finish_with_message("done");
+
+    let mut assets = collect_assets(domain, &certs);
+
+    if resolve {
+        let pb = ui::create_loading_bar();
+        pb.set_message(format!("Resolving {} hostnames", assets.len()));
+        resolve_assets(&mut assets).await?;
+        pb.finish_with_message("done");
+    }
+
+    println!("Found {} unique hostnames across {} certificates\n", assets.len(), certs.len());
+
+    let mut by_issuer: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (name, asset) in &assets {
+        for issuer in &asset.issuers {
+            by_issuer.entry(issuer.as_str()).or_default().push(name.as_str());
+        }
+    }
+
+    for (issuer, names) in by_issuer {
+        println!("Issuer: {}", issuer);
+        for name in names {
+            let asset = &assets[name];
+            let label = if asset.wildcard { format!("{} [wildcard]", name) } else { name.to_string() };
+            print!(
+                "  - {:<40} first seen {}, last seen {}, {} cert(s)",
+                label,
+                date_part(&asset.first_seen),
+                date_part(&asset.last_seen),
+                asset.certs
+            );
+            if resolve {
+                if asset.addresses.is_empty() {
+                    print!(" -> unresolved");
+                } else {
+                    let addrs: Vec<String> = asset.addresses.iter().map(|a| a.to_string()).collect();
+                    print!(" -> {}", addrs.join(", "));
+                }
+            }
+            println!();
+        }
+        println!();
+    }
+
+    if resolve {
+        let live = assets.values().filter(|a| !a.addresses.is_empty()).count();
+        println!("{} of {} hostnames resolve to an address.", live, assets.len());
+    }
+    Ok(())
+}
+
+fn collect_assets(domain: &str, certs: &[CertificateEntry]) -> BTreeMap<String, Asset> {
+    let domain = domain.trim_start_matches("%.").trim_start_matches("*.").to_lowercase();
+    let mut assets: BTreeMap<String, Asset> = BTreeMap::new();
+
     for cert in certs {
+        let mut seen_in_cert = BTreeSet::new();
+        for raw in cert.name_value.split('\n') {
+            let name = raw.trim().trim_end_matches('.').to_lowercase();
+            if name.is_empty() || name.contains('@') {
+                continue;
+            }
+            let (host, wildcard) = match name.strip_prefix("*.") {
+                Some(base) => (base.to_string(), true),
+                None => (name, false),
+            };
+            if host != domain && !host.ends_with(&format!(".{}", domain)) {
+                continue;
+            }
+            if !seen_in_cert.insert(host.clone()) {
+                continue;
+            }
+
+            let asset = assets.entry(host).or_insert_with(|| Asset {
+                wildcard: false,
+                issuers: BTreeSet::new(),
+                first_seen: cert.not_before.clone(),
+                last_seen: cert.entry_timestamp.clone(),
+                certs: 0,
+                addresses: vec![],
+            });
+            asset.wildcard |= wildcard;
+            asset.issuers.insert(cert.issuer_name.clone());
+            asset.certs += 1;
+            if cert.not_before < asset.first_seen {
+                asset.first_seen = cert.not_before.clone();
+            }
+            if cert.entry_timestamp > asset.last_seen {
+                asset.last_seen = cert.entry_timestamp.clone();
+            }
+        }
+    }
+    assets
+}
+
+async fn resolve_assets(assets: &mut BTreeMap<String, Asset>) -> Result<()> {
+    let resolver = TokioAsyncResolver::tokio_from_system_conf()
+        .context("Failed to create DNS resolver")?;
+
+    let mut tasks = tokio::task::JoinSet::new();
+    for name in assets.keys() {
+        let resolver = resolver.clone();
+        let name = name.clone();
+        tasks.spawn(async move {
+            let addrs: Vec<IpAddr> = match resolver.lookup_ip(format!("{}.", name)).await {
+                Ok(lookup) => lookup.iter().collect(),
+                Err(_) => vec![],
+            };
+            (name, addrs)
+        });
+    }
+
+    while let Some(res) = tasks.join_next().await {
+        let (name, mut addrs) = res.context("DNS lookup task failed")?;
+        addrs.sort();
+        addrs.dedup();
+        if let Some(asset) = assets.get_mut(&name) {
+            asset.addresses = addrs;
+        }
+    }
+    Ok(())
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct WatchState {
+    domains: BTreeMap<String, DomainState>,
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct DomainState {
+    ids: BTreeSet<u64>,
+    issuers: BTreeSet<String>,
+    hostnames: BTreeSet<String>,
+    last_run: String,
+}
+
+pub async fn watch(domains: &[String], state_path: &str, interval: u64, once: bool, endpoint: &str) -> Result<()> {
+    loop {
+        let mut state = load_watch_state(state_path)?;
+        let mut failed = None;
+        for domain in domains {
+            let certs = match fetch_certificates_from(endpoint, domain).await {
+                Ok(certs) => certs,
+                // A transient crt.sh error must not end a long-running watch.
+                Err(e) if !once => {
+                    println!("Warning: failed to fetch certificates for {}: {:#}", domain, e);
+                    continue;
+                }
+                Err(e) => {
+                    failed = Some(e.context(format!("Failed to fetch certificates for {}", domain)));
+                    break;
+                }
+            };
+            let first_run = !state.domains.contains_key(domain);
+            let entry = state.domains.entry(domain.clone()).or_default();
+            report_watch_diff(domain, &certs, entry, first_run);
+            entry.last_run = Utc::now().format("%Y-%m-%dT%H:%M:%S").to_string();
+        }
+        save_watch_state(state_path, &state)?;
+
+        if once {
+            return failed.map_or(Ok(()), Err);
+        }
+        println!("Next check in {} seconds...\n", interval);
+        tokio::time::sleep(std::time::Duration::from_secs(interval)).await;
+    }
+}
+
+fn report_watch_diff(domain: &str, certs: &[CertificateEntry], state: &mut DomainState, first_run: bool) {
+    let mut new_entries = Vec::new();
+    let mut new_issuers = BTreeSet::new();
+    let mut new_hostnames = BTreeSet::new();
+
+    for cert in certs {
+        if state.ids.insert(cert.id) {
+            new_entries.push(cert);
+        }
+        if state.issuers.insert(cert.issuer_name.clone()) {
+            new_issuers.insert(cert.issuer_name.clone());
+        }
+        for name in cert.name_value.split('\n') {
+            let name = name.trim().trim_end_matches('.').to_lowercase();
+            if !name.is_empty() && !name.contains('@') && state.hostnames.insert(name.clone()) {
+                new_hostnames.insert(name);
+            }
+        }
+    }
+
+    println!("[{}] {}", Utc::now().format("%Y-%m-%d %H:%M:%S"), domain);
+    if first_run {
         println!(
-            "- [{}] {}",
+            "  Baseline recorded: {} certificates, {} issuers, {} hostnames.\n",
+            state.ids.len(), state.issuers.len(), state.hostnames.len()
+        );
+        return;
+    }
+    if new_entries.is_empty() && new_issuers.is_empty() && new_hostnames.is_empty() {
+        println!("  No new certificates.\n");
+        return;
+    }
+
+    new_entries.sort_by(|a, b| a.entry_timestamp.cmp(&b.entry_timestamp));
+    println!("  {} new certificate(s):", new_entries.len());
+    for cert in new_entries {
+        println!(
+            "    - [{}] id={} issuer=\"{}\" {}",
             cert.entry_timestamp,
+            cert.id,
+            cert.issuer_name,
             cert.name_value.replace("\n", ", ")
         );
     }
-    Ok(())
+    if !new_issuers.is_empty() {
+        println!("  {} new issuer(s):", new_issuers.len());
+        for issuer in new_issuers {
+            println!("    - {}", issuer);
+        }
+    }
+    if !new_hostnames.is_empty() {
+        println!("  {} new hostname(s):", new_hostnames.len());
+        for name in new_hostnames {
+            println!("    - {}", name);
+        }
+    }
+    println!();
+}
+
+fn load_watch_state(path: &str) -> Result<WatchState> {
+    match std::fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse watch state {}", path)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(WatchState::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read watch state {}", path)),
+    }
+}
+
+fn save_watch_state(path: &str, state: &WatchState) -> Result<()> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+    }
+    std::fs::write(path, serde_json::to_string_pretty(state)?)
+        .with_context(|| format!("Failed to write watch state {}", path))
+}
+
+fn parse_ct_time(timestamp: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.f").ok()
+}
+
+fn date_part(timestamp: &str) -> &str {
+    timestamp.get(..10).unwrap_or(timestamp)
 }
 
 async fn fetch_certificates(domain: &str) -> Result<Vec<CertificateEntry>> {
-    let url = format!("https://crt.sh/?q={}&output=json", domain);
+    fetch_certificates_from(CRT_SH_URL, domain).await
+}
+
+async fn fetch_certificates_from(endpoint: &str, domain: &str) -> Result<Vec<CertificateEntry>> {
+    let url = format!("{}/?q={}&output=json", endpoint.trim_end_matches('/'), domain);
     let client = Client::new();
     let resp = client.get(&url).send().await
         .context("HTTP request to crt.sh failed")?;
 
-    if resp.status().is_success() {
-        let v: Value = resp.json().await
-            .context("Failed to parse JSON response body")?;
-        let mut certs: Vec<CertificateEntry> = serde_json::from_value(v)
-            .unwrap_or_else(|_| {
-                eprintln!("Failed to parse certificate entries");
-                vec![]
-            });
-        certs.sort_by(|a, b| a.name_value.cmp(&b.name_value));
-        certs.dedup_by(|a, b| a.name_value == b.name_value);
-        Ok(certs)
-    } else {
-        eprintln!("Failed to fetch certificates: {}", resp.status());
-        Ok(vec![])
+    let status = resp.status();
+    if !status.is_success() {
+        bail!("crt.sh returned {}", status);
+    }
+    resp.json().await.context("Failed to parse crt.sh certificate entries")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    /// Local crt.sh stand-in answering every request with the same status and body.
+    async fn serve(status: &'static str, body: String) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut request = vec![0; 4096];
+                let _ = socket.read(&mut request).await;
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                let _ = socket.write_all(response.as_bytes()).await;
+            }
+        });
+        endpoint
+    }
+
+    fn certificates(ids: &[u64]) -> String {
+        let entries: Vec<String> = ids.iter().map(|id| format!(
+            r#"{{"common_name":"www.example.com","entry_timestamp":"2024-01-0{id}T00:00:00.000","id":{id},"issuer_ca_id":1,"issuer_name":"C=US, O=Test CA {id}","name_value":"www{id}.example.com","not_after":"2025-01-01T00:00:00","not_before":"2024-01-01T00:00:00","result_count":1,"serial_number":"0{id}"}}"#
+        )).collect();
+        format!("[{}]", entries.join(","))
+    }
+
+    fn seen(state_path: &str) -> BTreeSet<u64> {
+        load_watch_state(state_path).unwrap().domains["example.com"].ids.clone()
+    }
+
+    #[tokio::test]
+    async fn watch_against_local_endpoint() {
+        let dir = std::env::temp_dir().join(format!("rex-watch-{}", std::process::id()));
+        let state_path = dir.join("state.json");
+        let state_path = state_path.to_str().unwrap();
+        let domains = vec!["example.com".to_string()];
+
+        // First run records the baseline.
+        let endpoint = serve("200 OK", certificates(&[1])).await;
+        watch(&domains, state_path, 0, true, &endpoint).await.unwrap();
+        assert_eq!(seen(state_path), BTreeSet::from([1]));
+
+        // A new certificate is added to the state.
+        let endpoint = serve("200 OK", certificates(&[1, 2])).await;
+        watch(&domains, state_path, 0, true, &endpoint).await.unwrap();
+        assert_eq!(seen(state_path), BTreeSet::from([1, 2]));
+        let mut state = load_watch_state(state_path).unwrap();
+        let entry = state.domains.get_mut("example.com").unwrap();
+        assert!(entry.hostnames.contains("www2.example.com"));
+
+        // Already-seen certificates change nothing.
+        let issuers = entry.issuers.clone();
+        watch(&domains, state_path, 0, true, &endpoint).await.unwrap();
+        assert_eq!(seen(state_path), BTreeSet::from([1, 2]));
+        assert_eq!(load_watch_state(state_path).unwrap().domains["example.com"].issuers, issuers);
+
+        // A crt.sh outage fails a --once run and leaves the state alone.
+        let endpoint = serve("502 Bad Gateway", "<html>Bad Gateway</html>".to_string()).await;
+        let error = watch(&domains, state_path, 0, true, &endpoint).await.unwrap_err();
+        assert!(format!("{:#}", error).contains("502"));
+        assert_eq!(seen(state_path), BTreeSet::from([1, 2]));
+
+        // So does a body that is not a certificate list.
+        let endpoint = serve("200 OK", "{\"error\":true}".to_string()).await;
+        assert!(watch(&domains, state_path, 0, true, &endpoint).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
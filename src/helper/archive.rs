@@ -0,0 +1,400 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use flate2::read::MultiGzDecoder;
+use std::io::Read;
+use crate::helper::{email, rar, tar, zip};
+
+// Deflate tops out around 1032:1; legitimate files rarely go past 100:1.
+const BOMB_RATIO: f64 = 100.0;
+const BOMB_TOTAL: u64 = 4 * 1024 * 1024 * 1024;
+// gzip payloads are inflated in memory to list the tar inside.
+const MAX_INFLATE: u64 = 512 * 1024 * 1024;
+const GZIP_MAGIC: &[u8] = &[0x1F, 0x8B];
+const SEVEN_ZIP_MAGIC: &[u8] = &[b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
+// 7zAES coder id.
+const SEVEN_ZIP_AES: &[u8] = &[0x06, 0xF1, 0x07, 0x01];
+// Containers that hide their content from mail gateways or drop Mark-of-the-Web.
+const NESTED: &[&str] = &["zip", "rar", "7z", "gz", "tar", "cab", "iso", "img", "vhd", "vhdx"];
+
+pub struct Item {
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub method: String,
+    pub encryption: Option<String>,
+    pub modified: Option<NaiveDateTime>,
+    pub is_dir: bool,
+    /// Symlink or hard link target.
+    pub link: Option<String>,
+    pub ratio: f64,
+    pub notes: Vec<String>,
+    pub source: Source,
+}
+
+/// Format-specific entry, kept for extraction.
+pub enum Source {
+    Zip(zip::Entry),
+    Tar(tar::Entry),
+    /// The whole inflated gzip payload.
+    Gzip,
+    /// Listed from its headers only (RAR).
+    Header,
+}
+
+impl Item {
+    /// Entries with content of their own; directories, links and devices have none.
+    pub fn has_content(&self) -> bool {
+        match &self.source {
+            Source::Tar(entry) => entry.is_file(),
+            _ => !self.is_dir,
+        }
+    }
+}
+
+pub struct Report {
+    /// ZIP, tar, gzip, tar.gz, RAR4, RAR5 or 7z.
+    pub format: &'static str,
+    pub items: Vec<Item>,
+    pub total_size: u64,
+    pub total_compressed: u64,
+    pub warnings: Vec<String>,
+    /// Inflated gzip payload that tar entries point into.
+    pub inflated: Option<Vec<u8>>,
+}
+
+impl Report {
+    fn new(format: &'static str) -> Self {
+        Report { format, items: vec![], total_size: 0, total_compressed: 0, warnings: vec![], inflated: None }
+    }
+
+    /// Only ZIP, tar and gzip entries can be decompressed; RAR and 7z are listed or identified.
+    pub fn extractable(&self) -> bool {
+        !matches!(self.format, "RAR4" | "RAR5" | "7z")
+    }
+}
+
+pub fn analyze(data: &[u8]) -> Result<Report> {
+    let mut report = if data.starts_with(GZIP_MAGIC) {
+        gzip(data)?
+    } else if data.starts_with(SEVEN_ZIP_MAGIC) {
+        seven_zip(data)
+    } else if rar::is_rar(data) {
+        rar_listing(data)
+    } else if tar::is_tar(data) {
+        tar_listing(data, "tar")
+    } else {
+        let entries = zip::entries(data)
+            .context("Unrecognized archive format (ZIP, tar, gzip and RAR are listed, 7z is identified only)")?;
+        zip_listing(entries)
+    };
+
+    let now = Utc::now().naive_utc();
+    for item in &mut report.items {
+        if item.ratio > BOMB_RATIO && item.size > 1024 * 1024 {
+            item.notes.push(format!("compression ratio {:.0}:1 (possible decompression bomb)", item.ratio));
+        }
+        if item.modified.is_some_and(|t| t > now) {
+            item.notes.push("timestamp in the future".to_string());
+        }
+        report.total_size = report.total_size.saturating_add(item.size);
+        report.total_compressed = report.total_compressed.saturating_add(item.compressed_size);
+    }
+    if report.inflated.is_some() {
+        report.total_compressed = data.len() as u64;
+    }
+
+    if report.total_size > BOMB_TOTAL {
+        report.warnings.push(format!("Declared uncompressed size is {} bytes (possible decompression bomb)", report.total_size));
+    }
+    let encrypted = report.items.iter().filter(|i| i.encryption.is_some()).count();
+    if encrypted > 0 {
+        report.warnings.push(format!("{} encrypted entr{}, content is hidden from scanners", encrypted, if encrypted == 1 { "y" } else { "ies" }));
+    }
+    for item in &report.items {
+        for note in &item.notes {
+            report.warnings.push(format!("'{}': {}", item.name, note));
+        }
+    }
+    Ok(report)
+}
+
+/// Reads an entry's content; RAR and 7z data is never decompressed.
+pub fn extract(data: &[u8], report: &Report, item: &Item, password: Option<&str>) -> Result<Vec<u8>> {
+    match &item.source {
+        Source::Zip(entry) => match (entry.is_encrypted(), password) {
+            (true, None) => bail!("'{}' is encrypted, pass --password to extract it", entry.name),
+            (_, Some(password)) => zip::read_with_password(data, entry, password.as_bytes()),
+            (false, None) => zip::read(data, entry),
+        },
+        Source::Tar(entry) => Ok(tar::read(report.inflated.as_deref().unwrap_or(data), entry).to_vec()),
+        Source::Gzip => Ok(report.inflated.clone().unwrap_or_default()),
+        Source::Header => bail!("Extracting {} entries is not supported", report.format),
+    }
+}
+
+/// Integrity notes for extracted content: CRC and size against what the archive declared.
+pub fn verify(item: &Item, content: &[u8]) -> Vec<String> {
+    let mut notes = vec![];
+    if let Source::Zip(entry) = &item.source {
+        // AE-2 zeroes the CRC and relies on the HMAC instead.
+        let crc_checked = entry.aes.is_none_or(|aes| aes.version != 2);
+        if crc_checked && zip::crc32(content) != entry.crc32 {
+            notes.push("CRC mismatch (corrupt entry or wrong password)".to_string());
+        }
+    }
+    if content.len() as u64 != item.size {
+        notes.push(format!("declared size {} but extracted {} bytes", item.size, content.len()));
+    }
+    notes
+}
+
+fn zip_listing(entries: Vec<zip::Entry>) -> Report {
+    let mut report = Report::new("ZIP");
+    // Overlapping-file bombs point many central directory entries at the same data.
+    let mut offsets: Vec<u64> = entries.iter().map(|e| e.local_header_offset).collect();
+    offsets.sort_unstable();
+    let shared = offsets.windows(2).filter(|w| w[0] == w[1]).count();
+    if shared > 0 {
+        report.warnings.push(format!("{} entries share their data with another entry (overlapping zip bomb)", shared));
+    }
+
+    for entry in entries {
+        let encryption = entry.is_encrypted().then(|| match &entry.aes {
+            Some(aes) => format!("AES-{}", aes.key_bits()),
+            None if entry.flags & 0x40 != 0 => "strong encryption".to_string(),
+            None => "ZipCrypto".to_string(),
+        });
+        let method = match &entry.aes {
+            Some(aes) => zip::method_name(aes.method),
+            None => zip::method_name(entry.method),
+        };
+        report.items.push(Item {
+            name: entry.name.clone(),
+            size: entry.size,
+            compressed_size: entry.compressed_size,
+            method: method.to_string(),
+            encryption,
+            modified: entry.modified,
+            is_dir: entry.is_dir(),
+            link: None,
+            ratio: ratio(entry.size, entry.compressed_size),
+            notes: name_notes(&entry.name),
+            source: Source::Zip(entry),
+        });
+    }
+    report
+}
+
+fn tar_listing(data: &[u8], format: &'static str) -> Report {
+    let mut report = Report::new(format);
+    let (entries, stopped) = tar::entries(data);
+    if let Some(reason) = stopped {
+        report.warnings.push(format!("Listing stopped early: {}", reason));
+    }
+    for entry in entries {
+        let mut notes = name_notes(&entry.name);
+        notes.extend(link_notes(entry.link.as_deref()));
+        if entry.mode & 0o6000 != 0 {
+            notes.push("setuid or setgid bit set".to_string());
+        }
+        if matches!(entry.kind, b'3' | b'4') {
+            notes.push(format!("{} node", entry.kind_name()));
+        }
+        // Only regular files occupy data; a link's size field is meaningless.
+        let size = if entry.is_file() { entry.size } else { 0 };
+        report.items.push(Item {
+            name: entry.name.clone(),
+            size,
+            compressed_size: size,
+            method: entry.kind_name().to_string(),
+            encryption: None,
+            modified: entry.modified,
+            is_dir: entry.is_dir(),
+            link: entry.link.clone(),
+            ratio: 1.0,
+            notes,
+            source: Source::Tar(entry),
+        });
+    }
+    report
+}
+
+fn gzip(data: &[u8]) -> Result<Report> {
+    let mut decoder = MultiGzDecoder::new(data);
+    let mut inflated = vec![];
+    let mut warnings = vec![];
+    if let Err(e) = decoder.by_ref().take(MAX_INFLATE).read_to_end(&mut inflated) {
+        if inflated.is_empty() {
+            return Err(e).context("Failed to decompress gzip data");
+        }
+        warnings.push(format!("gzip stream is corrupt after {} bytes: {}", inflated.len(), e));
+    }
+    if inflated.len() as u64 >= MAX_INFLATE {
+        warnings.push(format!("gzip stream inflates past the {} byte limit and was cut (possible gzip bomb)", MAX_INFLATE));
+    }
+    let expansion = ratio(inflated.len() as u64, data.len() as u64);
+
+    let mut report = if tar::is_tar(&inflated) {
+        // Tar entries are stored, so the stream as a whole carries the ratio.
+        if expansion > BOMB_RATIO && inflated.len() > 1024 * 1024 {
+            warnings.push(format!("gzip stream expands {:.0}:1 (possible gzip bomb)", expansion));
+        }
+        tar_listing(&inflated, "tar.gz")
+    } else {
+        let name = decoder.header()
+            .and_then(|h| h.filename())
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .unwrap_or_else(|| "unnamed".to_string());
+        let mut report = Report::new("gzip");
+        report.items.push(Item {
+            notes: name_notes(&name),
+            name,
+            size: inflated.len() as u64,
+            compressed_size: data.len() as u64,
+            method: "deflate".to_string(),
+            encryption: None,
+            modified: decoder.header()
+                .map(|h| h.mtime())
+                .filter(|&t| t > 0)
+                .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                .map(|t| t.naive_utc()),
+            is_dir: false,
+            link: None,
+            ratio: expansion,
+            source: Source::Gzip,
+        });
+        report
+    };
+    report.warnings.splice(0..0, warnings);
+    report.inflated = Some(inflated);
+    Ok(report)
+}
+
+fn rar_listing(data: &[u8]) -> Report {
+    let listing = rar::list(data);
+    let mut report = Report::new(if listing.version == 5 { "RAR5" } else { "RAR4" });
+    if listing.headers_encrypted {
+        report.warnings.push("Headers are encrypted, entry names and sizes are hidden without the password".to_string());
+    }
+    if let Some(reason) = listing.stopped {
+        report.warnings.push(format!("Listing stopped early: {}", reason));
+    }
+    for entry in listing.entries {
+        let mut notes = name_notes(&entry.name);
+        notes.extend(link_notes(entry.link.as_deref()));
+        report.items.push(Item {
+            notes,
+            ratio: ratio(entry.size, entry.compressed_size),
+            name: entry.name,
+            size: entry.size,
+            compressed_size: entry.compressed_size,
+            method: entry.method,
+            encryption: entry.encrypted.then(|| "RAR AES".to_string()),
+            modified: entry.modified,
+            is_dir: entry.is_dir,
+            link: entry.link,
+            source: Source::Header,
+        });
+    }
+    report
+}
+
+/// 7z keeps its file list in a (usually compressed) header at the end; only that header's
+/// encryption is checked, entries are not listed.
+fn seven_zip(data: &[u8]) -> Report {
+    let mut report = Report::new("7z");
+    report.warnings.push("7z entries are not listed, only the format and its encryption are identified".to_string());
+    let offset = data.get(12..20).map(|b| u64::from_le_bytes(b.try_into().expect("8 bytes")));
+    let size = data.get(20..28).map(|b| u64::from_le_bytes(b.try_into().expect("8 bytes")));
+    let header = offset.zip(size)
+        .and_then(|(offset, size)| Some((usize::try_from(offset).ok()?.checked_add(32)?, usize::try_from(size).ok()?)))
+        .and_then(|(start, size)| data.get(start..start.checked_add(size)?));
+    let Some(header) = header else {
+        report.warnings.push("Header lies past the end of the file (truncated archive)".to_string());
+        return report;
+    };
+    let aes = header.windows(SEVEN_ZIP_AES.len()).any(|w| w == SEVEN_ZIP_AES);
+    match header.first() {
+        // kEncodedHeader: the file list itself is packed, and encrypted with -mhe.
+        Some(0x17) if aes => report.warnings.push("Headers are AES-256 encrypted, entry names are hidden without the password".to_string()),
+        Some(_) if aes => report.warnings.push("Entries are AES-256 encrypted, content is hidden from scanners".to_string()),
+        _ => {}
+    }
+    report
+}
+
+fn ratio(size: u64, compressed_size: u64) -> f64 {
+    if compressed_size > 0 { size as f64 / compressed_size as f64 } else { 0.0 }
+}
+
+fn link_notes(link: Option<&str>) -> Vec<String> {
+    let Some(link) = link else { return vec![] };
+    let normalized = link.replace('\\', "/");
+    if normalized.starts_with('/') || normalized.as_bytes().get(1) == Some(&b':') || normalized.split('/').any(|p| p == "..") {
+        vec![format!("link target '{}' points outside the archive", link)]
+    } else {
+        vec![]
+    }
+}
+
+fn name_notes(name: &str) -> Vec<String> {
+    let mut notes = vec![];
+    let normalized = name.replace('\\', "/");
+    if normalized.split('/').any(|part| part == "..") {
+        notes.push("path traversal ('..' component)".to_string());
+    }
+    if normalized.starts_with('/') || normalized.as_bytes().get(1) == Some(&b':') {
+        notes.push("absolute path".to_string());
+    }
+    if name.chars().any(|c| c.is_control() || c == '\u{202e}') {
+        notes.push("control or right-to-left override character in the name".to_string());
+    }
+
+    let base = normalized.rsplit('/').next().unwrap_or_default().to_lowercase();
+    let parts: Vec<&str> = base.rsplit('.').collect();
+    let ext = parts.first().copied().unwrap_or_default();
+    if parts.len() > 2 && email::DANGEROUS_EXTENSIONS.contains(&ext) && parts[1].trim().len() <= 4 {
+        notes.push(format!("double extension '.{}.{}'", parts[1].trim(), ext));
+    } else if parts.len() > 1 && email::DANGEROUS_EXTENSIONS.contains(&ext) {
+        notes.push(format!("executable or script extension '.{}'", ext));
+    }
+    if base.contains("   ") {
+        notes.push("whitespace padding hides the real extension".to_string());
+    }
+    if parts.len() > 1 && NESTED.contains(&ext) {
+        notes.push(format!("nested .{} container", ext));
+    }
+    notes
+}
+
+pub fn print_report(path: &str, report: &Report) {
+    let time = |t: &Option<NaiveDateTime>| t.map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "-".to_string());
+
+    println!("Archive Report for '{}':", path);
+    println!("{:<18}{}", "Format:", report.format);
+    println!("{:<18}{}", "Entries:", report.items.len());
+    println!("{:<18}{} bytes", "Uncompressed:", report.total_size);
+    println!("{:<18}{} bytes", "Compressed:", report.total_compressed);
+
+    println!("\n=== Entries ===");
+    if report.items.is_empty() {
+        println!("None.");
+    }
+    for item in &report.items {
+        let encryption = item.encryption.as_ref().map(|enc| format!(", {}", enc)).unwrap_or_default();
+        println!("{}", item.name);
+        println!("  {:>12} -> {:>12} bytes  ratio {:>7.1}  {}{}  {}",
+            item.compressed_size, item.size, item.ratio, item.method, encryption, time(&item.modified));
+        if let Some(link) = &item.link {
+            println!("  link -> {}", link);
+        }
+    }
+
+    println!("\n=== Warnings ===");
+    if report.warnings.is_empty() {
+        println!("None.");
+    }
+    for warning in &report.warnings {
+        println!("Warning: {}", warning);
+    }
+}
@@ -0,0 +1,382 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use mail_parser::{Address, HeaderValue, Message, MessageParser, MimeHeaders};
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use crate::helper::{email_msg, hashes, ioc};
+
+/// Headers that carry authentication verdicts or signatures.
+const AUTH_HEADERS: &[&str] = &[
+    "Authentication-Results", "ARC-Authentication-Results", "ARC-Seal", "ARC-Message-Signature",
+    "DKIM-Signature", "Received-SPF", "X-MS-Exchange-Organization-AuthAs",
+];
+
+/// Attachment extensions that run code when opened on Windows.
+pub const DANGEROUS_EXTENSIONS: &[&str] = &[
+    "exe", "scr", "com", "pif", "bat", "cmd", "vbs", "vbe", "js", "jse", "wsf", "wsh", "hta", "ps1", "lnk",
+    "iso", "img", "vhd", "msi", "dll", "cpl", "jar", "one", "html", "htm", "svg", "xll", "docm", "xlsm",
+];
+
+// A delay above this between two hops is worth a look (queueing, or a forged header).
+const LONG_DELAY_SECS: i64 = 3600;
+
+pub struct Hop {
+    pub from: Option<String>,
+    pub from_ip: Option<String>,
+    pub by: Option<String>,
+    pub with: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    /// Seconds since the previous hop.
+    pub delay: Option<i64>,
+    pub raw: String,
+}
+
+pub struct Attachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+    pub hashes: hashes::Hashes,
+    pub note: Option<String>,
+}
+
+pub struct Body {
+    /// "text" or "html".
+    pub kind: &'static str,
+    pub content: String,
+}
+
+pub struct Email {
+    pub subject: Option<String>,
+    pub date: Option<DateTime<Utc>>,
+    pub message_id: Option<String>,
+    pub from: Option<String>,
+    pub reply_to: Option<String>,
+    pub return_path: Option<String>,
+    pub to: Vec<String>,
+    /// Oldest hop first.
+    pub hops: Vec<Hop>,
+    pub auth: Vec<(String, String)>,
+    /// spf/dkim/dmarc/arc verdicts found in Authentication-Results.
+    pub verdicts: Vec<(String, String)>,
+    pub dkim_domains: Vec<String>,
+    pub bodies: Vec<Body>,
+    pub attachments: Vec<Attachment>,
+    pub urls: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// Parses an RFC 5322 message, or an Outlook .msg after rebuilding it as one.
+pub fn parse(data: &[u8]) -> Result<Email> {
+    let converted;
+    let data = if email_msg::is_msg(data) {
+        converted = email_msg::to_rfc5322(data).context("Failed to read Outlook .msg")?;
+        &converted[..]
+    } else {
+        data
+    };
+    let message = MessageParser::default().parse(data).context("Failed to parse the message (not RFC 5322?)")?;
+
+    let mut email = Email {
+        subject: message.subject().map(str::to_string),
+        date: message.date().and_then(|d| DateTime::from_timestamp(d.to_timestamp(), 0)),
+        message_id: message.message_id().map(str::to_string),
+        from: first_address(message.from()),
+        reply_to: first_address(message.reply_to()),
+        return_path: message.return_address().map(|r| r.trim_matches(['<', '>']).to_string()).filter(|r| !r.is_empty()),
+        to: message.to().map(all_addresses).unwrap_or_default(),
+        hops: hops(&message),
+        auth: vec![],
+        verdicts: vec![],
+        dkim_domains: vec![],
+        bodies: vec![],
+        attachments: vec![],
+        urls: vec![],
+        warnings: vec![],
+    };
+
+    for (name, value) in message.headers_raw() {
+        if AUTH_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name)) {
+            let value = unfold(value);
+            if name.eq_ignore_ascii_case("Authentication-Results") || name.eq_ignore_ascii_case("ARC-Authentication-Results") {
+                for (method, result) in verdicts(&value) {
+                    if !email.verdicts.contains(&(method.clone(), result.clone())) {
+                        email.verdicts.push((method, result));
+                    }
+                }
+            }
+            if name.eq_ignore_ascii_case("DKIM-Signature") {
+                if let Some(domain) = tag(&value, "d") {
+                    email.dkim_domains.push(domain.to_ascii_lowercase());
+                }
+            }
+            email.auth.push((name.to_string(), value));
+        }
+    }
+
+    for part in message.text_bodies() {
+        if let Some(text) = part.text_contents() {
+            email.bodies.push(Body { kind: if part.is_text_html() { "html" } else { "text" }, content: text.to_string() });
+        }
+    }
+    for part in message.html_bodies() {
+        if let Some(html) = part.text_contents() {
+            if !email.bodies.iter().any(|b| b.content == html) {
+                email.bodies.push(Body { kind: "html", content: html.to_string() });
+            }
+        }
+    }
+
+    for part in message.attachments() {
+        let filename = part.attachment_name().unwrap_or("unnamed").to_string();
+        let content_type = part.content_type()
+            .map(|ct| match &ct.c_subtype {
+                Some(sub) => format!("{}/{}", ct.c_type, sub),
+                None => ct.c_type.to_string(),
+            })
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        // Forwarded messages come back as parsed sub-messages, keep their raw bytes.
+        let data = match part.message() {
+            Some(inner) => inner.raw_message().to_vec(),
+            None => part.contents().to_vec(),
+        };
+        email.attachments.push(Attachment {
+            note: attachment_note(&filename, &content_type, &data),
+            hashes: hashes::compute(&data),
+            filename,
+            content_type,
+            data,
+        });
+    }
+
+    email.urls = urls(&email.bodies);
+    email.warnings = mismatches(&email);
+    Ok(email)
+}
+
+fn first_address(address: Option<&Address>) -> Option<String> {
+    address.and_then(|a| a.first()).and_then(|a| a.address()).map(|a| a.to_ascii_lowercase())
+}
+
+fn all_addresses(address: &Address) -> Vec<String> {
+    address.iter().filter_map(|a| a.address()).map(str::to_string).collect()
+}
+
+fn hops(message: &Message) -> Vec<Hop> {
+    let raw = message.raw_message();
+    let mut hops: Vec<Hop> = message.headers().iter()
+        .filter(|h| h.name().eq_ignore_ascii_case("Received"))
+        .map(|h| {
+            let text = unfold(&String::from_utf8_lossy(raw.get(h.offset_start() as usize..h.offset_end() as usize).unwrap_or_default()));
+            match h.value() {
+                HeaderValue::Received(r) => Hop {
+                    from: r.from.as_ref().or(r.helo.as_ref()).map(|h| h.to_string()),
+                    from_ip: r.from_ip.map(|ip| ip.to_string()),
+                    by: r.by.as_ref().map(|h| h.to_string()),
+                    with: r.with.map(|w| w.to_string()),
+                    date: r.date.and_then(|d| DateTime::from_timestamp(d.to_timestamp(), 0)),
+                    delay: None,
+                    raw: text,
+                },
+                _ => Hop { from: None, from_ip: None, by: None, with: None, date: received_date(&text), delay: None, raw: text },
+            }
+        })
+        .collect();
+
+    // Each relay prepends its header, so the bottom one is the first hop.
+    hops.reverse();
+    for i in 1..hops.len() {
+        if let (Some(prev), Some(cur)) = (hops[i - 1].date, hops[i].date) {
+            hops[i].delay = Some((cur - prev).num_seconds());
+        }
+    }
+    hops
+}
+
+fn received_date(text: &str) -> Option<DateTime<Utc>> {
+    let date = text.rsplit(';').next()?.trim();
+    let date = date.split(" (").next().unwrap_or(date);
+    DateTime::parse_from_rfc2822(date).ok().map(|d| d.with_timezone(&Utc))
+}
+
+fn unfold(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn verdicts(value: &str) -> Vec<(String, String)> {
+    static RESULT: OnceLock<Regex> = OnceLock::new();
+    let result = RESULT.get_or_init(|| Regex::new(r"(?i)\b(spf|dkim|dmarc|arc|compauth)=([a-z]+)").expect("static regex"));
+    result.captures_iter(value).map(|c| (c[1].to_ascii_lowercase(), c[2].to_ascii_lowercase())).collect()
+}
+
+fn tag(value: &str, name: &str) -> Option<String> {
+    value.split(';')
+        .filter_map(|t| t.split_once('='))
+        .find(|(k, _)| k.trim() == name)
+        .map(|(_, v)| v.trim().to_string())
+}
+
+fn domain(address: &str) -> Option<&str> {
+    address.rsplit_once('@').map(|(_, d)| d.trim_end_matches('>'))
+}
+
+// Two domains are aligned when one is the other or a subdomain of it.
+fn aligned(a: &str, b: &str) -> bool {
+    let (a, b) = (a.to_ascii_lowercase(), b.to_ascii_lowercase());
+    a == b || a.ends_with(&format!(".{}", b)) || b.ends_with(&format!(".{}", a))
+}
+
+fn mismatches(email: &Email) -> Vec<String> {
+    let mut out = vec![];
+    let Some(from_domain) = email.from.as_deref().and_then(domain) else {
+        out.push("No From address".to_string());
+        return out;
+    };
+    if let Some(reply_to) = &email.reply_to {
+        if domain(reply_to).is_some_and(|d| !aligned(d, from_domain)) {
+            out.push(format!("Reply-To <{}> does not match From domain {}", reply_to, from_domain));
+        }
+    }
+    if let Some(return_path) = &email.return_path {
+        if domain(return_path).is_some_and(|d| !aligned(d, from_domain)) {
+            out.push(format!("Return-Path <{}> does not match From domain {}", return_path, from_domain));
+        }
+    }
+    if !email.dkim_domains.is_empty() && !email.dkim_domains.iter().any(|d| aligned(d, from_domain)) {
+        out.push(format!("DKIM signed by {} but From domain is {}", email.dkim_domains.join(", "), from_domain));
+    }
+    for (method, result) in &email.verdicts {
+        if matches!(result.as_str(), "fail" | "softfail" | "permerror" | "temperror" | "none") && method != "compauth" {
+            out.push(format!("{}={}", method, result));
+        }
+    }
+    for hop in &email.hops {
+        match hop.delay {
+            Some(delay) if delay < 0 => out.push(format!("Hop by {} is {}s older than the previous one (clock skew or forged header)",
+                hop.by.as_deref().unwrap_or("?"), -delay)),
+            Some(delay) if delay > LONG_DELAY_SECS => out.push(format!("Hop by {} took {}", hop.by.as_deref().unwrap_or("?"), human_delay(delay))),
+            _ => {}
+        }
+    }
+    for attachment in &email.attachments {
+        if let Some(note) = &attachment.note {
+            out.push(format!("Attachment '{}': {}", attachment.filename, note));
+        }
+    }
+    out
+}
+
+fn attachment_note(filename: &str, content_type: &str, data: &[u8]) -> Option<String> {
+    let lower = filename.to_ascii_lowercase();
+    let parts: Vec<&str> = lower.rsplit('.').collect();
+    let ext = parts.first().copied().unwrap_or_default();
+    if parts.len() > 2 && DANGEROUS_EXTENSIONS.contains(&ext) && parts[1].len() <= 4 {
+        return Some(format!("double extension '.{}.{}'", parts[1], ext));
+    }
+    if DANGEROUS_EXTENSIONS.contains(&ext) {
+        return Some(format!("executable or script extension '.{}'", ext));
+    }
+    if data.starts_with(b"MZ") && !lower.ends_with(".exe") && !lower.ends_with(".dll") {
+        return Some(format!("Windows executable disguised as {}", content_type));
+    }
+    if lower.contains('\u{202e}') {
+        return Some("right-to-left override character in the file name".to_string());
+    }
+    None
+}
+
+fn urls(bodies: &[Body]) -> Vec<String> {
+    static HREF: OnceLock<Regex> = OnceLock::new();
+    let href = HREF.get_or_init(|| Regex::new(r#"(?i)\b(?:href|src|action)\s*=\s*["']?([^"'\s>]+)"#).expect("static regex"));
+
+    let mut seen = HashSet::new();
+    let mut out = vec![];
+    for body in bodies {
+        let text = body.content.replace("&amp;", "&");
+        let linked = href.captures_iter(&text)
+            .map(|c| c[1].to_string())
+            .filter(|u| u.contains("://"));
+        let found = ioc::extract(&text).into_iter().filter(|i| i.kind == "url").map(|i| i.value);
+        for url in linked.chain(found) {
+            if seen.insert(url.clone()) {
+                out.push(url);
+            }
+        }
+    }
+    out
+}
+
+pub fn human_delay(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m {}s", s / 60, s % 60),
+        s if s < 86400 => format!("{}h {}m", s / 3600, (s % 3600) / 60),
+        s => format!("{}d {}h", s / 86400, (s % 86400) / 3600),
+    }
+}
+
+pub fn print_report(path: &str, email: &Email) {
+    let show = |label: &str, value: &Option<String>| println!("{:<13}{}", label, value.as_deref().unwrap_or("-"));
+    println!("Email Report for '{}':", path);
+    show("Subject:", &email.subject);
+    show("Date:", &email.date.map(|d| d.format("%Y-%m-%d %H:%M:%S UTC").to_string()));
+    show("Message-ID:", &email.message_id);
+    show("From:", &email.from);
+    show("Reply-To:", &email.reply_to);
+    show("Return-Path:", &email.return_path);
+    println!("{:<13}{}", "To:", if email.to.is_empty() { "-".to_string() } else { email.to.join(", ") });
+
+    println!("\n=== Received chain ({} hops, oldest first) ===", email.hops.len());
+    for (i, hop) in email.hops.iter().enumerate() {
+        let date = hop.date.map(|d| d.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_else(|| "no date".to_string());
+        let delay = match hop.delay {
+            Some(d) if d < 0 => format!("  (-{})", human_delay(-d)),
+            Some(d) => format!("  (+{})", human_delay(d)),
+            None => String::new(),
+        };
+        println!("  [{}] {}{}", i + 1, date, delay);
+        match (&hop.from, &hop.by) {
+            (None, None) => println!("      {}", hop.raw),
+            (from, by) => println!("      from {}{} by {}{}",
+                from.as_deref().unwrap_or("?"),
+                hop.from_ip.as_ref().map(|ip| format!(" [{}]", ip)).unwrap_or_default(),
+                by.as_deref().unwrap_or("?"),
+                hop.with.as_ref().map(|w| format!(" with {}", w)).unwrap_or_default()),
+        }
+    }
+
+    println!("\n=== Authentication ===");
+    if email.verdicts.is_empty() {
+        println!("No Authentication-Results header.");
+    } else {
+        let verdicts: Vec<String> = email.verdicts.iter().map(|(m, r)| format!("{}={}", m, r)).collect();
+        println!("  Verdicts: {}", verdicts.join(" "));
+    }
+    for (name, value) in &email.auth {
+        println!("  {}: {}", name, value);
+    }
+
+    println!("\n=== Bodies ===");
+    for body in &email.bodies {
+        println!("  {} ({} chars)", body.kind, body.content.chars().count());
+    }
+
+    println!("\n=== URLs ({}) ===", email.urls.len());
+    for url in &email.urls {
+        println!("  {}", url);
+    }
+
+    println!("\n=== Attachments ({}) ===", email.attachments.len());
+    for attachment in &email.attachments {
+        println!("  {} ({}, {} bytes)", attachment.filename, attachment.content_type, attachment.data.len());
+        println!("      SHA256: {}", attachment.hashes.sha256);
+    }
+
+    println!("\n=== Warnings ===");
+    if email.warnings.is_empty() {
+        println!("None.");
+    }
+    for warning in &email.warnings {
+        println!("  Warning: {}", warning);
+    }
+}
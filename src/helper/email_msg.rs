@@ -0,0 +1,263 @@
+use anyhow::{bail, Context, Result};
+use cfb::CompoundFile;
+use chrono::{DateTime, Utc};
+use openssl::base64;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use crate::helper::office_vba;
+
+/// OLE2 compound file signature; Outlook .msg files are compound files.
+pub const OLE_MAGIC: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+
+const BOUNDARY: &str = "=_rex_msg_mixed";
+const ALT_BOUNDARY: &str = "=_rex_msg_alternative";
+
+// Headers describing the original MIME layout; the rebuilt message has its own.
+const MIME_HEADERS: &[&str] = &["content-type", "content-transfer-encoding", "mime-version"];
+
+pub fn is_msg(data: &[u8]) -> bool {
+    data.starts_with(OLE_MAGIC)
+}
+
+/// Rebuilds an RFC 5322 message from an Outlook .msg (MS-OXMSG): the transport headers when the
+/// message was received, headers synthesized from MAPI properties otherwise, then the plain and
+/// HTML bodies and every attachment. Embedded messages and RTF-only bodies are not converted.
+pub fn to_rfc5322(data: &[u8]) -> Result<Vec<u8>> {
+    let mut cfb = CompoundFile::open(Cursor::new(data)).context("Failed to parse OLE2 compound file")?;
+    let root = Path::new("/");
+    if !cfb.is_stream(root.join("__properties_version1.0")) {
+        bail!("OLE2 file is not an Outlook message (no __properties_version1.0 stream)");
+    }
+
+    let mut out = String::new();
+    match string_prop(&mut cfb, root, "007D") {
+        Some(headers) => out.push_str(&strip_mime_headers(&headers)),
+        None => out.push_str(&synthesize_headers(&mut cfb)),
+    }
+    out.push_str("MIME-Version: 1.0\r\n");
+    out.push_str(&format!("Content-Type: multipart/mixed; boundary=\"{}\"\r\n\r\n", BOUNDARY));
+
+    let text = string_prop(&mut cfb, root, "1000");
+    let html = binary_prop(&mut cfb, root, "1013").map(|h| String::from_utf8_lossy(&h).into_owned());
+    out.push_str(&format!("--{}\r\n", BOUNDARY));
+    out.push_str(&format!("Content-Type: multipart/alternative; boundary=\"{}\"\r\n\r\n", ALT_BOUNDARY));
+    for (subtype, body) in [("plain", text), ("html", html)] {
+        if let Some(body) = body {
+            out.push_str(&format!("--{}\r\n", ALT_BOUNDARY));
+            out.push_str(&format!("Content-Type: text/{}; charset=utf-8\r\n", subtype));
+            out.push_str(&base64_part(body.as_bytes()));
+        }
+    }
+    out.push_str(&format!("--{}--\r\n", ALT_BOUNDARY));
+
+    for storage in storages(&cfb, "__attach_version1.0_#") {
+        let Some(content) = binary_prop(&mut cfb, &storage, "3701") else { continue };
+        let filename = ["3707", "3704", "3001"].iter()
+            .find_map(|id| string_prop(&mut cfb, &storage, id))
+            .unwrap_or_else(|| "unnamed".to_string());
+        let mime = mime_type(string_prop(&mut cfb, &storage, "370E"));
+        out.push_str(&format!("--{}\r\n", BOUNDARY));
+        out.push_str(&format!("Content-Type: {}\r\n", mime));
+        out.push_str(&format!("Content-Disposition: attachment; filename*=UTF-8''{}\r\n", percent_encode(&filename)));
+        out.push_str(&base64_part(&content));
+    }
+    out.push_str(&format!("--{}--\r\n", BOUNDARY));
+    Ok(out.into_bytes())
+}
+
+/// Unsent or saved-as-draft messages carry no transport headers; rebuild the basic ones.
+fn synthesize_headers(cfb: &mut CompoundFile<Cursor<&[u8]>>) -> String {
+    let root = Path::new("/");
+    let mut headers = String::new();
+    let sender = ["5D01", "0C1F", "0065"].iter()
+        .find_map(|id| string_prop(cfb, root, id))
+        .map(one_line)
+        .filter(|a| a.contains('@'));
+    if let Some(address) = sender {
+        match string_prop(cfb, root, "0C1A").map(one_line) {
+            Some(name) => headers.push_str(&format!("From: {} <{}>\r\n", encode_word(&name), address)),
+            None => headers.push_str(&format!("From: <{}>\r\n", address)),
+        }
+    }
+
+    let mut to = vec![];
+    let mut cc = vec![];
+    for storage in storages(cfb, "__recip_version1.0_#") {
+        let Some(address) = ["39FE", "3003"].iter().find_map(|id| string_prop(cfb, &storage, id)).map(one_line) else { continue };
+        // PR_RECIPIENT_TYPE: 1 To, 2 Cc, 3 Bcc.
+        match fixed_prop(cfb, &storage, 0x0C15, 8) {
+            Some(2) | Some(3) => cc.push(address),
+            _ => to.push(address),
+        }
+    }
+    if !to.is_empty() {
+        headers.push_str(&format!("To: {}\r\n", to.join(", ")));
+    }
+    if !cc.is_empty() {
+        headers.push_str(&format!("Cc: {}\r\n", cc.join(", ")));
+    }
+    if let Some(subject) = string_prop(cfb, root, "0037").map(one_line) {
+        headers.push_str(&format!("Subject: {}\r\n", encode_word(&subject)));
+    }
+    // PR_CLIENT_SUBMIT_TIME, then PR_MESSAGE_DELIVERY_TIME.
+    let date = [0x0039, 0x0E06].iter()
+        .find_map(|&id| fixed_prop(cfb, root, id, 32))
+        .and_then(filetime);
+    if let Some(date) = date {
+        headers.push_str(&format!("Date: {}\r\n", date.to_rfc2822()));
+    }
+    if let Some(id) = string_prop(cfb, root, "1035").map(one_line) {
+        headers.push_str(&format!("Message-ID: {}\r\n", id));
+    }
+    headers
+}
+
+fn storages(cfb: &CompoundFile<Cursor<&[u8]>>, prefix: &str) -> Vec<PathBuf> {
+    cfb.read_root_storage()
+        .filter(|e| e.is_storage() && e.name().starts_with(prefix))
+        .map(|e| e.path().to_path_buf())
+        .collect()
+}
+
+/// PT_UNICODE (001F) or PT_STRING8 (001E) property stream; 8-bit strings are read as Latin-1.
+fn string_prop(cfb: &mut CompoundFile<Cursor<&[u8]>>, storage: &Path, id: &str) -> Option<String> {
+    if let Ok(data) = office_vba::read_stream(cfb, &storage.join(format!("__substg1.0_{}001F", id))) {
+        let units: Vec<u16> = data.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+        let text = String::from_utf16_lossy(&units).trim_end_matches('\0').to_string();
+        return Some(text).filter(|t| !t.is_empty());
+    }
+    let data = office_vba::read_stream(cfb, &storage.join(format!("__substg1.0_{}001E", id))).ok()?;
+    let text: String = data.iter().map(|&b| b as char).collect();
+    Some(text.trim_end_matches('\0').to_string()).filter(|t| !t.is_empty())
+}
+
+fn binary_prop(cfb: &mut CompoundFile<Cursor<&[u8]>>, storage: &Path, id: &str) -> Option<Vec<u8>> {
+    office_vba::read_stream(cfb, &storage.join(format!("__substg1.0_{}0102", id))).ok()
+}
+
+/// Fixed-size property value from the properties stream, whose header is 32 bytes for the
+/// message itself and 8 bytes for recipients and attachments.
+fn fixed_prop(cfb: &mut CompoundFile<Cursor<&[u8]>>, storage: &Path, id: u16, header: usize) -> Option<u64> {
+    let data = office_vba::read_stream(cfb, &storage.join("__properties_version1.0")).ok()?;
+    data.get(header..)?
+        .chunks_exact(16)
+        .find(|entry| u16::from_le_bytes([entry[2], entry[3]]) == id)
+        .map(|entry| u64::from_le_bytes(entry[8..16].try_into().expect("8 bytes")))
+}
+
+fn filetime(value: u64) -> Option<DateTime<Utc>> {
+    let secs = (value / 10_000_000) as i64 - 11_644_473_600;
+    DateTime::from_timestamp(secs, 0)
+}
+
+/// Header values come from MAPI properties; a CR or LF in one would start a new header.
+fn one_line(value: String) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// PR_ATTACH_MIME_TAG is sender-controlled and lands in a header; anything but a bare
+/// `type/subtype` (CR/LF, parameters) falls back to application/octet-stream.
+fn mime_type(tag: Option<String>) -> String {
+    let token = |part: &str| !part.is_empty()
+        && part.bytes().all(|b| b.is_ascii_graphic() && !b"()<>@,;:\\\"/[]?=".contains(&b));
+    tag.map(|t| t.trim().to_string())
+        .filter(|t| t.split_once('/').is_some_and(|(kind, subtype)| token(kind) && token(subtype)))
+        .unwrap_or_else(|| "application/octet-stream".to_string())
+}
+
+fn strip_mime_headers(headers: &str) -> String {
+    let mut out = String::new();
+    let mut skipping = false;
+    for line in headers.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if !line.starts_with([' ', '\t']) {
+            let name = line.split(':').next().unwrap_or_default().trim().to_ascii_lowercase();
+            skipping = MIME_HEADERS.contains(&name.as_str());
+        }
+        if !skipping {
+            out.push_str(line);
+            out.push_str("\r\n");
+        }
+    }
+    out
+}
+
+fn base64_part(data: &[u8]) -> String {
+    let encoded = base64::encode_block(data);
+    let mut out = String::from("Content-Transfer-Encoding: base64\r\n\r\n");
+    for line in encoded.as_bytes().chunks(76) {
+        out.push_str(&String::from_utf8_lossy(line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// RFC 2047 encoded word for non-ASCII header text.
+fn encode_word(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    format!("=?UTF-8?B?{}?=", base64::encode_block(text.as_bytes()))
+}
+
+/// RFC 2231 parameter value.
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn unicode(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(|u| u.to_le_bytes()).collect()
+    }
+
+    fn message(mime_tag: &str) -> Vec<u8> {
+        let mut cfb = CompoundFile::create(Cursor::new(Vec::new())).unwrap();
+        let mut write = |path: &str, data: &[u8]| cfb.create_stream(path).unwrap().write_all(data).unwrap();
+        write("/__properties_version1.0", &[0; 32]);
+        write("/__substg1.0_0037001F", &unicode("Invoice\r\nBcc: attacker@example.com"));
+        write("/__substg1.0_1000001F", &unicode("See attached."));
+        cfb.create_storage("/__attach_version1.0_#00000000").unwrap();
+        let mut write = |path: &str, data: &[u8]| cfb.create_stream(path).unwrap().write_all(data).unwrap();
+        write("/__attach_version1.0_#00000000/__properties_version1.0", &[0; 8]);
+        write("/__attach_version1.0_#00000000/__substg1.0_37010102", b"payload");
+        write("/__attach_version1.0_#00000000/__substg1.0_3707001F", &unicode("invoice.txt"));
+        write("/__attach_version1.0_#00000000/__substg1.0_370E001F", &unicode(mime_tag));
+        cfb.flush().unwrap();
+        cfb.into_inner().into_inner()
+    }
+
+    #[test]
+    fn rebuilds_attachment_with_its_mime_type() {
+        let eml = String::from_utf8(to_rfc5322(&message("text/plain")).unwrap()).unwrap();
+        assert!(eml.contains("Subject: Invoice  Bcc: attacker@example.com\r\n"));
+        assert!(eml.contains("Content-Type: text/plain\r\nContent-Disposition: attachment; filename*=UTF-8''invoice.txt\r\n"));
+    }
+
+    #[test]
+    fn mime_tag_cannot_inject_headers() {
+        let eml = String::from_utf8(to_rfc5322(&message("text/plain\r\nX-Injected: yes\r\n\r\n--=_rex_msg_mixed")).unwrap()).unwrap();
+        assert!(!eml.contains("X-Injected"));
+        assert!(eml.contains("Content-Type: application/octet-stream\r\nContent-Disposition: attachment"));
+    }
+
+    #[test]
+    fn mime_type_validation() {
+        assert_eq!(mime_type(Some(" image/png ".to_string())), "image/png");
+        assert_eq!(mime_type(Some("application/vnd.ms-excel".to_string())), "application/vnd.ms-excel");
+        for bad in ["text/html; charset=utf-8", "text", "/plain", "text/", "te xt/plain", "text/plain\nX: y"] {
+            assert_eq!(mime_type(Some(bad.to_string())), "application/octet-stream", "{:?}", bad);
+        }
+        assert_eq!(mime_type(None), "application/octet-stream");
+    }
+}
@@ -0,0 +1,25 @@
+/// Shannon entropy in bits per byte (0.0 for empty input, 8.0 for uniform random data).
+pub fn shannon(data: &[u8]) -> f64 {
+    let mut counts = [0u64; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    from_counts(&counts)
+}
+
+/// Entropy from a byte histogram, for callers that read their input in chunks.
+pub fn from_counts(counts: &[u64; 256]) -> f64 {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let len = total as f64;
+    counts.iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum::<f64>()
+        .max(0.0)
+}
@@ -0,0 +1,361 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use goblin::elf::{self, Elf};
+use goblin::pe::{self, PE};
+use goblin::Object;
+use crate::helper::entropy;
+
+// Above this a section is almost certainly compressed or encrypted.
+const HIGH_ENTROPY: f64 = 7.2;
+
+/// Section names left behind by common packers and protectors.
+const PACKER_SECTIONS: &[(&str, &str)] = &[
+    ("UPX0", "UPX"), ("UPX1", "UPX"), ("UPX2", "UPX"), (".UPX0", "UPX"), (".UPX1", "UPX"),
+    (".aspack", "ASPack"), (".adata", "ASPack"), (".ASPack", "ASPack"),
+    (".MPRESS1", "MPRESS"), (".MPRESS2", "MPRESS"),
+    (".petite", "Petite"), (".nsp0", "NsPack"), (".nsp1", "NsPack"), (".nsp2", "NsPack"),
+    (".themida", "Themida"), (".winlice", "WinLicense"), (".vmp0", "VMProtect"), (".vmp1", "VMProtect"),
+    (".enigma1", "Enigma"), (".enigma2", "Enigma"), ("PEC2", "PECompact"), ("PECompact2", "PECompact"),
+    (".RLPack", "RLPack"), (".perplex", "Perplex"), (".yP", "Y0da Protector"), (".y0da", "Y0da Protector"),
+    ("FSG!", "FSG"), ("MEW", "MEW"), ("kkrunchy", "kkrunchy"), (".spack", "Simple Pack"), (".neolit", "NeoLite"),
+];
+
+pub struct Section {
+    pub name: String,
+    pub address: u64,
+    pub offset: u64,
+    pub size: u64,
+    pub virtual_size: u64,
+    /// rwx-style permissions.
+    pub flags: String,
+    pub entropy: f64,
+}
+
+pub struct RichEntry {
+    pub product: u16,
+    pub build: u16,
+    pub count: u32,
+}
+
+pub struct Report {
+    pub format: String,
+    pub arch: String,
+    pub kind: String,
+    /// Entry point in the same address space as the sections: an RVA for PE, a virtual address for ELF.
+    pub entry: u64,
+    /// PE preferred load address, 0 for ELF.
+    pub image_base: u64,
+    pub entropy: f64,
+    pub timestamp: Option<DateTime<Utc>>,
+    pub build_id: Option<String>,
+    pub sections: Vec<Section>,
+    pub libraries: Vec<String>,
+    /// (library, symbol); the library is empty when the format does not bind symbols to one (ELF).
+    pub imports: Vec<(String, String)>,
+    pub exports: Vec<String>,
+    pub rich: Vec<RichEntry>,
+    pub signature: Option<String>,
+    pub interpreter: Option<String>,
+    pub rpaths: Vec<String>,
+    pub packer: Vec<String>,
+}
+
+pub fn analyze(data: &[u8]) -> Result<Report> {
+    let mut report = match Object::parse(data).context("Failed to parse executable headers")? {
+        Object::Elf(elf) => analyze_elf(&elf, data),
+        Object::PE(pe) => analyze_pe(&pe, data),
+        Object::Mach(_) => bail!("Mach-O binaries are not supported"),
+        _ => bail!("Not an ELF or PE executable"),
+    };
+    report.entropy = entropy::shannon(data);
+    packer_indicators(&mut report, data);
+    Ok(report)
+}
+
+fn analyze_elf(elf: &Elf, data: &[u8]) -> Report {
+    let mut sections: Vec<Section> = elf.section_headers.iter()
+        .filter(|sh| sh.sh_type != elf::section_header::SHT_NULL)
+        .map(|sh| {
+            let bytes = if sh.sh_type == elf::section_header::SHT_NOBITS { &[][..] } else { slice(data, sh.sh_offset, sh.sh_size) };
+            let flags = format!("{}{}{}",
+                if sh.sh_flags & elf::section_header::SHF_ALLOC as u64 != 0 { "r" } else { "-" },
+                if sh.sh_flags & elf::section_header::SHF_WRITE as u64 != 0 { "w" } else { "-" },
+                if sh.sh_flags & elf::section_header::SHF_EXECINSTR as u64 != 0 { "x" } else { "-" });
+            Section {
+                name: elf.shdr_strtab.get_at(sh.sh_name).unwrap_or("?").to_string(),
+                address: sh.sh_addr,
+                offset: sh.sh_offset,
+                size: bytes.len() as u64,
+                virtual_size: sh.sh_size,
+                flags,
+                entropy: entropy::shannon(bytes),
+            }
+        })
+        .collect();
+
+    // Packed and hand-crafted binaries often drop the section table, fall back to loadable segments.
+    if sections.is_empty() {
+        sections = elf.program_headers.iter()
+            .filter(|ph| ph.p_type == elf::program_header::PT_LOAD)
+            .enumerate()
+            .map(|(i, ph)| {
+                let bytes = slice(data, ph.p_offset, ph.p_filesz);
+                Section {
+                    name: format!("LOAD[{}]", i),
+                    address: ph.p_vaddr,
+                    offset: ph.p_offset,
+                    size: bytes.len() as u64,
+                    virtual_size: ph.p_memsz,
+                    flags: format!("{}{}{}",
+                        if ph.is_read() { "r" } else { "-" },
+                        if ph.is_write() { "w" } else { "-" },
+                        if ph.is_executable() { "x" } else { "-" }),
+                    entropy: entropy::shannon(bytes),
+                }
+            })
+            .collect();
+    }
+
+    let symbol_name = |sym: &elf::Sym| elf.dynstrtab.get_at(sym.st_name).filter(|n| !n.is_empty()).map(str::to_string);
+    let imports = elf.dynsyms.iter()
+        .filter(|sym| sym.is_import())
+        .filter_map(|sym| symbol_name(&sym))
+        .map(|name| (String::new(), name))
+        .collect();
+    let mut exports: Vec<String> = elf.dynsyms.iter()
+        .filter(|sym| !sym.is_import() && sym.st_value != 0 && sym.st_bind() != elf::sym::STB_LOCAL)
+        .filter_map(|sym| symbol_name(&sym))
+        .collect();
+    exports.sort();
+    exports.dedup();
+
+    let build_id = elf.iter_note_sections(data, Some(".note.gnu.build-id"))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .find(|note| note.n_type == elf::note::NT_GNU_BUILD_ID)
+        .map(|note| note.desc.iter().map(|b| format!("{:02x}", b)).collect());
+
+    // Kernel modules append a PKCS#7 signature, some vendors add a dedicated section.
+    let signature = if data.ends_with(b"~Module signature appended~\n") {
+        Some("Appended kernel module signature".to_string())
+    } else {
+        sections.iter()
+            .find(|s| matches!(s.name.as_str(), ".sig" | ".signature" | ".note.signature" | ".gpg_sig"))
+            .map(|s| format!("{} section ({} bytes)", s.name, s.size))
+    };
+
+    Report {
+        format: format!("ELF{} {}", if elf.is_64 { 64 } else { 32 }, if elf.little_endian { "LSB" } else { "MSB" }),
+        arch: elf::header::machine_to_str(elf.header.e_machine).to_string(),
+        kind: match elf.header.e_type {
+            elf::header::ET_DYN if elf.interpreter.is_some() => "position-independent executable".to_string(),
+            elf::header::ET_DYN => "shared object".to_string(),
+            other => elf::header::et_to_str(other).to_lowercase(),
+        },
+        entry: elf.entry,
+        image_base: 0,
+        entropy: 0.0,
+        timestamp: None,
+        build_id,
+        sections,
+        libraries: elf.libraries.iter().map(|l| l.to_string()).collect(),
+        imports,
+        exports,
+        rich: vec![],
+        signature,
+        interpreter: elf.interpreter.map(str::to_string),
+        rpaths: elf.rpaths.iter().chain(elf.runpaths.iter()).map(|r| r.to_string()).collect(),
+        packer: vec![],
+    }
+}
+
+fn analyze_pe(pe: &PE, data: &[u8]) -> Report {
+    use pe::section_table::{IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE};
+
+    let sections = pe.sections.iter()
+        .map(|s| {
+            let bytes = slice(data, s.pointer_to_raw_data as u64, s.size_of_raw_data as u64);
+            Section {
+                name: s.name().map(str::to_string).unwrap_or_else(|_| String::from_utf8_lossy(&s.name).trim_end_matches('\0').to_string()),
+                address: s.virtual_address as u64,
+                offset: s.pointer_to_raw_data as u64,
+                size: bytes.len() as u64,
+                virtual_size: s.virtual_size as u64,
+                flags: format!("{}{}{}",
+                    if s.characteristics & IMAGE_SCN_MEM_READ != 0 { "r" } else { "-" },
+                    if s.characteristics & IMAGE_SCN_MEM_WRITE != 0 { "w" } else { "-" },
+                    if s.characteristics & IMAGE_SCN_MEM_EXECUTE != 0 { "x" } else { "-" }),
+                entropy: entropy::shannon(bytes),
+            }
+        })
+        .collect();
+
+    let rich = pe.header.rich_header
+        .map(|r| r.metadatas()
+            .filter_map(|m| m.ok())
+            .map(|m| RichEntry { product: m.product, build: m.build, count: m.use_count })
+            .collect())
+        .unwrap_or_default();
+
+    let signature = pe.certificates.first().map(|cert| {
+        format!("Authenticode ({:?}, {} bytes){}", cert.certificate_type, cert.certificate.len(),
+            if pe.certificates.len() > 1 { format!(", {} certificates", pe.certificates.len()) } else { String::new() })
+    });
+
+    let stamp = pe.header.coff_header.time_date_stamp;
+    let mut kind = if pe.is_lib { "DLL".to_string() } else { "executable".to_string() };
+    if let Some(subsystem) = pe.header.optional_header.map(|o| o.windows_fields.subsystem) {
+        kind.push_str(match subsystem {
+            1 => " (native)",
+            2 => " (GUI)",
+            3 => " (console)",
+            _ => "",
+        });
+    }
+    if pe.clr_data.is_some() {
+        kind.push_str(", .NET assembly");
+    }
+
+    Report {
+        format: if pe.is_64 { "PE32+".to_string() } else { "PE32".to_string() },
+        arch: pe::header::machine_to_str(pe.header.coff_header.machine).to_string(),
+        kind,
+        entry: pe.entry as u64,
+        image_base: pe.image_base,
+        entropy: 0.0,
+        timestamp: if stamp == 0 { None } else { DateTime::from_timestamp(stamp as i64, 0) },
+        build_id: None,
+        sections,
+        libraries: pe.libraries.iter().map(|l| l.to_string()).collect(),
+        imports: pe.imports.iter().map(|i| (i.dll.to_string(), i.name.to_string())).collect(),
+        exports: pe.exports.iter().filter_map(|e| e.name.map(str::to_string)).collect(),
+        rich,
+        signature,
+        interpreter: None,
+        rpaths: vec![],
+        packer: vec![],
+    }
+}
+
+fn packer_indicators(report: &mut Report, data: &[u8]) {
+    let mut found = vec![];
+    for section in &report.sections {
+        if let Some((_, packer)) = PACKER_SECTIONS.iter().find(|(name, _)| section.name.eq_ignore_ascii_case(name)) {
+            found.push(format!("section '{}' is typical of {}", section.name, packer));
+        }
+        if section.size >= 512 && section.entropy > HIGH_ENTROPY {
+            found.push(format!("section '{}' has high entropy ({:.2}), likely compressed or encrypted", section.name, section.entropy));
+        }
+        if section.flags.contains('w') && section.flags.contains('x') {
+            found.push(format!("section '{}' is both writable and executable", section.name));
+        }
+        if section.size == 0 && section.virtual_size > 0x10000 && section.flags.contains('x') {
+            found.push(format!("executable section '{}' is empty on disk but {} bytes in memory (unpacking stub target)",
+                section.name, section.virtual_size));
+        }
+    }
+    if data.windows(4).take(4096).any(|w| w == b"UPX!") {
+        found.push("UPX! signature found in the headers".to_string());
+    }
+
+    if report.format.starts_with("PE") {
+        let loader_only = report.imports.iter().all(|(_, name)| {
+            name.starts_with("LoadLibrary") || name.starts_with("GetProcAddress") || name.starts_with("VirtualAlloc")
+                || name.starts_with("VirtualProtect") || name == "ExitProcess"
+        });
+        if report.imports.len() < 10 && (loader_only || report.imports.is_empty()) && !report.kind.contains(".NET") {
+            found.push(format!("tiny import table ({} imports), functions are probably resolved at runtime", report.imports.len()));
+        }
+        if let Some(index) = report.sections.iter().position(|s| {
+            report.entry.checked_sub(s.address).is_some_and(|offset| offset < s.virtual_size.max(s.size))
+        }) {
+            let entry_section = &report.sections[index];
+            if index + 1 == report.sections.len() && report.sections.len() > 1 {
+                found.push(format!("entry point is in the last section '{}'", entry_section.name));
+            }
+        }
+    } else if report.sections.iter().all(|s| s.name.starts_with("LOAD[")) {
+        found.push("no section headers (stripped or packed)".to_string());
+    }
+    report.packer = found;
+}
+
+fn slice(data: &[u8], offset: u64, size: u64) -> &[u8] {
+    let start = (offset as usize).min(data.len());
+    let end = start.saturating_add(size as usize).min(data.len());
+    &data[start..end]
+}
+
+pub fn print_report(path: &str, report: &Report) {
+    println!("Executable Report for '{}':", path);
+    println!("Format:       {} {}", report.format, report.kind);
+    println!("Architecture: {}", report.arch);
+    // ImageBase is attacker-controlled, the absolute address is for display only.
+    match report.image_base {
+        0 => println!("Entry point:  0x{:x}", report.entry),
+        base => println!("Entry point:  0x{:x} (RVA 0x{:x})", base.wrapping_add(report.entry), report.entry),
+    }
+    println!("Entropy:      {:.2}", report.entropy);
+    match &report.timestamp {
+        Some(ts) => {
+            let note = if *ts > Utc::now() { " (in the future, forged)" } else { "" };
+            println!("Compiled:     {}{}", ts.format("%Y-%m-%d %H:%M:%S UTC"), note);
+        }
+        None if report.format.starts_with("PE") => println!("Compiled:     (zeroed)"),
+        None => {}
+    }
+    if let Some(build_id) = &report.build_id {
+        println!("Build ID:     {}", build_id);
+    }
+    if let Some(interpreter) = &report.interpreter {
+        println!("Interpreter:  {}", interpreter);
+    }
+    for rpath in &report.rpaths {
+        let note = if rpath.split(':').any(|p| p.is_empty() || p == "." || p.starts_with("/tmp") || p.starts_with("/dev/shm")) {
+            " (Warning: writable or relative path, library hijacking)"
+        } else {
+            ""
+        };
+        println!("RPATH:        {}{}", rpath, note);
+    }
+    println!("Signature:    {}", report.signature.as_deref().unwrap_or("none"));
+
+    println!("\n=== Sections ===");
+    println!("  {:<20} {:>18} {:>10} {:>10} {:>5} {:>8}", "Name", "Address", "Raw size", "Virt size", "Perm", "Entropy");
+    for s in &report.sections {
+        println!("  {:<20} {:>#18x} {:>10} {:>10} {:>5} {:>8.2}", s.name, s.address, s.size, s.virtual_size, s.flags, s.entropy);
+    }
+
+    if !report.rich.is_empty() {
+        println!("\n=== Rich header ===");
+        for entry in &report.rich {
+            println!("  product {:>4}  build {:>6}  count {}", entry.product, entry.build, entry.count);
+        }
+    }
+
+    println!("\n=== Imports ({}) ===", report.imports.len());
+    if !report.libraries.is_empty() {
+        println!("  Libraries: {}", report.libraries.join(", "));
+    }
+    for (library, name) in &report.imports {
+        if library.is_empty() {
+            println!("  {}", name);
+        } else {
+            println!("  {}!{}", library, name);
+        }
+    }
+
+    println!("\n=== Exports ({}) ===", report.exports.len());
+    for name in &report.exports {
+        println!("  {}", name);
+    }
+
+    println!("\n=== Packer indicators ===");
+    if report.packer.is_empty() {
+        println!("None.");
+    }
+    for indicator in &report.packer {
+        println!("  Warning: {}", indicator);
+    }
+}
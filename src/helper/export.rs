@@ -0,0 +1,94 @@
+use anyhow::{Result, Context, bail};
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> Result<Format> {
+        match name.to_lowercase().as_str() {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            other => bail!("Unknown output format '{}' (expected text, json, ndjson or csv)", other),
+        }
+    }
+}
+
+/// Render rows as JSON, NDJSON or CSV. Text output is left to the caller.
+pub fn render<T: Serialize>(format: Format, rows: &[T]) -> Result<String> {
+    match format {
+        Format::Json => Ok(serde_json::to_string_pretty(rows)? + "\n"),
+        Format::Ndjson => {
+            let mut out = String::new();
+            for row in rows {
+                out.push_str(&serde_json::to_string(row)?);
+                out.push('\n');
+            }
+            Ok(out)
+        }
+        Format::Csv => to_csv(rows),
+        Format::Text => bail!("Text output must be rendered by the caller"),
+    }
+}
+
+/// Write to the given file, or to stdout when no path is given.
+pub fn write_output(output: Option<&str>, content: &str) -> Result<()> {
+    match output {
+        Some(path) => {
+            std::fs::write(path, content)
+                .with_context(|| format!("Failed to write {}", path))?;
+            eprintln!("Output saved to '{}'", path);
+        }
+        None => print!("{}", content),
+    }
+    Ok(())
+}
+
+fn to_csv<T: Serialize>(rows: &[T]) -> Result<String> {
+    let values: Vec<Value> = rows.iter()
+        .map(serde_json::to_value)
+        .collect::<Result<_, _>>()?;
+
+    let mut header: Vec<String> = Vec::new();
+    for value in &values {
+        if let Value::Object(map) = value {
+            for key in map.keys() {
+                if !header.contains(key) {
+                    header.push(key.clone());
+                }
+            }
+        }
+    }
+
+    let mut out = header.iter().map(|h| csv_field(h)).collect::<Vec<_>>().join(",");
+    out.push('\n');
+    for value in &values {
+        let line = header.iter()
+            .map(|key| match value.get(key) {
+                None | Some(Value::Null) => String::new(),
+                Some(Value::String(s)) => csv_field(s),
+                Some(other) => csv_field(&other.to_string()),
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        out.push_str(&line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
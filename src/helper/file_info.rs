@@ -0,0 +1,218 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use crate::helper::{entropy, fuzzy, hashes};
+
+// Printable runs shorter than this are noise.
+const MIN_STRING: usize = 4;
+const STRING_SAMPLES: usize = 5;
+// Similarity digests need the whole file in memory, skip them for disk images and the like.
+pub const FUZZY_LIMIT: u64 = 256 * 1024 * 1024;
+
+/// Extensions that legitimately hold content `infer` reports under another name.
+const EQUIVALENT: &[(&str, &[&str])] = &[
+    ("jpg", &["jpeg", "jpe", "jfif"]),
+    ("tif", &["tiff"]),
+    ("html", &["htm", "xhtml"]),
+    ("mp4", &["m4v", "m4a"]),
+    ("gz", &["tgz", "gzip"]),
+    ("exe", &["dll", "sys", "scr", "cpl", "ocx", "efi", "drv", "com", "mui"]),
+    ("dll", &["exe", "sys", "cpl", "ocx", "mui"]),
+    ("elf", &["so", "o", "ko", "bin", "out", ""]),
+    ("zip", &["docx", "xlsx", "pptx", "docm", "xlsm", "pptm", "jar", "apk", "odt", "ods", "odp", "epub", "xpi", "vsix", "nupkg", "whl", "ipa", "kmz"]),
+    ("docx", &["docm", "dotx", "dotm"]),
+    ("xlsx", &["xlsm", "xltx", "xltm"]),
+    ("pptx", &["pptm", "potx", "potm", "ppsx", "ppsm"]),
+    ("msi", &["doc", "xls", "ppt", "msg", "msp", "mst", "vsd", "pub"]),
+    ("doc", &["xls", "ppt", "msg", "msi", "dot", "xlt", "pot"]),
+    ("xml", &["svg", "plist", "rss", "xsd", "xsl", "config", "manifest"]),
+];
+
+#[derive(Debug, Serialize)]
+pub struct FileInfo {
+    pub path: String,
+    pub size: u64,
+    /// MIME type detected from magic bytes, empty when unknown.
+    pub mime: String,
+    pub detected: String,
+    pub extension: String,
+    pub mismatch: bool,
+    #[serde(flatten)]
+    pub hashes: hashes::Hashes,
+    /// Context-triggered piecewise hash (ssdeep-style), empty above the size limit.
+    pub ctph: String,
+    /// Empty when the file is too small, too uniform or too large.
+    pub tlsh: String,
+    pub entropy: f64,
+    pub strings: u64,
+    pub longest_string: usize,
+    pub strings_sample: String,
+}
+
+/// Every regular file under `root` (or `root` itself), sorted for stable manifests.
+pub fn collect_files(root: &Path) -> Result<Vec<PathBuf>> {
+    let meta = std::fs::symlink_metadata(root).with_context(|| format!("Failed to stat {}", root.display()))?;
+    if !meta.is_dir() {
+        return Ok(vec![root.to_path_buf()]);
+    }
+    let mut files = vec![];
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                println!("Warning: cannot read {}: {}", dir.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            // Symlinks are not followed, they could loop or leave the evidence tree.
+            match entry.file_type() {
+                Ok(t) if t.is_dir() => stack.push(entry.path()),
+                Ok(t) if t.is_file() => files.push(entry.path()),
+                _ => {}
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+pub fn analyze(path: &Path) -> Result<FileInfo> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = hashes::Hasher::default();
+    let mut counts = [0u64; 256];
+    let mut content = Vec::new();
+    let mut size = 0u64;
+    let mut strings = StringStats::default();
+    let mut buf = vec![0u8; 1 << 20];
+
+    loop {
+        let n = file.read(&mut buf).with_context(|| format!("Failed to read {}", path.display()))?;
+        if n == 0 {
+            break;
+        }
+        let chunk = &buf[..n];
+        hasher.update(chunk);
+        for &b in chunk {
+            counts[b as usize] += 1;
+            strings.push(b);
+        }
+        size += n as u64;
+        if size <= FUZZY_LIMIT {
+            content.extend_from_slice(chunk);
+        } else if content.len() > 8192 {
+            content.truncate(8192);
+        }
+    }
+    strings.flush();
+
+    let kind = infer::get(&content[..content.len().min(8192)]);
+    let (ctph, tlsh) = if size <= FUZZY_LIMIT {
+        let digests = fuzzy::digests(&content);
+        (digests.ctph, digests.tlsh.map(|t| t.to_string()).unwrap_or_default())
+    } else {
+        (String::new(), String::new())
+    };
+    let detected = kind.map(|k| k.extension().to_string()).unwrap_or_default();
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+
+    Ok(FileInfo {
+        path: path.display().to_string(),
+        size,
+        mime: kind.map(|k| k.mime_type().to_string()).unwrap_or_default(),
+        mismatch: is_mismatch(&detected, &extension),
+        detected,
+        extension,
+        hashes: hasher.finish(),
+        ctph,
+        tlsh,
+        entropy: (entropy::from_counts(&counts) * 1000.0).round() / 1000.0,
+        strings: strings.count,
+        longest_string: strings.longest,
+        strings_sample: strings.samples.join(" | "),
+    })
+}
+
+fn is_mismatch(detected: &str, extension: &str) -> bool {
+    if detected.is_empty() || detected == extension {
+        return false;
+    }
+    // Files without an extension are only suspicious when they hide an executable.
+    if extension.is_empty() {
+        return matches!(detected, "exe" | "dll");
+    }
+    !EQUIVALENT.iter().any(|(kind, others)| *kind == detected && others.contains(&extension))
+}
+
+#[derive(Default)]
+struct StringStats {
+    current: Vec<u8>,
+    count: u64,
+    longest: usize,
+    samples: Vec<String>,
+}
+
+impl StringStats {
+    fn push(&mut self, b: u8) {
+        if b == b'\t' || (0x20..0x7F).contains(&b) {
+            self.current.push(b);
+        } else {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.current.len() >= MIN_STRING {
+            self.count += 1;
+            self.longest = self.longest.max(self.current.len());
+            if self.samples.len() < STRING_SAMPLES && self.current.len() >= 8 {
+                let sample: String = self.current.iter().take(60).map(|&b| b as char).collect();
+                self.samples.push(sample.trim().to_string());
+            }
+        }
+        self.current.clear();
+    }
+}
+
+pub fn render_text(infos: &[FileInfo]) -> String {
+    let mut out = String::new();
+    for info in infos {
+        out.push_str(&format!("{}\n", info.path));
+        out.push_str(&format!("  Size:      {} bytes\n", info.size));
+        let detected = if info.detected.is_empty() {
+            "unknown".to_string()
+        } else {
+            format!("{} ({})", info.detected, info.mime)
+        };
+        out.push_str(&format!("  Type:      {}\n", detected));
+        if info.mismatch {
+            out.push_str(&format!("  Warning: extension '.{}' does not match detected type '{}'\n", info.extension, info.detected));
+        }
+        out.push_str(&format!("  MD5:       {}\n", info.hashes.md5));
+        out.push_str(&format!("  SHA1:      {}\n", info.hashes.sha1));
+        out.push_str(&format!("  SHA256:    {}\n", info.hashes.sha256));
+        out.push_str(&format!("  SHA512:    {}\n", info.hashes.sha512));
+        if !info.ctph.is_empty() {
+            out.push_str(&format!("  CTPH:      {}\n", info.ctph));
+        }
+        if !info.tlsh.is_empty() {
+            out.push_str(&format!("  TLSH:      {}\n", info.tlsh));
+        }
+        let note = if info.entropy > 7.5 { " (compressed or encrypted)" } else { "" };
+        out.push_str(&format!("  Entropy:   {:.3}{}\n", info.entropy, note));
+        out.push_str(&format!("  Strings:   {} (longest {} chars)\n", info.strings, info.longest_string));
+        if !info.strings_sample.is_empty() {
+            out.push_str(&format!("  Sample:    {}\n", info.strings_sample));
+        }
+        out.push('\n');
+    }
+    if infos.len() > 1 {
+        let mismatches = infos.iter().filter(|i| i.mismatch).count();
+        let total: u64 = infos.iter().map(|i| i.size).sum();
+        out.push_str(&format!("{} files, {} bytes, {} type mismatch(es)\n", infos.len(), total, mismatches));
+    }
+    out
+}
@@ -0,0 +1,365 @@
+//! Similarity digests: spamsum/ssdeep-style context-triggered piecewise hashing and TLSH (T1, 128 buckets).
+
+const ROLLING_WINDOW: usize = 7;
+const MIN_BLOCKSIZE: u32 = 3;
+const SPAMSUM_LENGTH: usize = 64;
+const HASH_PRIME: u32 = 0x0100_0193;
+const HASH_INIT: u32 = 0x2802_1967;
+const B64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// TLSH needs enough data for its bucket quartiles to mean anything.
+pub const TLSH_MIN_LENGTH: usize = 50;
+
+#[derive(Default)]
+struct Roll {
+    window: [u8; ROLLING_WINDOW],
+    h1: u32,
+    h2: u32,
+    h3: u32,
+    n: usize,
+}
+
+impl Roll {
+    fn push(&mut self, c: u8) -> u32 {
+        let c32 = c as u32;
+        self.h2 = self.h2.wrapping_sub(self.h1).wrapping_add((ROLLING_WINDOW as u32).wrapping_mul(c32));
+        self.h1 = self.h1.wrapping_add(c32).wrapping_sub(self.window[self.n % ROLLING_WINDOW] as u32);
+        self.window[self.n % ROLLING_WINDOW] = c;
+        self.n += 1;
+        self.h3 = (self.h3 << 5) ^ c32;
+        self.sum()
+    }
+
+    fn sum(&self) -> u32 {
+        self.h1.wrapping_add(self.h2).wrapping_add(self.h3)
+    }
+}
+
+fn sum_hash(c: u8, h: u32) -> u32 {
+    h.wrapping_mul(HASH_PRIME) ^ c as u32
+}
+
+/// "blocksize:hash:hash" digest, comparable with `ctph_compare`.
+pub fn ctph(data: &[u8]) -> String {
+    let mut block_size = MIN_BLOCKSIZE;
+    while (block_size as usize) * SPAMSUM_LENGTH < data.len() {
+        block_size *= 2;
+    }
+
+    loop {
+        let mut roll = Roll::default();
+        let (mut h1, mut h2) = (HASH_INIT, HASH_INIT);
+        let (mut p1, mut p2) = (String::new(), String::new());
+
+        for &c in data {
+            h1 = sum_hash(c, h1);
+            h2 = sum_hash(c, h2);
+            let rh = roll.push(c);
+            // A trigger point ends a piece; the last character keeps absorbing once the digest is full.
+            if rh % block_size == block_size - 1 {
+                if p1.len() < SPAMSUM_LENGTH - 1 {
+                    p1.push(B64[(h1 % 64) as usize] as char);
+                    h1 = HASH_INIT;
+                }
+                if rh % (block_size * 2) == block_size * 2 - 1 && p2.len() < SPAMSUM_LENGTH / 2 - 1 {
+                    p2.push(B64[(h2 % 64) as usize] as char);
+                    h2 = HASH_INIT;
+                }
+            }
+        }
+        if roll.sum() != 0 {
+            p1.push(B64[(h1 % 64) as usize] as char);
+            p2.push(B64[(h2 % 64) as usize] as char);
+        }
+
+        if block_size > MIN_BLOCKSIZE && p1.len() < SPAMSUM_LENGTH / 2 {
+            block_size /= 2;
+            continue;
+        }
+        return format!("{}:{}:{}", block_size, p1, p2);
+    }
+}
+
+/// Similarity score of two CTPH digests, 0 (unrelated) to 100 (identical).
+pub fn ctph_compare(a: &str, b: &str) -> u32 {
+    let parse = |d: &str| -> Option<(u32, String, String)> {
+        let mut parts = d.splitn(3, ':');
+        let size = parts.next()?.parse().ok()?;
+        Some((size, eliminate_sequences(parts.next()?), eliminate_sequences(parts.next()?)))
+    };
+    let (Some((bs1, a1, a2)), Some((bs2, b1, b2))) = (parse(a), parse(b)) else { return 0 };
+
+    if bs1 == bs2 && a1 == b1 && !a1.is_empty() {
+        return 100;
+    }
+    if bs1 == bs2 {
+        score_strings(&a1, &b1, bs1).max(score_strings(&a2, &b2, bs1 * 2))
+    } else if bs1 == bs2 * 2 {
+        score_strings(&a1, &b2, bs1)
+    } else if bs2 == bs1 * 2 {
+        score_strings(&a2, &b1, bs2)
+    } else {
+        0
+    }
+}
+
+// Long runs of one character carry little information and inflate scores.
+fn eliminate_sequences(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    for (i, &c) in bytes.iter().enumerate() {
+        if i < 3 || !(c == bytes[i - 1] && c == bytes[i - 2] && c == bytes[i - 3]) {
+            out.push(c);
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn score_strings(a: &str, b: &str, block_size: u32) -> u32 {
+    if a.len() < ROLLING_WINDOW || b.len() < ROLLING_WINDOW {
+        return 0;
+    }
+    // Unrelated inputs rarely share a whole rolling window.
+    let common = a.as_bytes().windows(ROLLING_WINDOW).any(|w| b.as_bytes().windows(ROLLING_WINDOW).any(|v| v == w));
+    if !common {
+        return 0;
+    }
+
+    let distance = edit_distance(a.as_bytes(), b.as_bytes()) as u32;
+    let (len1, len2) = (a.len() as u32, b.len() as u32);
+    let score = distance * SPAMSUM_LENGTH as u32 / (len1 + len2);
+    let score = 100 * score / SPAMSUM_LENGTH as u32;
+    if score >= 100 {
+        return 0;
+    }
+    let score = 100 - score;
+
+    // Small block sizes mean small inputs, do not let them claim a perfect match.
+    if block_size >= (99 + ROLLING_WINDOW as u32) / ROLLING_WINDOW as u32 * MIN_BLOCKSIZE {
+        return score;
+    }
+    score.min(block_size / MIN_BLOCKSIZE * len1.min(len2))
+}
+
+// Insertions and deletions cost 1, substitutions 2.
+fn edit_distance(a: &[u8], b: &[u8]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut cur = vec![0; b.len() + 1];
+    for (i, &ca) in a.iter().enumerate() {
+        cur[0] = i + 1;
+        for (j, &cb) in b.iter().enumerate() {
+            let substitute = prev[j] + if ca == cb { 0 } else { 2 };
+            cur[j + 1] = substitute.min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    prev[b.len()]
+}
+
+// Pearson permutation table from the TLSH reference implementation.
+const V_TABLE: [u8; 256] = [
+    1, 87, 49, 12, 176, 178, 102, 166, 121, 193, 6, 84, 249, 230, 44, 163, 14, 197, 213, 181, 161, 85, 218, 80, 64, 239, 24, 226, 236, 142, 38, 200,
+    110, 177, 104, 103, 141, 253, 255, 50, 77, 101, 81, 18, 45, 96, 31, 222, 25, 107, 190, 70, 86, 237, 240, 34, 72, 242, 20, 214, 244, 227, 149, 235,
+    97, 234, 57, 22, 60, 250, 82, 175, 208, 5, 127, 199, 111, 62, 135, 248, 174, 169, 211, 58, 66, 154, 106, 195, 245, 171, 17, 187, 182, 179, 0, 243,
+    132, 56, 148, 75, 128, 133, 158, 100, 130, 126, 91, 13, 153, 246, 216, 219, 119, 68, 223, 78, 83, 88, 201, 99, 122, 11, 92, 32, 136, 114, 52, 10,
+    138, 30, 48, 183, 156, 35, 61, 26, 143, 74, 251, 94, 129, 162, 63, 152, 170, 7, 115, 167, 241, 206, 3, 150, 55, 59, 151, 220, 90, 53, 23, 131,
+    125, 173, 15, 238, 79, 95, 89, 16, 105, 137, 225, 224, 217, 160, 37, 123, 118, 73, 2, 157, 46, 116, 9, 145, 134, 228, 207, 212, 202, 215, 69, 229,
+    27, 188, 67, 124, 168, 252, 42, 4, 29, 108, 21, 247, 19, 205, 39, 203, 233, 40, 186, 147, 198, 192, 155, 33, 164, 191, 98, 204, 165, 180, 117, 76,
+    140, 36, 210, 172, 41, 54, 159, 8, 185, 232, 113, 196, 231, 47, 146, 120, 51, 65, 28, 144, 254, 221, 93, 189, 194, 139, 112, 43, 71, 109, 184, 209,
+];
+
+const BUCKETS: usize = 128;
+const CODE_SIZE: usize = BUCKETS / 4;
+
+fn b_mapping(salt: u8, i: u8, j: u8, k: u8) -> u8 {
+    let mut h = V_TABLE[salt as usize];
+    h = V_TABLE[(h ^ i) as usize];
+    h = V_TABLE[(h ^ j) as usize];
+    V_TABLE[(h ^ k) as usize]
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tlsh {
+    checksum: u8,
+    lvalue: u8,
+    q1: u8,
+    q2: u8,
+    code: [u8; CODE_SIZE],
+}
+
+impl std::fmt::Display for Tlsh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "T1{:02X}{:02X}{:X}{:X}", swap(self.checksum), swap(self.lvalue), self.q1, self.q2)?;
+        for b in self.code.iter().rev() {
+            write!(f, "{:02X}", b)?;
+        }
+        Ok(())
+    }
+}
+
+fn swap(b: u8) -> u8 {
+    b.rotate_left(4)
+}
+
+impl Tlsh {
+    pub fn parse(digest: &str) -> Option<Tlsh> {
+        let hex = digest.strip_prefix("T1").unwrap_or(digest);
+        if hex.len() != 6 + CODE_SIZE * 2 {
+            return None;
+        }
+        let byte = |i: usize| u8::from_str_radix(hex.get(i * 2..i * 2 + 2)?, 16).ok();
+        let q = byte(2)?;
+        let mut code = [0u8; CODE_SIZE];
+        for (i, slot) in code.iter_mut().enumerate() {
+            *slot = byte(3 + CODE_SIZE - 1 - i)?;
+        }
+        Some(Tlsh { checksum: swap(byte(0)?), lvalue: swap(byte(1)?), q1: q >> 4, q2: q & 0xF, code })
+    }
+
+    /// TLSH distance, 0 for identical inputs; below ~100 usually means related files.
+    pub fn diff(&self, other: &Tlsh) -> u32 {
+        let mut diff = 0;
+        diff += match mod_diff(self.lvalue as u32, other.lvalue as u32, 256) {
+            0 => 0,
+            1 => 1,
+            d => d * 12,
+        };
+        for (a, b) in [(self.q1, other.q1), (self.q2, other.q2)] {
+            let d = mod_diff(a as u32, b as u32, 16);
+            diff += if d <= 1 { d } else { (d - 1) * 12 };
+        }
+        if self.checksum != other.checksum {
+            diff += 1;
+        }
+        for (a, b) in self.code.iter().zip(other.code.iter()) {
+            for shift in (0..8).step_by(2) {
+                let d = ((a >> shift) & 3).abs_diff((b >> shift) & 3) as u32;
+                diff += if d == 3 { 6 } else { d };
+            }
+        }
+        diff
+    }
+}
+
+fn mod_diff(x: u32, y: u32, range: u32) -> u32 {
+    let (dl, dr) = if y > x { (y - x, x + range - y) } else { (x - y, y + range - x) };
+    dl.min(dr)
+}
+
+/// TLSH digest, or None when the input is too short or too uniform.
+pub fn tlsh(data: &[u8]) -> Option<Tlsh> {
+    if data.len() < TLSH_MIN_LENGTH {
+        return None;
+    }
+    let mut buckets = [0u32; 256];
+    let mut checksum = 0u8;
+    for w in data.windows(5) {
+        let (a, b, c, d, e) = (w[4], w[3], w[2], w[1], w[0]);
+        checksum = b_mapping(0, a, b, checksum);
+        buckets[b_mapping(2, a, b, c) as usize] += 1;
+        buckets[b_mapping(3, a, b, d) as usize] += 1;
+        buckets[b_mapping(5, a, c, d) as usize] += 1;
+        buckets[b_mapping(7, a, c, e) as usize] += 1;
+        buckets[b_mapping(11, a, b, e) as usize] += 1;
+        buckets[b_mapping(13, a, d, e) as usize] += 1;
+    }
+
+    let mut sorted = buckets[..BUCKETS].to_vec();
+    sorted.sort_unstable();
+    let (q1, q2, q3) = (sorted[BUCKETS / 4 - 1], sorted[BUCKETS / 2 - 1], sorted[BUCKETS - BUCKETS / 4 - 1]);
+    if q3 == 0 || buckets[..BUCKETS].iter().filter(|&&b| b > 0).count() <= CODE_SIZE * 2 {
+        return None;
+    }
+
+    let mut code = [0u8; CODE_SIZE];
+    for (i, slot) in code.iter_mut().enumerate() {
+        let mut h = 0u8;
+        for j in 0..4 {
+            let k = buckets[4 * i + j];
+            let value = if q3 < k { 3 } else if q2 < k { 2 } else if q1 < k { 1 } else { 0 };
+            h += value << (j * 2);
+        }
+        *slot = h;
+    }
+
+    Some(Tlsh {
+        checksum,
+        lvalue: l_capturing(data.len()),
+        q1: ((q1 as u64 * 100 / q3 as u64) % 16) as u8,
+        q2: ((q2 as u64 * 100 / q3 as u64) % 16) as u8,
+        code,
+    })
+}
+
+// Log-scale length bucket.
+fn l_capturing(len: usize) -> u8 {
+    let len = len as f64;
+    let i = if len <= 656.0 {
+        (len.ln() / 1.5f64.ln()).floor()
+    } else if len <= 3199.0 {
+        (len.ln() / 1.3f64.ln() - 8.72777).floor()
+    } else {
+        (len.ln() / 1.1f64.ln() - 62.5472).floor()
+    };
+    (i as i64 & 0xFF) as u8
+}
+
+pub struct Digests {
+    pub ctph: String,
+    pub tlsh: Option<Tlsh>,
+}
+
+pub fn digests(data: &[u8]) -> Digests {
+    Digests { ctph: ctph(data), tlsh: tlsh(data) }
+}
+
+/// A pair of inputs considered related, with both scores for the report.
+pub struct Match {
+    pub a: usize,
+    pub b: usize,
+    pub ctph: u32,
+    pub tlsh: Option<u32>,
+}
+
+/// Groups inputs linked by a CTPH score >= `min_score` or a TLSH distance <= `max_distance`
+/// (single linkage, so A~B and B~C put A, B and C together). Singletons are left out.
+pub fn cluster(items: &[Digests], min_score: u32, max_distance: u32) -> (Vec<Vec<usize>>, Vec<Match>) {
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+
+    let mut matches = vec![];
+    for a in 0..items.len() {
+        for b in a + 1..items.len() {
+            let score = ctph_compare(&items[a].ctph, &items[b].ctph);
+            let distance = match (&items[a].tlsh, &items[b].tlsh) {
+                (Some(x), Some(y)) => Some(x.diff(y)),
+                _ => None,
+            };
+            if score >= min_score || distance.is_some_and(|d| d <= max_distance) {
+                matches.push(Match { a, b, ctph: score, tlsh: distance });
+                let (ra, rb) = (root(&mut parent, a), root(&mut parent, b));
+                parent[ra] = rb;
+            }
+        }
+    }
+
+    let mut groups: Vec<Vec<usize>> = vec![];
+    let mut group_of = std::collections::HashMap::new();
+    for i in 0..items.len() {
+        let r = root(&mut parent, i);
+        let g = *group_of.entry(r).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[g].push(i);
+    }
+    groups.retain(|g| g.len() > 1);
+    groups.sort_by_key(|g| std::cmp::Reverse(g.len()));
+    (groups, matches)
+}
@@ -0,0 +1,43 @@
+use digest::Digest;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hashes {
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+    pub sha512: String,
+}
+
+pub fn compute(data: &[u8]) -> Hashes {
+    let mut hasher = Hasher::default();
+    hasher.update(data);
+    hasher.finish()
+}
+
+/// Incremental variant of `compute` for files too large to hold in memory.
+#[derive(Default)]
+pub struct Hasher {
+    md5: md5::Md5,
+    sha1: sha1::Sha1,
+    sha256: sha2::Sha256,
+    sha512: sha2::Sha512,
+}
+
+impl Hasher {
+    pub fn update(&mut self, data: &[u8]) {
+        self.md5.update(data);
+        self.sha1.update(data);
+        self.sha256.update(data);
+        self.sha512.update(data);
+    }
+
+    pub fn finish(self) -> Hashes {
+        Hashes {
+            md5: format!("{:x}", self.md5.finalize()),
+            sha1: format!("{:x}", self.sha1.finalize()),
+            sha256: format!("{:x}", self.sha256.finalize()),
+            sha512: format!("{:x}", self.sha512.finalize()),
+        }
+    }
+}
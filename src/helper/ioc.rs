@@ -0,0 +1,235 @@
+use regex::Regex;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+// Common and frequently abused TLDs; keeps `app.alert` or `file.exe` out of the domain list.
+const TLDS: &[&str] = &[
+    "com", "net", "org", "edu", "gov", "mil", "int", "info", "biz", "io", "co", "me", "tv",
+    "cc", "ws", "to", "su", "ru", "cn", "de", "uk", "fr", "nl", "be", "es", "it", "pl", "ch",
+    "at", "se", "no", "dk", "fi", "cz", "sk", "hu", "ro", "bg", "gr", "pt", "ie", "ua", "by",
+    "kz", "tr", "ir", "in", "pk", "bd", "jp", "kr", "tw", "hk", "sg", "my", "id", "th", "vn",
+    "ph", "au", "nz", "ca", "us", "mx", "br", "ar", "cl", "pe", "ve", "za", "ng", "ke", "eg",
+    "ma", "il", "sa", "ae", "eu", "asia", "xyz", "top", "club", "online", "site", "shop",
+    "store", "live", "app", "dev", "cloud", "tech", "space", "website", "link", "click",
+    "icu", "buzz", "work", "fun", "gq", "ml", "cf", "ga", "tk", "pw", "cyou", "rest", "bar",
+    "monster", "vip", "win", "bid", "loan", "download", "zip", "mov", "email", "support",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Indicator {
+    /// url, ip, domain or email; `classify` adds registry, path, base64, wallet and user_agent.
+    pub kind: &'static str,
+    pub value: String,
+}
+
+fn url_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)\b(?:https?|ftp|file|smb)://[^\s"'<>()\[\]{}\\^`|]+"#)
+        .expect("static regex"))
+}
+
+fn email_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\b[a-z0-9._%+-]+@((?:[a-z0-9-]+\.)+[a-z]{2,24})\b")
+        .expect("static regex"))
+}
+
+fn ipv4_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\b(?:\d{1,3}\.){3}\d{1,3}\b").expect("static regex"))
+}
+
+fn domain_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\b(?:[a-z0-9](?:[a-z0-9-]{0,61}[a-z0-9])?\.)+([a-z]{2,24})\b")
+        .expect("static regex"))
+}
+
+fn registry_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)\b(?:HKEY_(?:LOCAL_MACHINE|CURRENT_USER|CLASSES_ROOT|USERS|CURRENT_CONFIG)|HKLM|HKCU|HKCR|HKU|SOFTWARE|SYSTEM)(?:\\[^\\\r\n\t"*?<>|]+)+"#)
+        .expect("static regex"))
+}
+
+fn windows_path_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?i)(?:\b[a-z]:|\\\\[a-z0-9._$-]+|%[a-z_]+%)\\[^\\/:*?"<>|\r\n\t]+(?:\\[^\\/:*?"<>|\r\n\t]+)*\\?"#)
+        .expect("static regex"))
+}
+
+fn unix_path_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r#"(?:^|[\s"'=(])(/(?:etc|tmp|var|usr|home|root|bin|sbin|dev|proc|opt|lib|lib64|run|mnt|Users|Library|Applications|System|private)(?:/[^\s"'<>;|]*)?)"#)
+        .expect("static regex"))
+}
+
+fn base64_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"[A-Za-z0-9+/]{32,}={0,2}").expect("static regex"))
+}
+
+fn wallet_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(concat!(
+        r"\b(?:[13][a-km-zA-HJ-NP-Z1-9]{25,34}",
+        r"|bc1[ac-hj-np-z02-9]{11,71}",
+        r"|0x[a-fA-F0-9]{40}",
+        r"|4[0-9AB][1-9A-HJ-NP-Za-km-z]{93})\b",
+    )).expect("static regex"))
+}
+
+fn user_agent_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(concat!(
+        r"\b(?:Mozilla/\d\.\d \(|Opera/\d|curl/\d|Wget/\d|python-requests/\d|Python-urllib/\d",
+        r"|Go-http-client/\d|Java/\d|okhttp/\d|Microsoft BITS/\d|WindowsPowerShell/\d|WinHttp)[^\r\n\t]*",
+    )).expect("static regex"))
+}
+
+/// `extract` plus registry keys, file paths, base64 blobs, crypto wallets and user agents,
+/// for arbitrary strings pulled out of binaries.
+pub fn classify(text: &str) -> Vec<Indicator> {
+    let mut out = extract(text);
+    let mut push = |kind: &'static str, value: &str| {
+        let indicator = Indicator { kind, value: value.to_string() };
+        if !out.contains(&indicator) {
+            out.push(indicator);
+        }
+    };
+
+    for m in user_agent_re().find_iter(text) {
+        push("user_agent", m.as_str().trim_end_matches(['"', '\'']));
+    }
+    for m in registry_re().find_iter(text) {
+        // `C:\Windows\System\x.dll` is a path, not the SYSTEM hive.
+        if !text[..m.start()].ends_with('\\') {
+            push("registry", m.as_str().trim_end_matches('\\'));
+        }
+    }
+    for m in windows_path_re().find_iter(text) {
+        push("path", m.as_str());
+    }
+    for caps in unix_path_re().captures_iter(text) {
+        push("path", &caps[1]);
+    }
+    for m in wallet_re().find_iter(text) {
+        if is_wallet(m.as_str()) {
+            push("wallet", m.as_str());
+        }
+    }
+    for m in base64_re().find_iter(text) {
+        if looks_base64(m.as_str()) {
+            push("base64", m.as_str());
+        }
+    }
+    out
+}
+
+/// Legacy Bitcoin addresses carry a checksum, so random base58 runs are rejected here.
+fn is_wallet(value: &str) -> bool {
+    if value.starts_with('1') || value.starts_with('3') {
+        return base58_check(value);
+    }
+    // Ethereum addresses that are all one case are usually hashes, checksummed ones mix cases.
+    if let Some(hex) = value.strip_prefix("0x") {
+        return hex.chars().any(|c| c.is_ascii_digit()) && hex.chars().any(|c| c.is_ascii_alphabetic());
+    }
+    true
+}
+
+fn base58_check(value: &str) -> bool {
+    const ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+    let mut bytes = [0u8; 25];
+    for c in value.bytes() {
+        let Some(mut carry) = ALPHABET.iter().position(|&a| a == c).map(|v| v as u32) else {
+            return false;
+        };
+        for byte in bytes.iter_mut().rev() {
+            carry += *byte as u32 * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        if carry != 0 {
+            return false;
+        }
+    }
+    let checksum = Sha256::digest(Sha256::digest(&bytes[..21]));
+    checksum[..4] == bytes[21..]
+}
+
+/// Long runs of the base64 alphabet are common (hex digests, identifiers), so ask for
+/// a valid length and a real mix of character classes.
+fn looks_base64(value: &str) -> bool {
+    let body = value.trim_end_matches('=');
+    if !value.len().is_multiple_of(4) || value.len() - body.len() > 2 {
+        return false;
+    }
+    let upper = body.bytes().any(|b| b.is_ascii_uppercase());
+    let lower = body.bytes().any(|b| b.is_ascii_lowercase());
+    let digit = body.bytes().any(|b| b.is_ascii_digit());
+    upper && lower && digit
+}
+
+/// URLs, IPv4 addresses, domains and email addresses found in free text, deduplicated in order.
+pub fn extract(text: &str) -> Vec<Indicator> {
+    let mut seen = HashSet::new();
+    let mut out = vec![];
+    let mut push = |kind: &'static str, value: String| {
+        let indicator = Indicator { kind, value };
+        if seen.insert(indicator.clone()) {
+            out.push(indicator);
+        }
+    };
+
+    for m in url_re().find_iter(text) {
+        let url = m.as_str().trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        push("url", url.to_string());
+        if let Some(host) = url_host(url) {
+            if is_ipv4(host) {
+                push("ip", host.to_string());
+            } else if host.contains('.') {
+                push("domain", host.to_lowercase());
+            }
+        }
+    }
+    for caps in email_re().captures_iter(text) {
+        push("email", caps[0].to_lowercase());
+        if is_known_tld(&caps[1]) {
+            push("domain", caps[1].to_lowercase());
+        }
+    }
+    for m in ipv4_re().find_iter(text) {
+        if is_ipv4(m.as_str()) {
+            push("ip", m.as_str().to_string());
+        }
+    }
+    for caps in domain_re().captures_iter(text) {
+        if is_known_tld(&caps[1]) {
+            push("domain", caps[0].to_lowercase());
+        }
+    }
+    out
+}
+
+pub fn url_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://")?.1;
+    let authority = rest.split(['/', '?', '#']).next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = match host.strip_prefix('[') {
+        Some(v6) => v6.split(']').next()?,
+        None => host.split(':').next()?,
+    };
+    (!host.is_empty()).then_some(host)
+}
+
+pub fn is_ipv4(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('.').collect();
+    parts.len() == 4 && parts.iter().all(|p| !p.is_empty() && p.len() <= 3 && p.parse::<u8>().is_ok())
+}
+
+fn is_known_tld(domain: &str) -> bool {
+    let tld = domain.rsplit('.').next().unwrap_or(domain).to_lowercase();
+    TLDS.contains(&tld.as_str())
+}
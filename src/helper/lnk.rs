@@ -0,0 +1,439 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+
+const LINK_CLSID: [u8; 16] = [0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46];
+
+const HAS_TARGET_ID_LIST: u32 = 0x0001;
+const HAS_LINK_INFO: u32 = 0x0002;
+const HAS_NAME: u32 = 0x0004;
+const HAS_RELATIVE_PATH: u32 = 0x0008;
+const HAS_WORKING_DIR: u32 = 0x0010;
+const HAS_ARGUMENTS: u32 = 0x0020;
+const HAS_ICON_LOCATION: u32 = 0x0040;
+const IS_UNICODE: u32 = 0x0080;
+
+/// Programs that turn a shortcut into a downloader or script launcher.
+const LOLBINS: &[&str] = &[
+    "powershell", "pwsh", "cmd.exe", "mshta", "wscript", "cscript", "rundll32", "regsvr32", "certutil",
+    "bitsadmin", "msiexec", "forfiles", "conhost", "curl", "wmic", "schtasks",
+];
+
+// Arguments longer than this do not fit the Properties dialog and are rarely legitimate.
+const LONG_ARGUMENTS: usize = 200;
+
+pub struct Link {
+    pub flags: u32,
+    pub file_attributes: u32,
+    pub created: Option<DateTime<Utc>>,
+    pub accessed: Option<DateTime<Utc>>,
+    pub modified: Option<DateTime<Utc>>,
+    pub file_size: u32,
+    pub show_command: u32,
+    pub id_list_path: Option<String>,
+    pub local_path: Option<String>,
+    pub network_path: Option<String>,
+    pub drive_type: Option<u32>,
+    pub volume_serial: Option<u32>,
+    pub volume_label: Option<String>,
+    pub name: Option<String>,
+    pub relative_path: Option<String>,
+    pub working_dir: Option<String>,
+    pub arguments: Option<String>,
+    pub icon_location: Option<String>,
+    pub environment_target: Option<String>,
+    pub tracker: Option<Tracker>,
+    /// Names of the extra data blocks present, in file order.
+    pub extra_blocks: Vec<String>,
+    /// Bytes left after the terminal extra data block.
+    pub trailing: usize,
+}
+
+pub struct Tracker {
+    pub machine_id: String,
+    pub mac: Option<String>,
+    pub volume_droid: String,
+    pub file_droid: String,
+}
+
+impl Link {
+    /// Best guess of what the shortcut runs.
+    pub fn target(&self) -> Option<&str> {
+        self.local_path.as_deref()
+            .or(self.network_path.as_deref())
+            .or(self.environment_target.as_deref())
+            .or(self.id_list_path.as_deref())
+            .or(self.relative_path.as_deref())
+    }
+}
+
+pub fn parse(data: &[u8]) -> Result<Link> {
+    if data.len() < 0x4C || u32_at(data, 0) != Some(0x4C) || data[4..20] != LINK_CLSID {
+        bail!("Not a Shell Link (.lnk) file");
+    }
+    let flags = u32_at(data, 0x14).unwrap_or_default();
+    let mut link = Link {
+        flags,
+        file_attributes: u32_at(data, 0x18).unwrap_or_default(),
+        created: filetime(u64_at(data, 0x1C)),
+        accessed: filetime(u64_at(data, 0x24)),
+        modified: filetime(u64_at(data, 0x2C)),
+        file_size: u32_at(data, 0x34).unwrap_or_default(),
+        show_command: u32_at(data, 0x3C).unwrap_or_default(),
+        id_list_path: None,
+        local_path: None,
+        network_path: None,
+        drive_type: None,
+        volume_serial: None,
+        volume_label: None,
+        name: None,
+        relative_path: None,
+        working_dir: None,
+        arguments: None,
+        icon_location: None,
+        environment_target: None,
+        tracker: None,
+        extra_blocks: vec![],
+        trailing: 0,
+    };
+    let mut pos = 0x4C;
+
+    if flags & HAS_TARGET_ID_LIST != 0 {
+        let size = u16_at(data, pos).unwrap_or_default() as usize;
+        link.id_list_path = id_list_path(data.get(pos + 2..pos + 2 + size).unwrap_or_default());
+        pos += 2 + size;
+    }
+
+    if flags & HAS_LINK_INFO != 0 {
+        let size = u32_at(data, pos).unwrap_or_default() as usize;
+        if let Some(info) = data.get(pos..pos + size) {
+            link_info(info, &mut link);
+        }
+        pos += size;
+    }
+
+    let unicode = flags & IS_UNICODE != 0;
+    for (flag, field) in [
+        (HAS_NAME, &mut link.name),
+        (HAS_RELATIVE_PATH, &mut link.relative_path),
+        (HAS_WORKING_DIR, &mut link.working_dir),
+        (HAS_ARGUMENTS, &mut link.arguments),
+        (HAS_ICON_LOCATION, &mut link.icon_location),
+    ] {
+        if flags & flag != 0 {
+            let count = u16_at(data, pos).unwrap_or_default() as usize;
+            let len = if unicode { count * 2 } else { count };
+            let bytes = data.get(pos + 2..pos + 2 + len).unwrap_or_default();
+            *field = Some(if unicode { utf16(bytes) } else { ansi(bytes) });
+            pos += 2 + len;
+        }
+    }
+
+    extra_data(data, pos, &mut link);
+    Ok(link)
+}
+
+// Follows root, volume and file entry shell items into a path.
+fn id_list_path(list: &[u8]) -> Option<String> {
+    let mut parts: Vec<String> = vec![];
+    let mut pos = 0;
+    while let Some(size) = u16_at(list, pos).map(|s| s as usize) {
+        if size < 3 {
+            break;
+        }
+        let Some(item) = list.get(pos..pos + size) else { break };
+        match item[2] & 0x70 {
+            // Volume: "C:\"
+            0x20 => parts.push(cstr(item.get(3..).unwrap_or_default()).trim_end_matches('\\').to_string()),
+            // File entry: prefer the long name from the 0xBEEF0004 extension block.
+            0x30 => {
+                let short = cstr(item.get(14..).unwrap_or_default());
+                parts.push(long_name(item).unwrap_or(short));
+            }
+            // Network location: "\\server\share"
+            0x40 => parts.push(cstr(item.get(5..).unwrap_or_default())),
+            _ => {}
+        }
+        pos += size;
+    }
+    let parts: Vec<String> = parts.into_iter().filter(|p| !p.is_empty()).collect();
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("\\"))
+}
+
+fn long_name(item: &[u8]) -> Option<String> {
+    let sig = item.windows(4).position(|w| w == [0x04, 0x00, 0xEF, 0xBE])?;
+    let ext = sig.checked_sub(4)?;
+    let version = u16_at(item, ext + 2)?;
+    let offset = match version {
+        9.. => 0x2E,
+        8 => 0x2A,
+        7 => 0x26,
+        3..=6 => 0x14,
+        _ => return None,
+    };
+    let name = utf16_cstr(item.get(ext + offset..)?);
+    (!name.is_empty()).then_some(name)
+}
+
+fn link_info(info: &[u8], link: &mut Link) {
+    let header_size = u32_at(info, 4).unwrap_or_default();
+    let info_flags = u32_at(info, 8).unwrap_or_default();
+
+    if info_flags & 1 != 0 {
+        let volume_offset = u32_at(info, 12).unwrap_or_default() as usize;
+        if let Some(volume) = info.get(volume_offset..) {
+            link.drive_type = u32_at(volume, 4);
+            link.volume_serial = u32_at(volume, 8);
+            let label_offset = u32_at(volume, 12).unwrap_or_default() as usize;
+            link.volume_label = if label_offset == 0x14 {
+                u32_at(volume, 16).map(|o| utf16_cstr(volume.get(o as usize..).unwrap_or_default()))
+            } else {
+                Some(cstr(volume.get(label_offset..).unwrap_or_default()))
+            }
+            .filter(|l| !l.is_empty());
+        }
+        let base = if header_size >= 0x24 {
+            u32_at(info, 0x1C).map(|o| utf16_cstr(info.get(o as usize..).unwrap_or_default()))
+        } else {
+            None
+        };
+        let base = base.filter(|b| !b.is_empty())
+            .or_else(|| u32_at(info, 16).map(|o| cstr(info.get(o as usize..).unwrap_or_default())));
+        let suffix = u32_at(info, 24).map(|o| cstr(info.get(o as usize..).unwrap_or_default())).unwrap_or_default();
+        link.local_path = base.map(|b| format!("{}{}", b, suffix)).filter(|p| !p.is_empty());
+    }
+
+    if info_flags & 2 != 0 {
+        let net_offset = u32_at(info, 20).unwrap_or_default() as usize;
+        if let Some(net) = info.get(net_offset..) {
+            let name_offset = u32_at(net, 8).unwrap_or_default() as usize;
+            let share = cstr(net.get(name_offset..).unwrap_or_default());
+            let suffix = u32_at(info, 24).map(|o| cstr(info.get(o as usize..).unwrap_or_default())).unwrap_or_default();
+            if !share.is_empty() {
+                link.network_path = Some(if suffix.is_empty() { share } else { format!("{}\\{}", share, suffix) });
+            }
+        }
+    }
+}
+
+fn extra_data(data: &[u8], mut pos: usize, link: &mut Link) {
+    while let Some(size) = u32_at(data, pos).map(|s| s as usize) {
+        if size < 4 {
+            pos += 4;
+            break;
+        }
+        let block = data.get(pos..pos + size).unwrap_or(&data[pos..]);
+        let signature = u32_at(block, 4).unwrap_or_default();
+        let name = match signature {
+            0xA000_0001 => "EnvironmentVariables",
+            0xA000_0002 => "Console",
+            0xA000_0003 => "Tracker",
+            0xA000_0004 => "ConsoleFE",
+            0xA000_0005 => "SpecialFolder",
+            0xA000_0006 => "Darwin",
+            0xA000_0007 => "IconEnvironment",
+            0xA000_0008 => "Shim",
+            0xA000_0009 => "PropertyStore",
+            0xA000_000B => "KnownFolder",
+            0xA000_000C => "VistaAndAboveIDList",
+            _ => "unknown",
+        };
+        link.extra_blocks.push(format!("{} (0x{:08X}, {} bytes)", name, signature, size));
+
+        match signature {
+            0xA000_0001 => {
+                let unicode = utf16_cstr(block.get(268..788).unwrap_or_default());
+                let target = if unicode.is_empty() { cstr(block.get(8..268).unwrap_or_default()) } else { unicode };
+                link.environment_target = Some(target).filter(|t| !t.is_empty());
+            }
+            0xA000_0003 if block.len() >= 0x60 => link.tracker = Some(tracker(block)),
+            _ => {}
+        }
+        pos += size;
+        if pos >= data.len() {
+            break;
+        }
+    }
+    link.trailing = data.len().saturating_sub(pos);
+}
+
+// TrackerDataBlock: NetBIOS name of the machine that created the link plus two
+// distributed link tracking GUIDs; the file GUID is a version 1 UUID whose node is the MAC.
+fn tracker(block: &[u8]) -> Tracker {
+    let machine_id = cstr(&block[16..32]);
+    let volume_droid = guid(&block[32..48]);
+    let file = &block[48..64];
+    let version = file[7] >> 4;
+    let mac = (version == 1).then(|| file[10..16].iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":"));
+    Tracker { machine_id, mac, volume_droid, file_droid: guid(file) }
+}
+
+fn guid(b: &[u8]) -> String {
+    format!("{{{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{}}}",
+        u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        u16::from_le_bytes([b[4], b[5]]),
+        u16::from_le_bytes([b[6], b[7]]),
+        b[8], b[9],
+        b[10..16].iter().map(|x| format!("{:02X}", x)).collect::<String>())
+}
+
+/// Reasons the link looks like a launcher for a hidden payload.
+pub fn suspicious(link: &Link) -> Vec<String> {
+    let mut out = vec![];
+    // Every target field counts: the environment block can point elsewhere than LinkInfo.
+    let target = [&link.local_path, &link.network_path, &link.environment_target, &link.id_list_path]
+        .iter()
+        .filter_map(|t| t.as_deref())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_ascii_lowercase();
+    let args = link.arguments.as_deref().unwrap_or_default();
+    let lower_args = args.to_ascii_lowercase();
+
+    for bin in LOLBINS {
+        if target.contains(bin) {
+            out.push(format!("target runs {}", bin));
+        } else if lower_args.contains(bin) {
+            out.push(format!("arguments invoke {}", bin));
+        }
+    }
+    if args.chars().count() > LONG_ARGUMENTS {
+        out.push(format!("command line arguments are {} characters long", args.chars().count()));
+    }
+    // Padding pushes the real command out of the visible part of the Target field.
+    if args.starts_with("    ") || args.contains(['\n', '\r']) || args.contains("\t\t") {
+        out.push("arguments are padded with whitespace or line breaks to hide the command".to_string());
+    }
+    for marker in ["-enc", "-encodedcommand", "frombase64string", "-w hidden", "-windowstyle hidden", "-nop", "iex", "invoke-expression", "downloadstring", "http://", "https://", "\\\\"] {
+        if lower_args.contains(marker) {
+            out.push(format!("arguments contain '{}'", marker));
+        }
+    }
+    if link.show_command == 7 && !target.is_empty() {
+        out.push("window starts minimized (SW_SHOWMINNOACTIVE)".to_string());
+    }
+    if let Some(icon) = &link.icon_location {
+        let icon = icon.to_ascii_lowercase();
+        let disguised = [".pdf", ".doc", ".xls", ".jpg", ".png", ".txt", "imageres.dll", "shell32.dll", "msedge", "acro"]
+            .iter()
+            .any(|ext| icon.contains(ext));
+        if disguised && !target.is_empty() && !icon.contains(&target) {
+            out.push(format!("icon '{}' disguises the real target", link.icon_location.as_deref().unwrap_or_default()));
+        }
+    }
+    if link.trailing > 0 {
+        out.push(format!("{} byte(s) appended after the end of the link (embedded payload)", link.trailing));
+    }
+    out
+}
+
+pub fn print_report(path: &str, link: &Link) {
+    let show = |label: &str, value: &Option<String>| {
+        if let Some(value) = value {
+            println!("{:<18}{}", label, value);
+        }
+    };
+    let time = |t: &Option<DateTime<Utc>>| t.map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string());
+
+    println!("Shell Link Report for '{}':", path);
+    show("Target:", &link.target().map(str::to_string));
+    show("ID list path:", &link.id_list_path);
+    show("Local path:", &link.local_path);
+    show("Network path:", &link.network_path);
+    show("Environment path:", &link.environment_target);
+    show("Arguments:", &link.arguments);
+    show("Working dir:", &link.working_dir);
+    show("Relative path:", &link.relative_path);
+    show("Icon location:", &link.icon_location);
+    show("Description:", &link.name);
+    println!("{:<18}{}", "Show command:", match link.show_command {
+        3 => "maximized",
+        7 => "minimized",
+        _ => "normal",
+    });
+
+    println!("\n=== Target timestamps ===");
+    show("Created:", &time(&link.created));
+    show("Accessed:", &time(&link.accessed));
+    show("Modified:", &time(&link.modified));
+    println!("{:<18}{} bytes", "Target size:", link.file_size);
+
+    println!("\n=== Volume and machine ===");
+    if let Some(drive) = link.drive_type {
+        println!("{:<18}{}", "Drive type:", match drive {
+            2 => "removable",
+            3 => "fixed",
+            4 => "network",
+            5 => "CD-ROM",
+            6 => "RAM disk",
+            _ => "unknown",
+        });
+    }
+    show("Volume serial:", &link.volume_serial.map(|s| format!("{:04X}-{:04X}", s >> 16, s & 0xFFFF)));
+    show("Volume label:", &link.volume_label);
+    if let Some(tracker) = &link.tracker {
+        println!("{:<18}{}", "Machine ID:", tracker.machine_id);
+        show("MAC address:", &tracker.mac);
+        println!("{:<18}{}", "Volume droid:", tracker.volume_droid);
+        println!("{:<18}{}", "File droid:", tracker.file_droid);
+    }
+
+    if !link.extra_blocks.is_empty() {
+        println!("\n=== Extra data blocks ===");
+        for block in &link.extra_blocks {
+            println!("  {}", block);
+        }
+    }
+
+    let reasons = suspicious(link);
+    println!("\n=== Suspicious indicators ===");
+    if reasons.is_empty() {
+        println!("None.");
+    }
+    for reason in reasons {
+        println!("  Warning: {}", reason);
+    }
+}
+
+// FILETIME: 100ns intervals since 1601-01-01.
+fn filetime(value: Option<u64>) -> Option<DateTime<Utc>> {
+    let value = value.filter(|&v| v != 0)?;
+    let secs = (value / 10_000_000) as i64 - 11_644_473_600;
+    DateTime::from_timestamp(secs, ((value % 10_000_000) * 100) as u32)
+}
+
+fn cstr(bytes: &[u8]) -> String {
+    ansi(&bytes[..bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len())])
+}
+
+// Shortcuts store the ANSI code page of the creating machine; Latin-1 keeps every byte visible.
+fn ansi(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+fn utf16(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn utf16_cstr(bytes: &[u8]) -> String {
+    let units: Vec<u16> = bytes.chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    data.get(pos..pos + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    data.get(pos..pos + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Option<u64> {
+    data.get(pos..pos + 8).map(|b| u64::from_le_bytes(b.try_into().unwrap_or_default()))
+}
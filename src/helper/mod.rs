@@ -1,3 +1,37 @@
 pub mod ui;
 pub mod domain_typosquat;
 pub mod domain_mail;
+pub mod ssl_cert;
+pub mod ssl_live;
+pub mod ssl_check;
+pub mod ssl_file;
+pub mod export;
+pub mod ssl_ciphers;
+pub mod ssl_starttls;
+#[cfg(test)]
+pub mod ssl_test;
+pub mod pdf_decode;
+pub mod pdf_image;
+pub mod pdf_triage;
+pub mod hashes;
+pub mod pdf_attach;
+pub mod ioc;
+pub mod pdf_ioc;
+pub mod pdf_load;
+pub mod pdf_meta;
+pub mod zip;
+pub mod office_vba;
+pub mod office;
+pub mod entropy;
+pub mod exe;
+pub mod lnk;
+pub mod email;
+pub mod email_msg;
+pub mod file_info;
+pub mod fuzzy;
+pub mod yara_parse;
+pub mod yara_eval;
+pub mod strings;
+pub mod tar;
+pub mod rar;
+pub mod archive;
@@ -0,0 +1,460 @@
+use anyhow::{bail, Context, Result};
+use cfb::CompoundFile;
+use regex::Regex;
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::OnceLock;
+use crate::helper::{hashes, office_vba, zip};
+
+const OLE_MAGIC: &[u8] = b"\xD0\xCF\x11\xE0\xA1\xB1\x1A\xE1";
+
+/// Procedures Office runs on its own when a document is opened, created or closed.
+const AUTO_EXEC: &[&str] = &[
+    "AutoOpen", "Auto_Open", "AutoExec", "AutoNew", "Auto_New", "AutoClose", "Auto_Close", "AutoExit",
+    "Document_Open", "Document_New", "Document_Close", "Document_BeforeClose", "Document_ContentControlOnEnter",
+    "DocumentOpen", "DocumentBeforeClose", "Workbook_Open", "Workbook_Activate", "Workbook_BeforeClose",
+    "Workbook_Deactivate", "Auto_Activate", "Presentation_Open", "Worksheet_Change", "Worksheet_Calculate",
+    "InkPicture1_Painted", "Frame1_Layout", "MultiPage1_Layout",
+];
+
+/// Calls and strings commonly used by droppers and downloaders.
+const SUSPICIOUS: &[(&str, &str)] = &[
+    ("Shell", "may run an executable file or a system command"),
+    ("WScript.Shell", "may run an executable file or a system command"),
+    ("Shell.Application", "may run an application"),
+    ("ShellExecute", "may run an executable file or a system command"),
+    ("CreateObject", "may create an OLE object (COM automation)"),
+    ("GetObject", "may get an OLE object with a running instance"),
+    ("CallByName", "may call a method by name, hiding the real call"),
+    ("URLDownloadToFile", "may download files from the Internet"),
+    ("XMLHTTP", "may download or upload data over HTTP"),
+    ("WinHttp", "may download or upload data over HTTP"),
+    ("ADODB.Stream", "may create a text or binary file"),
+    ("SaveToFile", "may create a text or binary file"),
+    ("Open", "may open a file"),
+    ("Kill", "may delete a file"),
+    ("Environ", "may read system environment variables"),
+    ("powershell", "may run PowerShell commands"),
+    ("cmd.exe", "may run a command prompt"),
+    ("mshta", "may run HTML applications"),
+    ("regsvr32", "may register or run a DLL"),
+    ("rundll32", "may run a DLL"),
+    ("certutil", "may decode or download files"),
+    ("bitsadmin", "may download files"),
+    ("schtasks", "may create a scheduled task"),
+    ("Chr", "may obfuscate strings with character codes"),
+    ("ChrW", "may obfuscate strings with character codes"),
+    ("StrReverse", "may obfuscate strings"),
+    ("Base64", "may decode Base64 data"),
+    ("Declare", "may call Windows API functions"),
+    ("Lib", "may call a function from a DLL"),
+    ("VirtualAlloc", "may allocate executable memory (shellcode)"),
+    ("RtlMoveMemory", "may copy shellcode into memory"),
+    ("CreateThread", "may run shellcode in a new thread"),
+    ("VBProject", "may modify VBA code (self-replication)"),
+    ("ExecuteExcel4Macro", "may run Excel 4 macros"),
+];
+
+pub enum Format {
+    /// Binary .doc / .xls / .ppt.
+    Ole(String),
+    /// Zip-based .docx / .docm / .xlsx / .xlsm / .pptx...
+    OpenXml(String),
+}
+
+pub struct Module {
+    /// Where the VBA project was found (OLE storage, or zip member for OpenXML).
+    pub location: String,
+    pub name: String,
+    pub code: String,
+}
+
+pub struct KeywordHit {
+    pub keyword: &'static str,
+    pub description: &'static str,
+    pub modules: Vec<String>,
+}
+
+pub struct Relationship {
+    pub part: String,
+    pub kind: String,
+    pub target: String,
+    /// Reason the relationship is worth a look (remote template, OLE link...).
+    pub flag: Option<&'static str>,
+}
+
+pub struct Embedded {
+    pub location: String,
+    /// OLE class or ProgID from CompObj, content type for OpenXML parts.
+    pub class: Option<String>,
+    /// Original file name and path from an Ole10Native package.
+    pub label: Option<String>,
+    pub source_path: Option<String>,
+    pub data: Vec<u8>,
+    pub hashes: hashes::Hashes,
+    pub note: Option<String>,
+}
+
+pub struct Report {
+    pub format: Format,
+    pub modules: Vec<Module>,
+    pub auto_exec: Vec<(String, String)>,
+    pub keywords: Vec<KeywordHit>,
+    pub relationships: Vec<Relationship>,
+    pub embedded: Vec<Embedded>,
+    pub warnings: Vec<String>,
+}
+
+pub fn analyze(data: &[u8]) -> Result<Report> {
+    let mut report = if data.starts_with(OLE_MAGIC) {
+        analyze_ole(data)?
+    } else if data.starts_with(b"PK\x03\x04") {
+        analyze_openxml(data)?
+    } else {
+        bail!("Not an Office document (neither OLE2 nor OpenXML)");
+    };
+
+    for module in &report.modules {
+        for name in procedures(&module.code) {
+            if AUTO_EXEC.iter().any(|a| a.eq_ignore_ascii_case(&name))
+                && !report.auto_exec.iter().any(|(n, m)| n == &name && m == &module.name) {
+                report.auto_exec.push((name, module.name.clone()));
+            }
+        }
+        for (keyword, description) in SUSPICIOUS {
+            if !contains_word(&module.code, keyword) {
+                continue;
+            }
+            match report.keywords.iter_mut().find(|h| h.keyword == *keyword) {
+                Some(hit) if hit.modules.contains(&module.name) => {}
+                Some(hit) => hit.modules.push(module.name.clone()),
+                None => report.keywords.push(KeywordHit { keyword, description, modules: vec![module.name.clone()] }),
+            }
+        }
+    }
+    Ok(report)
+}
+
+pub fn format_name(format: &Format) -> String {
+    match format {
+        Format::Ole(kind) => format!("OLE2 compound file ({})", kind),
+        Format::OpenXml(kind) => format!("OpenXML package ({})", kind),
+    }
+}
+
+fn analyze_ole(data: &[u8]) -> Result<Report> {
+    let cfb = CompoundFile::open(Cursor::new(data)).context("Failed to parse OLE2 compound file")?;
+    let streams: Vec<String> = cfb.walk().map(|e| e.path().to_string_lossy().into_owned()).collect();
+    let kind = if streams.iter().any(|s| s == "/WordDocument") {
+        "Word document"
+    } else if streams.iter().any(|s| s == "/Workbook" || s == "/Book") {
+        "Excel workbook"
+    } else if streams.iter().any(|s| s == "/PowerPoint Document") {
+        "PowerPoint presentation"
+    } else {
+        "unknown application"
+    };
+
+    let mut report = empty_report(Format::Ole(kind.to_string()));
+    vba_modules(data, "", &mut report);
+    ole_objects(data, "", &mut report);
+    Ok(report)
+}
+
+fn analyze_openxml(data: &[u8]) -> Result<Report> {
+    let entries = zip::entries(data)?;
+    let content_types = zip::find(&entries, "[Content_Types].xml")
+        .and_then(|e| zip::read(data, e).ok())
+        .map(|d| String::from_utf8_lossy(&d).into_owned())
+        .unwrap_or_default();
+    let kind = if content_types.contains("wordprocessingml") {
+        "Word"
+    } else if content_types.contains("spreadsheetml") || content_types.contains("ms-excel") {
+        "Excel"
+    } else if content_types.contains("presentationml") {
+        "PowerPoint"
+    } else {
+        "unknown application"
+    };
+    let kind = if content_types.contains("macroEnabled") { format!("{}, macro-enabled", kind) } else { kind.to_string() };
+
+    let mut report = empty_report(Format::OpenXml(kind));
+    for entry in entries.iter().filter(|e| !e.is_dir()) {
+        let lower = entry.name.to_ascii_lowercase();
+        let part = match zip::read(data, entry) {
+            Ok(part) => part,
+            Err(e) => {
+                report.warnings.push(e.to_string());
+                continue;
+            }
+        };
+
+        if lower.ends_with(".rels") {
+            relationships(&entry.name, &String::from_utf8_lossy(&part), &mut report);
+        } else if part.starts_with(OLE_MAGIC) {
+            // vbaProject.bin, embeddings/oleObject*.bin, activeX/*.bin
+            vba_modules(&part, &entry.name, &mut report);
+            ole_objects(&part, &entry.name, &mut report);
+            if !lower.ends_with("vbaproject.bin") && !report.embedded.iter().any(|e| e.location.starts_with(&entry.name)) {
+                report.embedded.push(embedded(entry.name.clone(), class_of(&part), part, None));
+            }
+        } else if lower.contains("/embeddings/") {
+            report.embedded.push(embedded(entry.name.clone(), None, part, None));
+        }
+    }
+    Ok(report)
+}
+
+fn empty_report(format: Format) -> Report {
+    Report {
+        format,
+        modules: vec![],
+        auto_exec: vec![],
+        keywords: vec![],
+        relationships: vec![],
+        embedded: vec![],
+        warnings: vec![],
+    }
+}
+
+fn vba_modules(ole: &[u8], location: &str, report: &mut Report) {
+    match office_vba::extract(ole) {
+        Ok(modules) => report.modules.extend(modules.into_iter().map(|m| Module {
+            location: match (location, m.project.as_str()) {
+                ("", project) => project.to_string(),
+                (part, "/") => part.to_string(),
+                (part, project) => format!("{}{}", part, project),
+            },
+            name: m.name,
+            code: m.code,
+        })),
+        Err(e) => report.warnings.push(format!("{}: VBA project: {:#}", if location.is_empty() { "/" } else { location }, e)),
+    }
+}
+
+// Storages (other than the root) carrying a CompObj stream are embedded OLE objects.
+fn ole_objects(ole: &[u8], location: &str, report: &mut Report) {
+    let Ok(mut cfb) = CompoundFile::open(Cursor::new(ole)) else { return };
+    let mut storages: Vec<String> = cfb.walk()
+        .filter(|e| e.is_stream() && (e.name() == "\u{1}CompObj" || e.name() == "\u{1}Ole10Native"))
+        .filter_map(|e| e.path().parent().map(|p| p.to_string_lossy().into_owned()))
+        .collect();
+    storages.dedup();
+
+    for storage in storages {
+        let base = Path::new(&storage);
+        let class = office_vba::read_stream(&mut cfb, &base.join("\u{1}CompObj")).ok().and_then(|c| comp_obj_class(&c));
+        let native = office_vba::read_stream(&mut cfb, &base.join("\u{1}Ole10Native")).ok();
+        // The root of an embedded part is the object itself, it is reported by the caller.
+        if storage == "/" && native.is_none() {
+            continue;
+        }
+        let name = format!("{}{}", location, storage);
+
+        match native.as_deref().and_then(ole10_native) {
+            Some((label, source_path, payload)) => {
+                let mut object = embedded(name, class, payload, None);
+                object.label = Some(label);
+                object.source_path = Some(source_path);
+                report.embedded.push(object);
+            }
+            None => {
+                let contents = ["CONTENTS", "Package", "\u{1}Ole10Native", "Equation Native"].iter()
+                    .find_map(|s| office_vba::read_stream(&mut cfb, &base.join(s)).ok())
+                    .unwrap_or_default();
+                report.embedded.push(embedded(name, class, contents, None));
+            }
+        }
+    }
+}
+
+fn embedded(location: String, class: Option<String>, data: Vec<u8>, note: Option<String>) -> Embedded {
+    let note = note.or_else(|| match class.as_deref() {
+        Some(c) if c.contains("Equation.3") => Some("Equation Editor object (CVE-2017-11882 / CVE-2018-0802 exploit vehicle)".to_string()),
+        Some(c) if c.starts_with("Package") => Some("OLE Package, can wrap any file type".to_string()),
+        _ if data.starts_with(b"MZ") => Some("payload is a Windows executable".to_string()),
+        _ => None,
+    });
+    Embedded {
+        location,
+        class,
+        label: None,
+        source_path: None,
+        hashes: hashes::compute(&data),
+        data,
+        note,
+    }
+}
+
+fn class_of(ole: &[u8]) -> Option<String> {
+    let mut cfb = CompoundFile::open(Cursor::new(ole)).ok()?;
+    let data = office_vba::read_stream(&mut cfb, Path::new("/\u{1}CompObj")).ok()?;
+    comp_obj_class(&data)
+}
+
+// CompObj holds length-prefixed ANSI strings: user type, clipboard format, ProgID.
+fn comp_obj_class(data: &[u8]) -> Option<String> {
+    let strings: Vec<String> = data.split(|b| !(0x20..=0x7E).contains(b))
+        .filter(|s| s.len() >= 4)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .collect();
+    if strings.is_empty() {
+        return None;
+    }
+    Some(strings.join(" / "))
+}
+
+// MS-OLEDS Ole10Native: size, flags, label, source path, reserved, temp path, data size, data.
+fn ole10_native(data: &[u8]) -> Option<(String, String, Vec<u8>)> {
+    let mut pos = 6;
+    let label = cstring(data, &mut pos)?;
+    let source_path = cstring(data, &mut pos)?;
+    pos += 8;
+    cstring(data, &mut pos)?;
+    let size = u32::from_le_bytes(data.get(pos..pos + 4)?.try_into().ok()?) as usize;
+    pos += 4;
+    let payload = data.get(pos..pos + size.min(data.len() - pos))?.to_vec();
+    Some((label, source_path, payload))
+}
+
+fn cstring(data: &[u8], pos: &mut usize) -> Option<String> {
+    let rest = data.get(*pos..)?;
+    let end = rest.iter().position(|&b| b == 0)?;
+    *pos += end + 1;
+    Some(rest[..end].iter().map(|&b| b as char).collect())
+}
+
+fn relationships(part: &str, xml: &str, report: &mut Report) {
+    static REL: OnceLock<Regex> = OnceLock::new();
+    static ATTR: OnceLock<Regex> = OnceLock::new();
+    let rel = REL.get_or_init(|| Regex::new(r"<Relationship\b[^>]*>").expect("static regex"));
+    let attr = ATTR.get_or_init(|| Regex::new(r#"(\w+)\s*=\s*"([^"]*)""#).expect("static regex"));
+
+    for tag in rel.find_iter(xml) {
+        let mut kind = String::new();
+        let mut target = String::new();
+        let mut external = false;
+        for cap in attr.captures_iter(tag.as_str()) {
+            match &cap[1] {
+                "Type" => kind = cap[2].rsplit('/').next().unwrap_or_default().to_string(),
+                "Target" => target = unescape(&cap[2]),
+                "TargetMode" => external = cap[2].eq_ignore_ascii_case("External"),
+                _ => {}
+            }
+        }
+        let lower = target.to_ascii_lowercase();
+        let flag = if lower.starts_with("mhtml:") || lower.contains("!x-usc:") {
+            Some("MHTML link (MSHTML remote code execution)")
+        } else if !external {
+            if kind == "oleObject" || kind == "package" { Some("embedded OLE object") } else { None }
+        } else {
+            match kind.as_str() {
+                "attachedTemplate" => Some("remote template injection"),
+                "oleObject" => Some("linked OLE object (loaded on open)"),
+                "frame" | "subDocument" => Some("remote frame / sub-document"),
+                "externalLinkPath" => Some("external workbook link"),
+                _ if lower.starts_with("http") || lower.starts_with("\\\\") || lower.starts_with("file:") => Some("external target"),
+                _ => None,
+            }
+        };
+        // Internal links between package parts are noise, only keep what leaves the package or is flagged.
+        if external || flag.is_some() {
+            report.relationships.push(Relationship { part: part.to_string(), kind, target, flag });
+        }
+    }
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("&amp;", "&").replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'")
+}
+
+fn procedures(code: &str) -> Vec<String> {
+    static PROC: OnceLock<Regex> = OnceLock::new();
+    let proc = PROC.get_or_init(|| {
+        Regex::new(r"(?im)^[ \t]*(?:(?:public|private|friend)[ \t]+)?(?:static[ \t]+)?(?:sub|function)[ \t]+(\w+)")
+            .expect("static regex")
+    });
+    proc.captures_iter(code).map(|c| c[1].to_string()).collect()
+}
+
+fn contains_word(code: &str, keyword: &str) -> bool {
+    let code = code.to_ascii_lowercase();
+    let keyword = keyword.to_ascii_lowercase();
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+    code.match_indices(&keyword).any(|(i, _)| {
+        !is_word(code[..i].chars().next_back()) && !is_word(code[i + keyword.len()..].chars().next())
+    })
+}
+
+/// Module names mapped to safe file names, duplicates numbered.
+pub fn module_file_name(index: usize, module: &Module) -> String {
+    let safe: String = module.name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    format!("{}_{}.vba", index, safe)
+}
+
+pub fn print_report(path: &str, report: &Report) {
+    println!("Office Analysis Report for '{}':", path);
+    println!("Format: {}", format_name(&report.format));
+
+    println!("\n=== VBA Modules ===");
+    if report.modules.is_empty() {
+        println!("No VBA macros found.");
+    }
+    for module in &report.modules {
+        println!("  {:<24} {:>7} bytes   ({})", module.name, module.code.len(), module.location);
+    }
+
+    println!("\n=== Auto-exec triggers ===");
+    if report.auto_exec.is_empty() {
+        println!("None.");
+    }
+    for (name, module) in &report.auto_exec {
+        println!("  {:<24} in {}", name, module);
+    }
+
+    println!("\n=== Suspicious keywords ===");
+    if report.keywords.is_empty() {
+        println!("None.");
+    }
+    for hit in &report.keywords {
+        println!("  {:<20} {} ({})", hit.keyword, hit.description, hit.modules.join(", "));
+    }
+
+    println!("\n=== External relationships ===");
+    if report.relationships.is_empty() {
+        println!("None.");
+    }
+    for rel in &report.relationships {
+        println!("  [{}] {} -> {}", rel.kind, rel.part, rel.target);
+        if let Some(flag) = rel.flag {
+            println!("      Warning: {}", flag);
+        }
+    }
+
+    println!("\n=== Embedded objects ===");
+    if report.embedded.is_empty() {
+        println!("None.");
+    }
+    for object in &report.embedded {
+        println!("  {} ({} bytes)", object.location, object.data.len());
+        if let Some(class) = &object.class {
+            println!("      Class:  {}", class);
+        }
+        if let Some(label) = &object.label {
+            println!("      Label:  {}", label);
+        }
+        if let Some(source) = &object.source_path {
+            println!("      Path:   {}", source);
+        }
+        println!("      SHA256: {}", object.hashes.sha256);
+        if let Some(note) = &object.note {
+            println!("      Warning: {}", note);
+        }
+    }
+
+    for warning in &report.warnings {
+        println!("Warning: {}", warning);
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::{bail, Context, Result};
+use cfb::CompoundFile;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+
+pub struct Module {
+    /// Path of the VBA project storage inside the compound file.
+    pub project: String,
+    pub name: String,
+    pub code: String,
+}
+
+/// Every VBA module of every project found in an OLE2 compound file.
+pub fn extract(ole: &[u8]) -> Result<Vec<Module>> {
+    let mut cfb = CompoundFile::open(Cursor::new(ole)).context("Not a valid OLE2 compound file")?;
+
+    // A project is any storage holding a VBA/dir stream (Macros, _VBA_PROJECT_CUR, or the root).
+    let dirs: Vec<PathBuf> = cfb.walk()
+        .filter(|e| e.is_stream() && e.name().eq_ignore_ascii_case("dir"))
+        .filter(|e| e.path().parent()
+            .and_then(|p| p.file_name())
+            .is_some_and(|n| n.to_string_lossy().eq_ignore_ascii_case("VBA")))
+        .map(|e| e.path().to_path_buf())
+        .collect();
+
+    let mut modules = vec![];
+    for dir_path in dirs {
+        let vba_storage = dir_path.parent().unwrap_or(Path::new("/")).to_path_buf();
+        let project = vba_storage.parent().unwrap_or(Path::new("/")).to_string_lossy().into_owned();
+
+        let dir = decompress(&read_stream(&mut cfb, &dir_path)?)
+            .with_context(|| format!("Failed to decompress {}", dir_path.display()))?;
+        for (name, stream_name, offset) in parse_dir(&dir) {
+            let stream_path = vba_storage.join(&stream_name);
+            let code = match read_stream(&mut cfb, &stream_path) {
+                Ok(data) if (offset as usize) <= data.len() => match decompress(&data[offset as usize..]) {
+                    Ok(source) => latin1(&source),
+                    Err(e) => format!("' rex: failed to decompress module source: {}", e),
+                },
+                Ok(_) => "' rex: module offset is past the end of the stream".to_string(),
+                Err(e) => format!("' rex: module stream missing: {}", e),
+            };
+            modules.push(Module { project: project.clone(), name, code });
+        }
+    }
+    Ok(modules)
+}
+
+pub fn read_stream(cfb: &mut CompoundFile<Cursor<&[u8]>>, path: &Path) -> Result<Vec<u8>> {
+    let mut stream = cfb.open_stream(path)
+        .with_context(|| format!("Failed to open stream {}", path.display()))?;
+    let mut data = Vec::new();
+    stream.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// MS-OVBA 2.4.1 compression: 4 KiB chunks of literal bytes and back-reference tokens.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    if data.first() != Some(&0x01) {
+        bail!("missing compressed container signature");
+    }
+    let mut out = Vec::new();
+    let mut pos = 1;
+
+    while pos + 2 <= data.len() {
+        let header = u16::from_le_bytes([data[pos], data[pos + 1]]);
+        let chunk_end = (pos + (header & 0x0FFF) as usize + 3).min(data.len());
+        let compressed = header & 0x8000 != 0;
+        pos += 2;
+        let chunk_start = out.len();
+
+        if !compressed {
+            out.extend_from_slice(&data[pos..(pos + 4096).min(data.len())]);
+            pos = chunk_end;
+            continue;
+        }
+
+        while pos < chunk_end {
+            let flags = data[pos];
+            pos += 1;
+            for bit in 0..8 {
+                if pos >= chunk_end {
+                    break;
+                }
+                if flags & (1 << bit) == 0 {
+                    out.push(data[pos]);
+                    pos += 1;
+                    continue;
+                }
+                if pos + 2 > data.len() {
+                    bail!("truncated copy token");
+                }
+                let token = u16::from_le_bytes([data[pos], data[pos + 1]]);
+                pos += 2;
+
+                let decompressed = out.len() - chunk_start;
+                let mut bit_count = 4;
+                while (1usize << bit_count) < decompressed {
+                    bit_count += 1;
+                }
+                let length_mask = 0xFFFFu16 >> bit_count;
+                let length = (token & length_mask) as usize + 3;
+                let offset = ((token & !length_mask) >> (16 - bit_count)) as usize + 1;
+                if offset > out.len() - chunk_start {
+                    bail!("copy token points before the chunk start");
+                }
+                let from = out.len() - offset;
+                for i in 0..length {
+                    let byte = out[from + i];
+                    out.push(byte);
+                }
+            }
+        }
+        pos = chunk_end;
+    }
+    Ok(out)
+}
+
+// (module name, stream name, source offset) from the decompressed dir stream records.
+fn parse_dir(dir: &[u8]) -> Vec<(String, String, u32)> {
+    const PROJECTVERSION: u16 = 0x0009;
+    const MODULENAME: u16 = 0x0019;
+    const MODULESTREAMNAME: u16 = 0x001A;
+    const MODULEOFFSET: u16 = 0x0031;
+    const MODULE_END: u16 = 0x002B;
+
+    let mut modules = vec![];
+    let mut name = None;
+    let mut stream = None;
+    let mut offset = 0;
+    let mut pos = 0;
+
+    while pos + 6 <= dir.len() {
+        let id = u16::from_le_bytes([dir[pos], dir[pos + 1]]);
+        let mut size = u32::from_le_bytes([dir[pos + 2], dir[pos + 3], dir[pos + 4], dir[pos + 5]]) as usize;
+        // PROJECTVERSION declares 4 bytes but carries 6.
+        if id == PROJECTVERSION {
+            size = 6;
+        }
+        let body = dir.get(pos + 6..pos + 6 + size).unwrap_or_default();
+        match id {
+            MODULENAME => name = Some(latin1(body)),
+            MODULESTREAMNAME => stream = Some(latin1(body)),
+            MODULEOFFSET if body.len() >= 4 => offset = u32::from_le_bytes([body[0], body[1], body[2], body[3]]),
+            MODULE_END => {
+                if let Some(module) = name.take() {
+                    let stream_name = stream.take().unwrap_or_else(|| module.clone());
+                    modules.push((module, stream_name, offset));
+                }
+                offset = 0;
+            }
+            _ => {}
+        }
+        pos += 6 + size;
+    }
+    modules
+}
+
+// VBA uses the project code page, almost always Windows-1252; Latin-1 keeps every byte visible.
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
@@ -0,0 +1,125 @@
+use lopdf::{Dictionary, Document, ObjectId};
+use std::collections::HashSet;
+use crate::helper::hashes::{self, Hashes};
+use crate::helper::{pdf_decode, pdf_triage};
+use crate::helper::pdf_load::{resolve, string};
+
+pub struct Attachment {
+    /// Where the file was found: EmbeddedFiles tree, FileAttachment annotation, or a loose stream.
+    pub source: String,
+    /// Object id of the embedded file stream.
+    pub stream: u32,
+    pub filename: String,
+    pub description: Option<String>,
+    /// MIME type from the stream /Subtype.
+    pub mime: Option<String>,
+    /// Size declared in /Params, which droppers sometimes fake.
+    pub declared_size: Option<i64>,
+    pub data: Vec<u8>,
+    pub hashes: Hashes,
+    /// Decoding problem, if the filters could not all be applied.
+    pub note: Option<String>,
+}
+
+pub fn collect(doc: &Document) -> Vec<Attachment> {
+    let mut found = vec![];
+    let mut seen: HashSet<ObjectId> = HashSet::new();
+
+    if let Some(tree) = doc.catalog().ok()
+        .and_then(|c| c.get(b"Names").ok())
+        .and_then(|n| resolve(doc, n).as_dict().ok())
+        .and_then(|n| n.get(b"EmbeddedFiles").ok())
+    {
+        let mut leaves = vec![];
+        pdf_triage::name_tree_values(doc, tree, &mut leaves);
+        for (name, spec) in leaves {
+            if let Ok(spec) = resolve(doc, spec).as_dict() {
+                found.extend(from_file_spec(doc, spec, &name, &format!("EmbeddedFiles '{}'", name), &mut seen));
+            }
+        }
+    }
+
+    for (id, object) in &doc.objects {
+        let Ok(dict) = object.as_dict() else { continue };
+        if !matches!(dict.get(b"Subtype").and_then(|s| s.as_name()), Ok(b"FileAttachment")) {
+            continue;
+        }
+        if let Some(spec) = dict.get(b"FS").ok().and_then(|fs| resolve(doc, fs).as_dict().ok()) {
+            found.extend(from_file_spec(doc, spec, "", &format!("FileAttachment annotation obj {}", id.0), &mut seen));
+        }
+    }
+
+    // Embedded file streams nothing points to are still worth recovering.
+    for (id, object) in &doc.objects {
+        if seen.contains(id) {
+            continue;
+        }
+        if let Ok(stream) = object.as_stream() {
+            if matches!(stream.dict.get(b"Type").and_then(|t| t.as_name()), Ok(b"EmbeddedFile")) {
+                seen.insert(*id);
+                found.push(build(doc, *id, format!("obj_{}", id.0), None, "unreferenced EmbeddedFile stream".to_string()));
+            }
+        }
+    }
+    found
+}
+
+fn from_file_spec(doc: &Document, spec: &Dictionary, fallback_name: &str, source: &str, seen: &mut HashSet<ObjectId>) -> Vec<Attachment> {
+    let filename = ["UF", "F", "Unix", "DOS", "Mac"].iter()
+        .find_map(|key| spec.get(key.as_bytes()).ok().and_then(|v| string(doc, v)))
+        .unwrap_or_else(|| fallback_name.to_string());
+    let description = spec.get(b"Desc").ok().and_then(|d| string(doc, d));
+
+    let Some(ef) = spec.get(b"EF").ok().and_then(|ef| resolve(doc, ef).as_dict().ok()) else {
+        return vec![];
+    };
+    let mut out = vec![];
+    for (_, stream_ref) in ef.iter() {
+        let Ok(id) = stream_ref.as_reference() else { continue };
+        if seen.insert(id) {
+            out.push(build(doc, id, filename.clone(), description.clone(), source.to_string()));
+        }
+    }
+    out
+}
+
+fn build(doc: &Document, id: ObjectId, filename: String, description: Option<String>, source: String) -> Attachment {
+    let stream = doc.get_object(id).ok().and_then(|o| o.as_stream().ok());
+    let (data, mime, declared_size, note) = match stream {
+        Some(stream) => {
+            let decoded = pdf_decode::decode_stream(doc, stream);
+            let mime = stream.dict.get(b"Subtype").ok()
+                .and_then(|s| s.as_name().ok())
+                .map(|s| String::from_utf8_lossy(s).into_owned());
+            let declared_size = stream.dict.get(b"Params").ok()
+                .and_then(|p| resolve(doc, p).as_dict().ok())
+                .and_then(|p| p.get(b"Size").ok())
+                .and_then(|s| s.as_i64().ok());
+            (decoded.data, mime, declared_size, decoded.error)
+        }
+        None => (vec![], None, None, Some(format!("object {} is missing or not a stream", id.0))),
+    };
+    Attachment {
+        source,
+        stream: id.0,
+        filename,
+        description,
+        mime,
+        declared_size,
+        hashes: hashes::compute(&data),
+        data,
+        note,
+    }
+}
+
+/// File name safe to write inside the output directory (no separators, no traversal).
+pub fn safe_filename(name: &str) -> String {
+    let base = name.rsplit(['/', '\\']).next().unwrap_or("");
+    let cleaned: String = base.chars()
+        .map(|c| if c.is_control() || ":*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    match cleaned.trim_matches('.') {
+        "" => "attachment".to_string(),
+        _ => cleaned,
+    }
+}
@@ -0,0 +1,417 @@
+use flate2::read::{DeflateDecoder, ZlibDecoder};
+use lopdf::{Dictionary, Document, Object, Stream};
+use std::io::Read;
+use crate::helper::pdf_load::resolve;
+
+// Streams inflating past this are cut; a few KB of deflate can claim gigabytes.
+const MAX_INFLATE: u64 = 512 * 1024 * 1024;
+const MAX_COLORS: i64 = 32;
+
+pub struct Decoded {
+    /// Output of the last filter that could be applied.
+    pub data: Vec<u8>,
+    /// Filters that were applied, in order.
+    pub applied: Vec<String>,
+    /// Image codec left for the caller (DCTDecode, JPXDecode, JBIG2Decode, CCITTFaxDecode).
+    pub image_filter: Option<String>,
+    /// Why decoding stopped early, if it did.
+    pub error: Option<String>,
+    /// Problems that did not stop decoding (e.g. a truncated Flate stream).
+    pub warnings: Vec<String>,
+}
+
+pub fn decode_stream(doc: &Document, stream: &Stream) -> Decoded {
+    let chain = filter_chain(doc, &stream.dict);
+    let mut decoded = Decoded {
+        data: stream.content.clone(),
+        applied: vec![],
+        image_filter: None,
+        error: None,
+        warnings: vec![],
+    };
+
+    for (name, parms) in chain {
+        let result = match name.as_str() {
+            "FlateDecode" | "Fl" => flate(&decoded.data, &mut decoded.warnings)
+                .map(|d| output_limit(&name, d, &mut decoded.warnings))
+                .and_then(|d| predict(d, parms.as_ref())),
+            "LZWDecode" | "LZW" => {
+                let early = parms.as_ref()
+                    .and_then(|p| p.get(b"EarlyChange").ok())
+                    .and_then(|o| o.as_i64().ok())
+                    .unwrap_or(1);
+                lzw(&decoded.data, early != 0)
+                    .map(|d| output_limit(&name, d, &mut decoded.warnings))
+                    .and_then(|d| predict(d, parms.as_ref()))
+            }
+            "ASCIIHexDecode" | "AHx" => ascii_hex(&decoded.data),
+            "ASCII85Decode" | "A85" => ascii85(&decoded.data).map(|d| output_limit(&name, d, &mut decoded.warnings)),
+            "RunLengthDecode" | "RL" => run_length(&decoded.data).map(|d| output_limit(&name, d, &mut decoded.warnings)),
+            "DCTDecode" | "DCT" | "JPXDecode" | "JBIG2Decode" | "CCITTFaxDecode" | "CCF" => {
+                decoded.image_filter = Some(expand_abbreviation(&name).to_string());
+                return decoded;
+            }
+            other => {
+                decoded.error = Some(format!("unsupported filter /{}, stream left encoded", other));
+                return decoded;
+            }
+        };
+
+        match result {
+            Ok(data) => {
+                decoded.data = data;
+                decoded.applied.push(expand_abbreviation(&name).to_string());
+            }
+            Err(e) => {
+                decoded.error = Some(format!("{} failed: {}", expand_abbreviation(&name), e));
+                return decoded;
+            }
+        }
+    }
+    decoded
+}
+
+pub fn filter_chain(doc: &Document, dict: &Dictionary) -> Vec<(String, Option<Dictionary>)> {
+    let filters: Vec<String> = match dict.get(b"Filter").ok().map(|o| resolve(doc, o)) {
+        Some(Object::Name(name)) => vec![String::from_utf8_lossy(name).into_owned()],
+        Some(Object::Array(items)) => items.iter()
+            .filter_map(|o| resolve(doc, o).as_name().ok())
+            .map(|n| String::from_utf8_lossy(n).into_owned())
+            .collect(),
+        _ => vec![],
+    };
+
+    let parms = dict.get(b"DecodeParms").or_else(|_| dict.get(b"DP")).ok().map(|o| resolve(doc, o));
+    let parms: Vec<Option<Dictionary>> = match parms {
+        Some(Object::Dictionary(d)) => vec![Some(d.clone())],
+        Some(Object::Array(items)) => items.iter()
+            .map(|o| resolve(doc, o).as_dict().ok().cloned())
+            .collect(),
+        _ => vec![],
+    };
+
+    filters.into_iter()
+        .enumerate()
+        .map(|(i, f)| (f, parms.get(i).cloned().flatten()))
+        .collect()
+}
+
+fn expand_abbreviation(name: &str) -> &str {
+    match name {
+        "Fl" => "FlateDecode",
+        "LZW" => "LZWDecode",
+        "AHx" => "ASCIIHexDecode",
+        "A85" => "ASCII85Decode",
+        "RL" => "RunLengthDecode",
+        "DCT" => "DCTDecode",
+        "CCF" => "CCITTFaxDecode",
+        other => other,
+    }
+}
+
+fn flate(data: &[u8], warnings: &mut Vec<String>) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    match ZlibDecoder::new(data).take(MAX_INFLATE).read_to_end(&mut out) {
+        Ok(_) => return Ok(out),
+        // Truncated or corrupted streams are common in malicious files; keep what inflated.
+        Err(e) if !out.is_empty() => {
+            warnings.push(format!("FlateDecode stream is damaged ({}), kept {} bytes", e, out.len()));
+            return Ok(out);
+        }
+        Err(_) => {}
+    }
+
+    // Some writers omit the zlib header.
+    let mut out = Vec::new();
+    DeflateDecoder::new(data).take(MAX_INFLATE).read_to_end(&mut out)
+        .map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
+/// Flate, LZW, RunLength and ASCII85 stop at MAX_INFLATE; output of that size was cut short.
+fn output_limit(name: &str, data: Vec<u8>, warnings: &mut Vec<String>) -> Vec<u8> {
+    if data.len() as u64 >= MAX_INFLATE {
+        warnings.push(format!("{} output reached the {} byte limit and was cut (possible decompression bomb)",
+            expand_abbreviation(name), MAX_INFLATE));
+    }
+    data
+}
+
+fn ascii_hex(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut high: Option<u8> = None;
+    for &b in data {
+        if b == b'>' {
+            break;
+        }
+        if b.is_ascii_whitespace() {
+            continue;
+        }
+        let nibble = (b as char).to_digit(16)
+            .ok_or_else(|| format!("invalid hex character 0x{:02x}", b))? as u8;
+        match high.take() {
+            Some(h) => out.push(h << 4 | nibble),
+            None => high = Some(nibble),
+        }
+    }
+    if let Some(h) = high {
+        out.push(h << 4);
+    }
+    Ok(out)
+}
+
+fn ascii85(data: &[u8]) -> Result<Vec<u8>, String> {
+    let data = data.strip_prefix(b"<~").unwrap_or(data);
+    let mut out = Vec::new();
+    let mut group = [0u8; 5];
+    let mut n = 0;
+
+    for &b in data {
+        if out.len() as u64 >= MAX_INFLATE {
+            out.truncate(MAX_INFLATE as usize);
+            return Ok(out);
+        }
+        match b {
+            b'~' => break,
+            b'z' if n == 0 => out.extend_from_slice(&[0, 0, 0, 0]),
+            b'!'..=b'u' => {
+                group[n] = b - b'!';
+                n += 1;
+                if n == 5 {
+                    out.extend_from_slice(&a85_value(&group)?.to_be_bytes());
+                    n = 0;
+                }
+            }
+            b if b.is_ascii_whitespace() => {}
+            other => return Err(format!("invalid ASCII85 character 0x{:02x}", other)),
+        }
+    }
+
+    if n == 1 {
+        return Err("truncated ASCII85 group".to_string());
+    }
+    if n > 1 {
+        for slot in group.iter_mut().skip(n) {
+            *slot = b'u' - b'!';
+        }
+        out.extend_from_slice(&a85_value(&group)?.to_be_bytes()[..n - 1]);
+    }
+    Ok(out)
+}
+
+fn a85_value(group: &[u8; 5]) -> Result<u32, String> {
+    let value = group.iter().fold(0u64, |acc, &d| acc * 85 + d as u64);
+    u32::try_from(value).map_err(|_| "ASCII85 group overflow".to_string())
+}
+
+fn run_length(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() && (out.len() as u64) < MAX_INFLATE {
+        let len = data[i] as usize;
+        i += 1;
+        match len {
+            128 => break,
+            0..=127 => {
+                let end = i + len + 1;
+                if end > data.len() {
+                    return Err("literal run past end of data".to_string());
+                }
+                out.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+            _ => {
+                let byte = *data.get(i).ok_or("repeat run past end of data")?;
+                out.extend(std::iter::repeat_n(byte, 257 - len));
+                i += 1;
+            }
+        }
+    }
+    out.truncate(MAX_INFLATE as usize);
+    Ok(out)
+}
+
+fn lzw(data: &[u8], early_change: bool) -> Result<Vec<u8>, String> {
+    const CLEAR: usize = 256;
+    const EOD: usize = 257;
+
+    let mut table: Vec<Vec<u8>> = (0..=255u8).map(|b| vec![b]).collect();
+    table.push(vec![]);
+    table.push(vec![]);
+
+    let mut out = Vec::new();
+    let mut code_len = 9;
+    let mut prev: Option<usize> = None;
+    let mut bit_buf: u32 = 0;
+    let mut bit_count = 0;
+
+    for &byte in data {
+        bit_buf = (bit_buf << 8) | byte as u32;
+        bit_count += 8;
+
+        while bit_count >= code_len {
+            let code = ((bit_buf >> (bit_count - code_len)) & ((1 << code_len) - 1)) as usize;
+            bit_count -= code_len;
+
+            match code {
+                CLEAR => {
+                    table.truncate(258);
+                    code_len = 9;
+                    prev = None;
+                    continue;
+                }
+                EOD => return Ok(out),
+                _ => {}
+            }
+
+            let entry = if code < table.len() {
+                table[code].clone()
+            } else if let (Some(p), true) = (prev, code == table.len()) {
+                let mut e = table[p].clone();
+                e.push(table[p][0]);
+                e
+            } else {
+                return Err(format!("invalid LZW code {}", code));
+            };
+
+            out.extend_from_slice(&entry);
+            if out.len() as u64 >= MAX_INFLATE {
+                out.truncate(MAX_INFLATE as usize);
+                return Ok(out);
+            }
+            if let (Some(p), true) = (prev, table.len() < 4096) {
+                let mut new_entry = table[p].clone();
+                new_entry.push(entry[0]);
+                table.push(new_entry);
+            }
+            prev = Some(code);
+
+            let threshold = table.len() + usize::from(early_change);
+            code_len = match threshold {
+                t if t >= 2048 => 12,
+                t if t >= 1024 => 11,
+                t if t >= 512 => 10,
+                _ => 9,
+            };
+        }
+    }
+    Ok(out)
+}
+
+fn predict(data: Vec<u8>, parms: Option<&Dictionary>) -> Result<Vec<u8>, String> {
+    let Some(parms) = parms else { return Ok(data) };
+    let get = |key: &[u8], default: i64| {
+        parms.get(key).ok().and_then(|o| o.as_i64().ok()).unwrap_or(default)
+    };
+    let predictor = get(b"Predictor", 1);
+    if predictor <= 1 {
+        return Ok(data);
+    }
+
+    let colors = get(b"Colors", 1);
+    let bpc = get(b"BitsPerComponent", 8);
+    let columns = get(b"Columns", 1);
+    if !(1..=MAX_COLORS).contains(&colors) {
+        return Err(format!("invalid predictor /Colors {}", colors));
+    }
+    if ![1, 2, 4, 8, 16].contains(&bpc) {
+        return Err(format!("invalid predictor /BitsPerComponent {}", bpc));
+    }
+    if columns < 1 {
+        return Err(format!("invalid predictor /Columns {}", columns));
+    }
+    let (colors, bpc) = (colors as usize, bpc as usize);
+    let bpp = (colors * bpc).div_ceil(8);
+    let row_len = usize::try_from(columns).ok()
+        .and_then(|columns| (colors * bpc).checked_mul(columns))
+        .map(|bits| bits.div_ceil(8))
+        .filter(|&len| len as u64 <= MAX_INFLATE)
+        .ok_or_else(|| format!("predictor row of {} columns is too large", columns))?;
+
+    if predictor == 2 {
+        if bpc != 8 {
+            return Err(format!("TIFF predictor with {} bits per component is not supported", bpc));
+        }
+        let mut out = data;
+        for row in out.chunks_mut(row_len) {
+            for i in bpp..row.len() {
+                row[i] = row[i].wrapping_add(row[i - bpp]);
+            }
+        }
+        return Ok(out);
+    }
+
+    // PNG predictors: every row starts with its own filter type byte.
+    let mut out = Vec::with_capacity(data.len());
+    let mut prior = vec![0u8; row_len];
+    for chunk in data.chunks(row_len + 1) {
+        if chunk.len() < 2 {
+            break;
+        }
+        let filter = chunk[0];
+        let mut row = chunk[1..].to_vec();
+        row.resize(row_len, 0);
+        for i in 0..row_len {
+            let left = if i >= bpp { row[i - bpp] } else { 0 };
+            let up = prior[i];
+            let up_left = if i >= bpp { prior[i - bpp] } else { 0 };
+            row[i] = match filter {
+                0 => row[i],
+                1 => row[i].wrapping_add(left),
+                2 => row[i].wrapping_add(up),
+                3 => row[i].wrapping_add(((left as u16 + up as u16) / 2) as u8),
+                4 => row[i].wrapping_add(paeth(left, up, up_left)),
+                other => return Err(format!("invalid PNG predictor row filter {}", other)),
+            };
+        }
+        out.extend_from_slice(&row);
+        prior = row;
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Decode a PDF text string: UTF-16BE or UTF-8 when it carries a byte order mark,
+/// PDFDocEncoding otherwise.
+pub fn text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xFE, 0xFF]) {
+        let units: Vec<u16> = utf16.chunks(2)
+            .map(|c| u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect();
+        return String::from_utf16_lossy(&units);
+    }
+    if let Some(utf8) = bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]) {
+        return String::from_utf8_lossy(utf8).into_owned();
+    }
+    bytes.iter().map(|&b| pdf_doc_char(b)).collect()
+}
+
+// PDFDocEncoding is Latin-1 except for these two ranges.
+fn pdf_doc_char(b: u8) -> char {
+    const LOW: [char; 8] = ['\u{02D8}', '\u{02C7}', '\u{02C6}', '\u{02D9}', '\u{02DD}', '\u{02DB}', '\u{02DA}', '\u{02DC}'];
+    const HIGH: [char; 33] = [
+        '\u{2022}', '\u{2020}', '\u{2021}', '\u{2026}', '\u{2014}', '\u{2013}', '\u{0192}', '\u{2044}',
+        '\u{2039}', '\u{203A}', '\u{2212}', '\u{2030}', '\u{201E}', '\u{201C}', '\u{201D}', '\u{2018}',
+        '\u{2019}', '\u{201A}', '\u{2122}', '\u{FB01}', '\u{FB02}', '\u{0141}', '\u{0152}', '\u{0160}',
+        '\u{0178}', '\u{017D}', '\u{0131}', '\u{0142}', '\u{0153}', '\u{0161}', '\u{017E}', '\u{FFFD}',
+        '\u{20AC}',
+    ];
+    match b {
+        0x18..=0x1F => LOW[(b - 0x18) as usize],
+        0x80..=0xA0 => HIGH[(b - 0x80) as usize],
+        _ => b as char,
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::{bail, Result};
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+use lopdf::{Document, Object, Stream};
+use std::io::Write;
+use crate::helper::pdf_decode::{self, Decoded};
+use crate::helper::pdf_load::resolve;
+
+// Width and Height are attacker-chosen; a few-byte stream must not force a huge allocation.
+const MAX_PIXEL_BYTES: usize = 256 * 1024 * 1024;
+
+/// What an image stream turned into once written to disk.
+pub struct ImageFile {
+    pub extension: &'static str,
+    pub data: Vec<u8>,
+    /// Set when the output is not directly viewable or was only partially rebuilt.
+    pub note: Option<String>,
+}
+
+enum ColorSpace {
+    Gray,
+    Rgb,
+    Cmyk,
+    Indexed { palette: Vec<u8> },
+}
+
+pub fn export(doc: &Document, stream: &Stream, decoded: &Decoded) -> ImageFile {
+    match decoded.image_filter.as_deref() {
+        Some("DCTDecode") => file("jpg", decoded.data.clone(), None),
+        Some("JPXDecode") => file("jp2", decoded.data.clone(), None),
+        Some("JBIG2Decode") => {
+            let note = if stream.dict.get(b"DecodeParms").is_ok() {
+                "JBIG2 image (uses JBIG2Globals), not viewable as is; JBIG2 has a history of decoder exploits"
+            } else {
+                "JBIG2 image, not viewable as is; JBIG2 has a history of decoder exploits"
+            };
+            file("jbig2", decoded.data.clone(), Some(note.to_string()))
+        }
+        Some("CCITTFaxDecode") => file("ccitt", decoded.data.clone(), Some(
+            "CCITT fax image, raw G3/G4 data written without a TIFF wrapper".to_string())),
+        Some(other) => file("bin", decoded.data.clone(), Some(format!("unhandled image codec {}", other))),
+        None if decoded.error.is_some() => file("bin", decoded.data.clone(), Some(
+            "stream could not be fully decoded, partial data written".to_string())),
+        None => match to_png(doc, stream, &decoded.data) {
+            Ok((png, note)) => file("png", png, note),
+            Err(e) => file("bin", decoded.data.clone(), Some(format!("raw pixels not converted: {}", e))),
+        },
+    }
+}
+
+/// Human readable summary of the image dictionary, written next to the image.
+pub fn describe(doc: &Document, id: u32, stream: &Stream, decoded: &Decoded, image: &ImageFile) -> String {
+    let get = |key: &[u8]| stream.dict.get(key).ok()
+        .map(|o| describe_object(doc, o))
+        .unwrap_or_else(|| "-".to_string());
+    let filters: Vec<String> = pdf_decode::filter_chain(doc, &stream.dict)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+
+    let mut out = String::new();
+    out.push_str(&format!("Object:           {}\n", id));
+    out.push_str(&format!("Width:            {}\n", get(b"Width")));
+    out.push_str(&format!("Height:           {}\n", get(b"Height")));
+    out.push_str(&format!("BitsPerComponent: {}\n", get(b"BitsPerComponent")));
+    out.push_str(&format!("ColorSpace:       {}\n", get(b"ColorSpace")));
+    out.push_str(&format!("Filters:          {}\n", if filters.is_empty() { "-".to_string() } else { filters.join(", ") }));
+    out.push_str(&format!("Raw size:         {} bytes\n", stream.content.len()));
+    out.push_str(&format!("Decoded size:     {} bytes\n", decoded.data.len()));
+    out.push_str(&format!("Output format:    {}\n", image.extension));
+    if let Some(note) = &image.note {
+        out.push_str(&format!("Note:             {}\n", note));
+    }
+    out
+}
+
+fn file(extension: &'static str, data: Vec<u8>, note: Option<String>) -> ImageFile {
+    ImageFile { extension, data, note }
+}
+
+fn to_png(doc: &Document, stream: &Stream, data: &[u8]) -> Result<(Vec<u8>, Option<String>)> {
+    let dict = &stream.dict;
+    let int = |key: &[u8]| dict.get(key).ok()
+        .map(|o| resolve(doc, o))
+        .and_then(|o| o.as_i64().ok());
+
+    let width = int(b"Width").filter(|w| *w > 0).ok_or_else(|| anyhow::anyhow!("missing /Width"))? as usize;
+    let height = int(b"Height").filter(|h| *h > 0).ok_or_else(|| anyhow::anyhow!("missing /Height"))? as usize;
+    let image_mask = matches!(dict.get(b"ImageMask").map(|o| resolve(doc, o)), Ok(Object::Boolean(true)));
+
+    let (space, bpc) = if image_mask {
+        (ColorSpace::Gray, 1)
+    } else {
+        let bpc = int(b"BitsPerComponent").unwrap_or(8) as usize;
+        let space = match dict.get(b"ColorSpace") {
+            Ok(cs) => color_space(doc, cs, 0)?,
+            Err(_) => bail!("missing /ColorSpace"),
+        };
+        (space, bpc)
+    };
+
+    let components = match &space {
+        ColorSpace::Gray | ColorSpace::Indexed { .. } => 1,
+        ColorSpace::Rgb => 3,
+        ColorSpace::Cmyk => 4,
+    };
+    if !matches!(bpc, 1 | 2 | 4 | 8 | 16) {
+        bail!("unsupported bit depth {}", bpc);
+    }
+    let stride = width.checked_mul(components * bpc).map(|bits| bits.div_ceil(8));
+    let size = stride.and_then(|stride| stride.checked_mul(height))
+        .filter(|&size| size <= MAX_PIXEL_BYTES)
+        .ok_or_else(|| anyhow::anyhow!("{}x{} image exceeds the {} byte pixel budget", width, height, MAX_PIXEL_BYTES))?;
+    let mut note = None;
+    let mut pixels = data.to_vec();
+    if pixels.len() < size {
+        note = Some(format!("pixel data is short by {} bytes, padded with zeros", size - pixels.len()));
+        pixels.resize(size, 0);
+    }
+    pixels.truncate(size);
+
+    let (color_type, bit_depth, palette) = match space {
+        ColorSpace::Gray => {
+            if !matches!(bpc, 1 | 2 | 4 | 8 | 16) {
+                bail!("unsupported gray depth {}", bpc);
+            }
+            // Mask samples of 0 are painted unless /Decode flips them; show them black on white.
+            let decode_inverted = matches!(
+                dict.get(b"Decode").map(|o| resolve(doc, o)),
+                Ok(Object::Array(d)) if d.first().and_then(|o| o.as_float().ok()) == Some(1.0)
+            );
+            if image_mask != decode_inverted {
+                for b in pixels.iter_mut() {
+                    *b = !*b;
+                }
+            }
+            (0u8, bpc as u8, None)
+        }
+        ColorSpace::Rgb => {
+            if !matches!(bpc, 8 | 16) {
+                bail!("unsupported RGB depth {}", bpc);
+            }
+            (2, bpc as u8, None)
+        }
+        ColorSpace::Cmyk => {
+            if bpc != 8 {
+                bail!("unsupported CMYK depth {}", bpc);
+            }
+            pixels = pixels.chunks(4).flat_map(cmyk_to_rgb).collect();
+            return Ok((encode_png(width, height, 2, 8, &pixels, None)?, note));
+        }
+        ColorSpace::Indexed { palette } => {
+            if !matches!(bpc, 1 | 2 | 4 | 8) {
+                bail!("unsupported indexed depth {}", bpc);
+            }
+            (3, bpc as u8, Some(palette))
+        }
+    };
+
+    let png = encode_png(width, height, color_type, bit_depth, &pixels, palette.as_deref())?;
+    Ok((png, note))
+}
+
+/// `depth` counts enclosing /Indexed spaces; the spec forbids an indexed base, so one is the limit.
+fn color_space(doc: &Document, obj: &Object, depth: usize) -> Result<ColorSpace> {
+    match resolve(doc, obj) {
+        Object::Name(name) => match name.as_slice() {
+            b"DeviceGray" | b"G" | b"CalGray" => Ok(ColorSpace::Gray),
+            b"DeviceRGB" | b"RGB" | b"CalRGB" => Ok(ColorSpace::Rgb),
+            b"DeviceCMYK" | b"CMYK" => Ok(ColorSpace::Cmyk),
+            other => bail!("unsupported color space /{}", String::from_utf8_lossy(other)),
+        },
+        Object::Array(items) => {
+            let family = items.first()
+                .map(|o| resolve(doc, o))
+                .and_then(|o| o.as_name().ok())
+                .unwrap_or_default();
+            match family {
+                b"CalGray" => Ok(ColorSpace::Gray),
+                b"CalRGB" => Ok(ColorSpace::Rgb),
+                b"ICCBased" => {
+                    let n = items.get(1)
+                        .map(|o| resolve(doc, o))
+                        .and_then(|o| o.as_stream().ok())
+                        .and_then(|s| s.dict.get(b"N").ok())
+                        .and_then(|o| o.as_i64().ok())
+                        .unwrap_or(3);
+                    match n {
+                        1 => Ok(ColorSpace::Gray),
+                        3 => Ok(ColorSpace::Rgb),
+                        4 => Ok(ColorSpace::Cmyk),
+                        other => bail!("unsupported ICC profile with {} components", other),
+                    }
+                }
+                b"Indexed" | b"I" => {
+                    if depth > 0 {
+                        bail!("indexed color space based on another indexed color space");
+                    }
+                    let base = items.get(1).ok_or_else(|| anyhow::anyhow!("indexed color space without base"))?;
+                    let base = color_space(doc, base, depth + 1)?;
+                    let lookup = match items.get(3).map(|o| resolve(doc, o)) {
+                        Some(Object::String(bytes, _)) => bytes.clone(),
+                        Some(Object::Stream(s)) => pdf_decode::decode_stream(doc, s).data,
+                        _ => bail!("indexed color space without lookup table"),
+                    };
+                    let palette = match base {
+                        ColorSpace::Gray => lookup.iter().flat_map(|&g| [g, g, g]).collect(),
+                        ColorSpace::Rgb => lookup,
+                        ColorSpace::Cmyk => lookup.chunks(4).flat_map(cmyk_to_rgb).collect(),
+                        ColorSpace::Indexed { .. } => bail!("nested indexed color space"),
+                    };
+                    Ok(ColorSpace::Indexed { palette })
+                }
+                other => bail!("unsupported color space /{}", String::from_utf8_lossy(other)),
+            }
+        }
+        _ => bail!("invalid /ColorSpace entry"),
+    }
+}
+
+fn cmyk_to_rgb(px: &[u8]) -> [u8; 3] {
+    let get = |i: usize| px.get(i).copied().unwrap_or(0) as u16;
+    let k = get(3);
+    let channel = |c: u16| ((255 - c) * (255 - k) / 255) as u8;
+    [channel(get(0)), channel(get(1)), channel(get(2))]
+}
+
+fn encode_png(width: usize, height: usize, color_type: u8, bit_depth: u8, pixels: &[u8], palette: Option<&[u8]>) -> Result<Vec<u8>> {
+    let channels = match color_type {
+        2 => 3,
+        _ => 1,
+    };
+    let stride = (width * channels * bit_depth as usize).div_ceil(8);
+
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    for row in pixels.chunks(stride).take(height) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(&raw)?;
+    let idat = encoder.finish()?;
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&(width as u32).to_be_bytes());
+    ihdr.extend_from_slice(&(height as u32).to_be_bytes());
+    ihdr.extend_from_slice(&[bit_depth, color_type, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &ihdr);
+    if let Some(palette) = palette {
+        let entries = (palette.len() / 3).clamp(1, 256);
+        let mut plte = palette[..(entries * 3).min(palette.len())].to_vec();
+        plte.resize(entries * 3, 0);
+        chunk(&mut png, b"PLTE", &plte);
+    }
+    chunk(&mut png, b"IDAT", &idat);
+    chunk(&mut png, b"IEND", &[]);
+    Ok(png)
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    png.extend_from_slice(&crc.sum().to_be_bytes());
+}
+
+fn describe_object(doc: &Document, obj: &Object) -> String {
+    match resolve(doc, obj) {
+        Object::Integer(i) => i.to_string(),
+        Object::Real(r) => r.to_string(),
+        Object::Boolean(b) => b.to_string(),
+        Object::Name(n) => format!("/{}", String::from_utf8_lossy(n)),
+        Object::Array(items) => format!("[{}]", items.iter()
+            .map(|o| match o {
+                Object::Reference(r) => format!("{} {} R", r.0, r.1),
+                Object::String(s, _) => format!("<{} bytes>", s.len()),
+                other => describe_object(doc, other),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")),
+        Object::String(s, _) => format!("<{} bytes>", s.len()),
+        Object::Stream(_) => "<stream>".to_string(),
+        Object::Dictionary(_) => "<dictionary>".to_string(),
+        Object::Reference(r) => format!("{} {} R", r.0, r.1),
+        Object::Null => "null".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lopdf::{dictionary, StringFormat};
+
+    #[test]
+    fn self_referencing_indexed_color_space_is_rejected() {
+        let mut doc = Document::with_version("1.7");
+        doc.objects.insert((5, 0), Object::Array(vec![
+            Object::Name(b"Indexed".to_vec()),
+            Object::Reference((5, 0)),
+            Object::Integer(1),
+            Object::String(vec![0, 0, 0, 255, 255, 255], StringFormat::Hexadecimal),
+        ]));
+        let stream = Stream::new(dictionary! {
+            "Subtype" => "Image",
+            "Width" => 1,
+            "Height" => 1,
+            "BitsPerComponent" => 8,
+            "ColorSpace" => Object::Reference((5, 0)),
+        }, vec![0]);
+        let decoded = Decoded { data: vec![0], applied: vec![], image_filter: None, error: None, warnings: vec![] };
+
+        let image = export(&doc, &stream, &decoded);
+        assert_eq!(image.extension, "bin");
+        assert!(image.note.unwrap().contains("indexed color space based on another indexed color space"));
+    }
+}
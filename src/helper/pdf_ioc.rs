@@ -0,0 +1,123 @@
+use lopdf::{Dictionary, Document, Object};
+use serde::Serialize;
+use std::collections::HashSet;
+use crate::helper::{ioc, pdf_decode, pdf_triage};
+use crate::helper::pdf_load::{file_spec, launch_targets, string};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct PdfIndicator {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub value: String,
+    /// URI action, Launch action, SubmitForm action, JavaScript, content stream...
+    pub source: String,
+    pub object: u32,
+}
+
+pub fn collect(doc: &Document) -> Vec<PdfIndicator> {
+    let mut collector = Collector { out: vec![], seen: HashSet::new() };
+
+    for (id, object) in &doc.objects {
+        match object {
+            Object::Dictionary(dict) => collector.walk_dict(doc, dict, id.0, 0),
+            Object::Stream(stream) => {
+                collector.walk_dict(doc, &stream.dict, id.0, 0);
+                if is_text_stream(&stream.dict) {
+                    let decoded = pdf_decode::decode_stream(doc, stream);
+                    if decoded.image_filter.is_none() {
+                        let kind = match stream.dict.get(b"Type").and_then(|t| t.as_name()) {
+                            Ok(b"JavaScript") => "JavaScript stream",
+                            Ok(b"EmbeddedFile") => "embedded file",
+                            _ => "content stream",
+                        };
+                        collector.text(&String::from_utf8_lossy(&decoded.data), kind, id.0);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    collector.out
+}
+
+struct Collector {
+    out: Vec<PdfIndicator>,
+    seen: HashSet<PdfIndicator>,
+}
+
+impl Collector {
+    fn push(&mut self, kind: &'static str, value: String, source: &str, object: u32) {
+        let indicator = PdfIndicator { kind, value, source: source.to_string(), object };
+        if self.seen.insert(indicator.clone()) {
+            self.out.push(indicator);
+        }
+    }
+
+    fn text(&mut self, text: &str, source: &str, object: u32) {
+        for found in ioc::extract(text) {
+            self.push(found.kind, found.value, source, object);
+        }
+    }
+
+    // Actions are often inline (annotation /A << ... >>), so nested dictionaries are walked too.
+    fn walk_dict(&mut self, doc: &Document, dict: &Dictionary, object: u32, depth: usize) {
+        if depth > 16 {
+            return;
+        }
+        if let Ok(kind) = dict.get(b"S").and_then(|s| s.as_name()) {
+            match kind {
+                b"URI" => {
+                    if let Some(uri) = dict.get(b"URI").ok().and_then(|u| string(doc, u)) {
+                        self.push("url", uri.clone(), "URI action", object);
+                        self.text(&uri, "URI action", object);
+                    }
+                }
+                b"Launch" => {
+                    for target in launch_targets(doc, dict) {
+                        self.push("launch", target.clone(), "Launch action", object);
+                        self.text(&target, "Launch action", object);
+                    }
+                }
+                b"SubmitForm" | b"ImportData" | b"GoToR" | b"GoToE" => {
+                    let source = format!("{} action", String::from_utf8_lossy(kind));
+                    if let Some(target) = dict.get(b"F").ok().and_then(|f| file_spec(doc, f)) {
+                        let kind = if target.contains("://") { "url" } else { "file" };
+                        self.push(kind, target.clone(), &source, object);
+                        self.text(&target, &source, object);
+                    }
+                }
+                _ => {}
+            }
+        }
+        // Inline /JS strings; stream scripts are handled with the other streams.
+        if let Ok(js @ Object::String(..)) = dict.get(b"JS") {
+            if let Some((code, _)) = pdf_triage::js_code(doc, js) {
+                self.text(&code, "JavaScript", object);
+            }
+        }
+
+        for (_, value) in dict.iter() {
+            match value {
+                Object::Dictionary(inner) => self.walk_dict(doc, inner, object, depth + 1),
+                Object::Array(items) => {
+                    for item in items {
+                        if let Object::Dictionary(inner) = item {
+                            self.walk_dict(doc, inner, object, depth + 1);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Images, fonts and ICC profiles only produce false positives.
+fn is_text_stream(dict: &Dictionary) -> bool {
+    let subtype = dict.get(b"Subtype").and_then(|s| s.as_name()).unwrap_or_default();
+    let binary_subtype = matches!(subtype, b"Image" | b"Type1C" | b"CIDFontType0C" | b"OpenType");
+    let font_or_profile = dict.get(b"Length1").is_ok()
+        || dict.get(b"Length2").is_ok()
+        || (dict.get(b"N").is_ok() && dict.get(b"Alternate").is_ok());
+    !binary_subtype && !font_or_profile
+}
@@ -0,0 +1,392 @@
+use anyhow::{anyhow, bail, Context, Result};
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use regex::bytes::Regex;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::sync::OnceLock;
+use crate::helper::pdf_decode;
+
+// Highest object number allowed by the spec; bigger ones would blow up the rebuilt xref.
+const MAX_OBJECT_NUMBER: u32 = 8_388_607;
+
+pub struct Loaded {
+    pub doc: Document,
+    pub raw: Vec<u8>,
+    /// True when the xref was unusable and objects were recovered by scanning the raw bytes.
+    pub recovered: bool,
+    /// How encryption was handled, if the file is encrypted.
+    pub encryption: Option<Encryption>,
+}
+
+pub struct Encryption {
+    /// Security handler /V and /R values.
+    pub version: i64,
+    pub revision: i64,
+    /// True when the file opened without a password, i.e. encryption only hides it from scanners.
+    pub empty_user_password: bool,
+}
+
+impl std::fmt::Display for Encryption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let password = if self.empty_user_password { "the empty user password" } else { "the supplied password" };
+        write!(f, "V{} R{}, decrypted with {}", self.version, self.revision, password)
+    }
+}
+
+pub struct Structure {
+    /// Offsets of every %%EOF marker.
+    pub eof_markers: Vec<usize>,
+    /// Bytes after the last %%EOF (appended payloads, polyglots).
+    pub trailing_bytes: usize,
+    /// Incremental updates appended after the original revision.
+    pub updates: usize,
+    /// Linearized files carry an extra xref section that is not an update.
+    pub linearized: bool,
+    /// Objects defined more than once in the file, with the number of definitions.
+    pub redefined: Vec<(u32, usize)>,
+    /// Objects that no reference chain from the trailer reaches.
+    pub unreachable: Vec<u32>,
+}
+
+fn header_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?:^|[\r\n\s])(\d{1,10})\s+(\d{1,5})\s+obj\b").expect("static regex"))
+}
+
+fn length_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"/Length\s+\d+(?:\s+\d+\s+R)?").expect("static regex"))
+}
+
+fn trailer_ref_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"/(Root|Info|Encrypt)\s+(\d+)\s+(\d+)\s+R").expect("static regex"))
+}
+
+fn id_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"/ID\s*\[\s*(<[0-9A-Fa-f\s]*>)\s*(<[0-9A-Fa-f\s]*>)\s*\]").expect("static regex"))
+}
+
+/// Load a PDF the way a lenient viewer would: regular parse, then decryption, then raw recovery.
+pub fn load(path: &str, password: Option<&str>) -> Result<Loaded> {
+    let raw = std::fs::read(path)
+        .with_context(|| format!("Failed to read {}", path))?;
+
+    let (mut doc, recovered) = match Document::load_mem(&raw) {
+        Ok(doc) => (doc, false),
+        Err(e) => {
+            println!("Warning: regular parsing failed ({}), scanning raw objects instead", e);
+            let rebuilt = rebuild(&raw)
+                .with_context(|| format!("Failed to recover objects from {}", path))?;
+            let doc = Document::load_mem(&rebuilt)
+                .map_err(|e| anyhow!("Failed to load recovered PDF objects: {}", e))?;
+            (doc, true)
+        }
+    };
+
+    let encryption = describe_encryption(&mut doc, password)?;
+    Ok(Loaded { doc, raw, recovered, encryption })
+}
+
+fn describe_encryption(doc: &mut Document, password: Option<&str>) -> Result<Option<Encryption>> {
+    // lopdf decrypts on load when the empty user password works.
+    if let Some(state) = &doc.encryption_state {
+        return Ok(Some(Encryption { version: state.version(), revision: state.revision(), empty_user_password: true }));
+    }
+    if !doc.is_encrypted() {
+        return Ok(None);
+    }
+
+    let handler = doc.get_encrypted().ok()
+        .and_then(|e| e.get(b"Filter").ok())
+        .and_then(|f| f.as_name().ok())
+        .map(|f| String::from_utf8_lossy(f).into_owned())
+        .unwrap_or_else(|| "unknown".to_string());
+    match password {
+        Some(password) => {
+            doc.decrypt(password)
+                .map_err(|e| anyhow!("Failed to decrypt PDF with the supplied password: {}", e))?;
+            let state = doc.encryption_state.as_ref();
+            Ok(Some(Encryption {
+                version: state.map(|s| s.version()).unwrap_or_default(),
+                revision: state.map(|s| s.revision()).unwrap_or_default(),
+                empty_user_password: false,
+            }))
+        }
+        None => bail!("PDF is encrypted (/{}) and the empty user password does not open it; retry with --password", handler),
+    }
+}
+
+/// Rebuild a loadable file from every `N G obj ... endobj` block, last definition winning,
+/// with stream lengths recomputed and a fresh xref table.
+fn rebuild(raw: &[u8]) -> Result<Vec<u8>> {
+    let objects = scan_objects(raw);
+    if objects.is_empty() {
+        bail!("no 'N G obj' blocks found");
+    }
+
+    let mut latest: BTreeMap<u32, (u16, &[u8])> = BTreeMap::new();
+    for (id, body) in &objects {
+        latest.insert(id.0, (id.1, body));
+    }
+
+    let mut out = b"%PDF-1.7\n".to_vec();
+    let mut offsets = BTreeMap::new();
+    for (&num, &(gen, body)) in &latest {
+        offsets.insert(num, (gen, out.len()));
+        out.extend_from_slice(format!("{} {} obj\n", num, gen).as_bytes());
+        out.extend_from_slice(&fix_stream_length(body));
+        out.extend_from_slice(b"\nendobj\n");
+    }
+
+    let size = latest.keys().max().copied().unwrap_or(0) + 1;
+    let xref_start = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f \n", size).as_bytes());
+    for num in 1..size {
+        match offsets.get(&num) {
+            Some((gen, offset)) => out.extend_from_slice(format!("{:010} {:05} n \n", offset, gen).as_bytes()),
+            None => out.extend_from_slice(b"0000000000 65535 f \n"),
+        }
+    }
+
+    let root = last_reference(raw, "Root")
+        .or_else(|| find_catalog(&latest))
+        .ok_or_else(|| anyhow!("no /Root reference or /Catalog object found"))?;
+    let mut trailer = format!("<< /Size {} /Root {} {} R", size, root.0, root.1);
+    for key in ["Info", "Encrypt"] {
+        if let Some(id) = last_reference(raw, key) {
+            trailer.push_str(&format!(" /{} {} {} R", key, id.0, id.1));
+        }
+    }
+    if let Some(caps) = id_re().captures_iter(raw).last() {
+        trailer.push_str(&format!(" /ID [{} {}]",
+            String::from_utf8_lossy(&caps[1]), String::from_utf8_lossy(&caps[2])));
+    }
+    trailer.push_str(" >>");
+    out.extend_from_slice(format!("trailer\n{}\nstartxref\n{}\n%%EOF\n", trailer, xref_start).as_bytes());
+    Ok(out)
+}
+
+/// Every `N G obj` block in file order, including ones later redefined.
+pub fn scan_objects(raw: &[u8]) -> Vec<(ObjectId, &[u8])> {
+    let starts: Vec<(ObjectId, usize, usize)> = header_re().captures_iter(raw)
+        .filter_map(|caps| {
+            let num: u32 = std::str::from_utf8(&caps[1]).ok()?.parse().ok()?;
+            if num > MAX_OBJECT_NUMBER {
+                return None;
+            }
+            let gen = std::str::from_utf8(&caps[2]).ok()?.parse().ok()?;
+            let whole = caps.get(0)?;
+            Some(((num, gen), caps.get(1)?.start(), whole.end()))
+        })
+        .collect();
+
+    let mut objects = vec![];
+    for (i, &(id, _, body_start)) in starts.iter().enumerate() {
+        let limit = starts.get(i + 1).map(|s| s.1).unwrap_or(raw.len());
+        let window = &raw[body_start..limit];
+        // Stream data may itself contain "endobj"; skip past endstream first.
+        let search_from = find(window, b"endstream").map(|p| p + 9).unwrap_or(0);
+        let end = find(&window[search_from..], b"endobj")
+            .map(|p| search_from + p)
+            .unwrap_or(window.len());
+        objects.push((id, trim(&window[..end])));
+    }
+    objects
+}
+
+fn fix_stream_length(body: &[u8]) -> Vec<u8> {
+    let Some(stream_kw) = find(body, b"stream") else { return body.to_vec() };
+    let mut data_start = stream_kw + 6;
+    if body[data_start..].starts_with(b"\r\n") {
+        data_start += 2;
+    } else if body[data_start..].starts_with(b"\n") || body[data_start..].starts_with(b"\r") {
+        data_start += 1;
+    }
+    let data_end = rfind(body, b"endstream").filter(|&e| e >= data_start).unwrap_or(body.len());
+    let mut data = &body[data_start..data_end];
+    if data.ends_with(b"\r\n") {
+        data = &data[..data.len() - 2];
+    } else if data.ends_with(b"\n") || data.ends_with(b"\r") {
+        data = &data[..data.len() - 1];
+    }
+
+    let dict = &body[..stream_kw];
+    let replacement = format!("/Length {}", data.len());
+    let dict = length_re().replace(dict, replacement.as_bytes());
+
+    let mut out = dict.into_owned();
+    if !out.ends_with(b"\n") {
+        out.push(b'\n');
+    }
+    out.extend_from_slice(b"stream\n");
+    out.extend_from_slice(data);
+    out.extend_from_slice(b"\nendstream");
+    out
+}
+
+/// Last `/Root`, `/Info` or `/Encrypt` reference in the raw bytes.
+fn last_reference(raw: &[u8], key: &str) -> Option<ObjectId> {
+    let caps = trailer_ref_re().captures_iter(raw)
+        .filter(|caps| &caps[1] == key.as_bytes())
+        .last()?;
+    Some((std::str::from_utf8(&caps[2]).ok()?.parse().ok()?, std::str::from_utf8(&caps[3]).ok()?.parse().ok()?))
+}
+
+fn find_catalog(objects: &BTreeMap<u32, (u16, &[u8])>) -> Option<ObjectId> {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    let re = RE.get_or_init(|| Regex::new(r"/Type\s*/Catalog\b").expect("static regex"));
+    objects.iter()
+        .rev()
+        .find(|(_, (_, body))| re.is_match(body))
+        .map(|(&num, &(gen, _))| (num, gen))
+}
+
+pub fn structure(raw: &[u8], doc: &Document) -> Structure {
+    let eof_markers: Vec<usize> = find_all(raw, b"%%EOF");
+    let trailing_bytes = eof_markers.last()
+        .map(|&p| raw[p + 5..].iter().filter(|b| !b.is_ascii_whitespace()).count())
+        .unwrap_or(0);
+
+    let mut counts: BTreeMap<u32, usize> = BTreeMap::new();
+    for (id, _) in scan_objects(raw) {
+        *counts.entry(id.0).or_default() += 1;
+    }
+    let redefined = counts.into_iter().filter(|(_, n)| *n > 1).collect();
+    let linearized = find(&raw[..raw.len().min(1024)], b"/Linearized").is_some();
+    let sections = find_all(raw, b"startxref").len();
+
+    Structure {
+        eof_markers,
+        trailing_bytes,
+        updates: sections.saturating_sub(1 + usize::from(linearized)),
+        linearized,
+        redefined,
+        unreachable: unreachable(doc),
+    }
+}
+
+pub fn print_structure(loaded: &Loaded, structure: &Structure) {
+    println!("=== Structure ===");
+    if loaded.recovered {
+        println!("  Xref table broken, objects recovered by raw scan");
+    }
+    if let Some(encryption) = &loaded.encryption {
+        println!("  Encrypted:         {}", encryption);
+    }
+    println!("  Linearized:        {}", structure.linearized);
+    println!("  Updates:           {}", structure.updates);
+    println!("  %%EOF markers:     {}", structure.eof_markers.len());
+    if structure.trailing_bytes > 0 {
+        println!("  Trailing data:     {} byte(s) after the last %%EOF", structure.trailing_bytes);
+    }
+    if !structure.redefined.is_empty() {
+        let list: Vec<String> = structure.redefined.iter()
+            .map(|(id, n)| format!("{} (x{})", id, n))
+            .collect();
+        println!("  Redefined objects: {}", list.join(", "));
+    }
+    if !structure.unreachable.is_empty() {
+        let list: Vec<String> = structure.unreachable.iter().map(|id| id.to_string()).collect();
+        println!("  Unreachable:       {}", list.join(", "));
+    }
+}
+
+fn unreachable(doc: &Document) -> Vec<u32> {
+    let mut seen: HashSet<ObjectId> = HashSet::new();
+    let mut queue: Vec<ObjectId> = vec![];
+    collect_refs(&Object::Dictionary(doc.trailer.clone()), &mut queue);
+    while let Some(id) = queue.pop() {
+        if !seen.insert(id) {
+            continue;
+        }
+        if let Some(object) = doc.objects.get(&id) {
+            collect_refs(object, &mut queue);
+        }
+    }
+
+    let structural: BTreeSet<ObjectId> = doc.objects.iter()
+        .filter(|(_, o)| matches!(
+            o.as_stream().ok().and_then(|s| s.dict.get(b"Type").ok()).and_then(|t| t.as_name().ok()),
+            Some(b"XRef") | Some(b"ObjStm")
+        ))
+        .map(|(id, _)| *id)
+        .collect();
+
+    doc.objects.keys()
+        .filter(|id| !seen.contains(id) && !structural.contains(id))
+        .map(|id| id.0)
+        .collect()
+}
+
+fn collect_refs(object: &Object, out: &mut Vec<ObjectId>) {
+    match object {
+        Object::Reference(id) => out.push(*id),
+        Object::Array(items) => items.iter().for_each(|o| collect_refs(o, out)),
+        Object::Dictionary(dict) => dict.iter().for_each(|(_, o)| collect_refs(o, out)),
+        Object::Stream(stream) => stream.dict.iter().for_each(|(_, o)| collect_refs(o, out)),
+        _ => {}
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+fn rfind(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).rposition(|w| w == needle)
+}
+
+fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+    haystack.windows(needle.len())
+        .enumerate()
+        .filter(|(_, w)| *w == needle)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+fn trim(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| !b.is_ascii_whitespace()).unwrap_or(bytes.len());
+    let end = bytes.iter().rposition(|b| !b.is_ascii_whitespace()).map(|p| p + 1).unwrap_or(start);
+    &bytes[start..end]
+}
+
+/// Follows a reference to its object; anything else (or a dangling reference) is returned as is.
+pub(crate) fn resolve<'a>(doc: &'a Document, object: &'a Object) -> &'a Object {
+    doc.dereference(object).map(|(_, o)| o).unwrap_or(object)
+}
+
+/// A PDF text string (PDFDocEncoding or UTF-16BE), through a reference if needed.
+pub(crate) fn string(doc: &Document, object: &Object) -> Option<String> {
+    match resolve(doc, object) {
+        Object::String(bytes, _) => Some(pdf_decode::text_string(bytes)),
+        _ => None,
+    }
+}
+
+/// The file a file specification (plain string or dictionary) points to.
+pub(crate) fn file_spec(doc: &Document, object: &Object) -> Option<String> {
+    match resolve(doc, object) {
+        Object::String(..) => string(doc, object),
+        Object::Dictionary(spec) => ["UF", "F", "Unix", "DOS", "Mac"].iter()
+            .find_map(|key| spec.get(key.as_bytes()).ok().and_then(|v| string(doc, v))),
+        _ => None,
+    }
+}
+
+/// What a Launch action runs: /F, then the Windows-specific /Win << /F (cmd.exe) /P (params) >>.
+pub(crate) fn launch_targets(doc: &Document, dict: &Dictionary) -> Vec<String> {
+    let mut targets = vec![];
+    if let Some(f) = dict.get(b"F").ok().and_then(|f| file_spec(doc, f)) {
+        targets.push(f);
+    }
+    if let Some(win) = dict.get(b"Win").ok().and_then(|w| resolve(doc, w).as_dict().ok()) {
+        let file = win.get(b"F").ok().and_then(|f| string(doc, f)).unwrap_or_default();
+        match win.get(b"P").ok().and_then(|p| string(doc, p)) {
+            Some(params) => targets.push(format!("{} {}", file, params)),
+            None if !file.is_empty() => targets.push(file),
+            None => {}
+        }
+    }
+    targets
+}
@@ -0,0 +1,397 @@
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use lopdf::{Document, Object};
+use regex::Regex;
+use std::sync::OnceLock;
+use crate::helper::pdf_decode;
+use crate::helper::pdf_load::resolve;
+
+pub struct Metadata {
+    /// Info dictionary entries with decoded values, in file order.
+    pub info: Vec<(String, String)>,
+    pub xmp: Option<Xmp>,
+}
+
+#[derive(Default)]
+pub struct Xmp {
+    pub raw: String,
+    pub creator_tool: Option<String>,
+    pub producer: Option<String>,
+    pub title: Option<String>,
+    pub creator: Option<String>,
+    pub create_date: Option<String>,
+    pub modify_date: Option<String>,
+    pub metadata_date: Option<String>,
+    pub document_id: Option<String>,
+    pub instance_id: Option<String>,
+    pub original_document_id: Option<String>,
+    pub history: Vec<HistoryEvent>,
+}
+
+pub struct HistoryEvent {
+    pub action: Option<String>,
+    pub when: Option<String>,
+    pub software_agent: Option<String>,
+    pub instance_id: Option<String>,
+    pub changed: Option<String>,
+}
+
+pub fn read(doc: &Document) -> Metadata {
+    let mut info = vec![];
+    if let Some(dict) = doc.trailer.get(b"Info").ok()
+        .map(|o| resolve(doc, o))
+        .and_then(|o| o.as_dict().ok())
+    {
+        for (key, value) in dict.iter() {
+            info.push((String::from_utf8_lossy(key).into_owned(), value_to_string(doc, value)));
+        }
+    }
+
+    let xmp = doc.catalog().ok()
+        .and_then(|c| c.get(b"Metadata").ok())
+        .map(|m| resolve(doc, m))
+        .and_then(|m| m.as_stream().ok())
+        .map(|stream| {
+            let decoded = pdf_decode::decode_stream(doc, stream);
+            parse_xmp(&String::from_utf8_lossy(&decoded.data))
+        });
+
+    Metadata { info, xmp }
+}
+
+/// Parse a PDF date (`D:YYYYMMDDHHmmSSOHH'mm'`); every field after the year is optional.
+pub fn parse_pdf_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let s = value.trim().trim_start_matches("D:");
+    let digits: String = s.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 4 {
+        return None;
+    }
+    let field = |start: usize, len: usize, default: u32| -> u32 {
+        digits.get(start..start + len).and_then(|v| v.parse().ok()).unwrap_or(default)
+    };
+    let year = digits[..4].parse().ok()?;
+    let date = NaiveDate::from_ymd_opt(year, field(4, 2, 1), field(6, 2, 1))?
+        .and_hms_opt(field(8, 2, 0), field(10, 2, 0), field(12, 2, 0))?;
+
+    let rest = &s[digits.len()..];
+    let offset_secs = match rest.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let tz: String = rest[1..].chars().filter(|c| c.is_ascii_digit()).collect();
+            let hours: i32 = tz.get(..2).and_then(|h| h.parse().ok()).unwrap_or(0);
+            let minutes: i32 = tz.get(2..4).and_then(|m| m.parse().ok()).unwrap_or(0);
+            let secs = hours * 3600 + minutes * 60;
+            if sign == '-' { -secs } else { secs }
+        }
+        _ => 0,
+    };
+    FixedOffset::east_opt(offset_secs)?.from_local_datetime(&date).single()
+}
+
+/// Parse an XMP (ISO 8601) date, with or without seconds and offset.
+pub fn parse_xmp_date(value: &str) -> Option<DateTime<FixedOffset>> {
+    let value = value.trim();
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt);
+    }
+    for fmt in ["%Y-%m-%dT%H:%M%:z", "%Y-%m-%dT%H:%M:%S%.f%:z"] {
+        if let Ok(dt) = DateTime::parse_from_str(value, fmt) {
+            return Some(dt);
+        }
+    }
+    for fmt in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"] {
+        if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(value, fmt) {
+            return Some(Utc.from_utc_datetime(&dt).fixed_offset());
+        }
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()
+        .and_then(|d| d.and_hms_opt(0, 0, 0))
+        .map(|d| Utc.from_utc_datetime(&d).fixed_offset())
+}
+
+pub fn render(meta: &Metadata) -> String {
+    let mut out = String::from("=== Info ===\n");
+    if meta.info.is_empty() {
+        out.push_str("(no Info dictionary)\n");
+    }
+    for (key, value) in &meta.info {
+        match (key.as_str(), parse_pdf_date(value)) {
+            ("CreationDate" | "ModDate", Some(date)) => {
+                out.push_str(&format!("{}: {} ({})\n", key, date.to_rfc3339(), value));
+            }
+            _ => out.push_str(&format!("{}: {}\n", key, value)),
+        }
+    }
+
+    out.push_str("\n=== XMP ===\n");
+    match &meta.xmp {
+        None => out.push_str("(no XMP metadata stream)\n"),
+        Some(xmp) => {
+            for (label, value) in [
+                ("CreatorTool", &xmp.creator_tool),
+                ("Producer", &xmp.producer),
+                ("Title", &xmp.title),
+                ("Creator", &xmp.creator),
+                ("CreateDate", &xmp.create_date),
+                ("ModifyDate", &xmp.modify_date),
+                ("MetadataDate", &xmp.metadata_date),
+                ("DocumentID", &xmp.document_id),
+                ("InstanceID", &xmp.instance_id),
+                ("OriginalDocumentID", &xmp.original_document_id),
+            ] {
+                if let Some(value) = value {
+                    out.push_str(&format!("{}: {}\n", label, value));
+                }
+            }
+            if !xmp.history.is_empty() {
+                out.push_str("\n=== XMP History ===\n");
+                for event in &xmp.history {
+                    out.push_str(&format!("- {} {} by {}{}{}\n",
+                        event.when.as_deref().unwrap_or("?"),
+                        event.action.as_deref().unwrap_or("?"),
+                        event.software_agent.as_deref().unwrap_or("?"),
+                        event.changed.as_deref().map(|c| format!(" changed {}", c)).unwrap_or_default(),
+                        event.instance_id.as_deref().map(|i| format!(" [{}]", i)).unwrap_or_default()));
+                }
+            }
+        }
+    }
+
+    let issues = inconsistencies(meta);
+    out.push_str("\n=== Inconsistencies ===\n");
+    if issues.is_empty() {
+        out.push_str("None found.\n");
+    }
+    for issue in issues {
+        out.push_str(&format!("- {}\n", issue));
+    }
+    out
+}
+
+/// Disagreements between Info and XMP, and dates that do not make sense on a timeline.
+pub fn inconsistencies(meta: &Metadata) -> Vec<String> {
+    let mut issues = vec![];
+    let info = |key: &str| meta.info.iter().find(|(k, _)| k == key).map(|(_, v)| v.trim().to_string());
+    let info_created = info("CreationDate").and_then(|d| parse_pdf_date(&d));
+    let info_modified = info("ModDate").and_then(|d| parse_pdf_date(&d));
+    let now = Utc::now().fixed_offset();
+
+    if let (Some(created), Some(modified)) = (info_created, info_modified) {
+        if modified < created {
+            issues.push(format!("Info ModDate {} is before CreationDate {}", modified.to_rfc3339(), created.to_rfc3339()));
+        }
+    }
+    for (label, date) in [("Info CreationDate", info_created), ("Info ModDate", info_modified)] {
+        if date.is_some_and(|d| d > now) {
+            issues.push(format!("{} is in the future", label));
+        }
+    }
+
+    let Some(xmp) = &meta.xmp else {
+        if !meta.info.is_empty() {
+            issues.push("Info dictionary present but no XMP metadata (stripped or written by a minimal tool)".to_string());
+        }
+        return issues;
+    };
+
+    for (info_key, info_value, xmp_key, xmp_value) in [
+        ("Producer", info("Producer"), "pdf:Producer", &xmp.producer),
+        ("Creator", info("Creator"), "xmp:CreatorTool", &xmp.creator_tool),
+        ("Title", info("Title"), "dc:title", &xmp.title),
+        ("Author", info("Author"), "dc:creator", &xmp.creator),
+    ] {
+        if let (Some(a), Some(b)) = (&info_value, xmp_value) {
+            if a != b.trim() {
+                issues.push(format!("Info {} '{}' differs from XMP {} '{}'", info_key, a, xmp_key, b));
+            }
+        }
+    }
+
+    let xmp_created = xmp.create_date.as_deref().and_then(parse_xmp_date);
+    let xmp_modified = xmp.modify_date.as_deref().and_then(parse_xmp_date);
+    let xmp_metadata = xmp.metadata_date.as_deref().and_then(parse_xmp_date);
+    for (label, a, b) in [
+        ("CreationDate / xmp:CreateDate", info_created, xmp_created),
+        ("ModDate / xmp:ModifyDate", info_modified, xmp_modified),
+    ] {
+        if let (Some(a), Some(b)) = (a, b) {
+            if (a - b).num_seconds().abs() > 1 {
+                issues.push(format!("{} disagree: {} vs {}", label, a.to_rfc3339(), b.to_rfc3339()));
+            }
+        }
+    }
+    if let (Some(created), Some(modified)) = (xmp_created, xmp_modified) {
+        if modified < created {
+            issues.push("xmp:ModifyDate is before xmp:CreateDate".to_string());
+        }
+    }
+    if let Some(modified) = info_modified {
+        let latest_xmp = [xmp_modified, xmp_metadata].into_iter().flatten().max();
+        if latest_xmp.is_some_and(|x| modified - x > chrono::Duration::seconds(1)) {
+            issues.push("Info ModDate is newer than any XMP date (edited by a tool that did not update XMP)".to_string());
+        }
+    }
+    for event in &xmp.history {
+        let when = event.when.as_deref().and_then(parse_xmp_date);
+        if let (Some(when), Some(created)) = (when, xmp_created.or(info_created)) {
+            if when < created {
+                issues.push(format!("XMP history event '{}' at {} predates the creation date",
+                    event.action.as_deref().unwrap_or("?"), when.to_rfc3339()));
+            }
+        }
+    }
+    if let (Some(doc_id), Some(original)) = (&xmp.document_id, &xmp.original_document_id) {
+        if doc_id != original {
+            issues.push(format!("DocumentID {} differs from OriginalDocumentID {} (derived from another document)", doc_id, original));
+        }
+    }
+    issues
+}
+
+fn parse_xmp(xml: &str) -> Xmp {
+    let history = container(xml, "xmpMM:History")
+        .map(|seq| {
+            li_items(&seq).iter()
+                .map(|(attrs, body)| format!("{} {}", attrs, body))
+                .map(|item| HistoryEvent {
+                    action: field(&item, "stEvt:action"),
+                    when: field(&item, "stEvt:when"),
+                    software_agent: field(&item, "stEvt:softwareAgent"),
+                    instance_id: field(&item, "stEvt:instanceID"),
+                    changed: field(&item, "stEvt:changed"),
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Xmp {
+        raw: xml.to_string(),
+        creator_tool: field(xml, "xmp:CreatorTool"),
+        producer: field(xml, "pdf:Producer"),
+        title: container(xml, "dc:title")
+            .and_then(|c| li_items(&c).into_iter().next().map(|(_, body)| unescape(body.trim()))),
+        creator: container(xml, "dc:creator")
+            .map(|c| li_items(&c).iter().map(|(_, body)| unescape(body.trim())).collect::<Vec<_>>().join("; "))
+            .filter(|c| !c.is_empty()),
+        create_date: field(xml, "xmp:CreateDate"),
+        modify_date: field(xml, "xmp:ModifyDate"),
+        metadata_date: field(xml, "xmp:MetadataDate"),
+        document_id: field(xml, "xmpMM:DocumentID"),
+        instance_id: field(xml, "xmpMM:InstanceID"),
+        original_document_id: field(xml, "xmpMM:OriginalDocumentID"),
+        history,
+    }
+}
+
+/// A simple property, written either as an element or as an attribute.
+fn field(xml: &str, name: &str) -> Option<String> {
+    if let Some(body) = elements(xml, name).into_iter().find(|body| !body.contains('<')) {
+        return Some(unescape(body.trim())).filter(|v| !v.is_empty());
+    }
+    attribute(xml, name).map(|value| unescape(value.trim()))
+}
+
+fn container(xml: &str, name: &str) -> Option<String> {
+    elements(xml, name).first().map(|body| body.to_string())
+}
+
+/// Bodies of the `<name ...>body</name>` elements, in document order; self-closing ones are skipped.
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut bodies = vec![];
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(tag_end) = after.find('>') else { break };
+        // `<nameSuffix` is a different element.
+        if !after.starts_with(|c: char| c == '>' || c == '/' || c.is_whitespace()) || after[..tag_end].ends_with('/') {
+            rest = &after[tag_end + 1..];
+            continue;
+        }
+        let body = &after[tag_end + 1..];
+        let Some(end) = body.find(&close) else { break };
+        bodies.push(&body[..end]);
+        rest = &body[end + close.len()..];
+    }
+    bodies
+}
+
+/// Value of the first `name="value"` (or single-quoted) attribute.
+fn attribute<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = xml;
+    while let Some(start) = rest.find(name) {
+        rest = &rest[start + name.len()..];
+        let Some(value) = rest.trim_start().strip_prefix('=').map(str::trim_start) else { continue };
+        let Some(value) = value.strip_prefix(['"', '\'']) else { continue };
+        if let Some(end) = value.find(['"', '\'']) {
+            return Some(&value[..end]);
+        }
+    }
+    None
+}
+
+fn li_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?s)<rdf:li(\s[^>]*?)?(?:/>|>(.*?)</rdf:li>)").expect("static regex"))
+}
+
+// rdf:li items as (attributes, content), either `<rdf:li .../>` or `<rdf:li ...>content</rdf:li>`.
+fn li_items(xml: &str) -> Vec<(String, String)> {
+    li_re().captures_iter(xml)
+        .map(|caps| (
+            caps.get(1).map(|m| m.as_str().to_string()).unwrap_or_default(),
+            caps.get(2).map(|m| m.as_str().to_string()).unwrap_or_default(),
+        ))
+        .collect()
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn value_to_string(doc: &Document, value: &Object) -> String {
+    match resolve(doc, value) {
+        Object::String(bytes, _) => pdf_decode::text_string(bytes),
+        Object::Name(name) => format!("/{}", String::from_utf8_lossy(name)),
+        Object::Integer(i) => i.to_string(),
+        Object::Real(r) => r.to_string(),
+        Object::Boolean(b) => b.to_string(),
+        Object::Null => "null".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_elements_attributes_and_history() {
+        let xml = r#"<x:xmpmeta><rdf:RDF><rdf:Description xmp:CreatorTool="Word &amp; Co" pdf:Producer='Acrobat'>
+            <xmp:CreateDate>2020-01-02T03:04:05Z</xmp:CreateDate>
+            <xmp:ModifyDateX>ignored</xmp:ModifyDateX>
+            <xmp:ModifyDate/>
+            <xmp:ModifyDate>2021-01-01T00:00:00Z</xmp:ModifyDate>
+            <dc:title><rdf:Alt><rdf:li xml:lang="x-default">Invoice</rdf:li></rdf:Alt></dc:title>
+            <dc:creator><rdf:Seq><rdf:li>Alice</rdf:li><rdf:li>Bob</rdf:li></rdf:Seq></dc:creator>
+            <xmpMM:History><rdf:Seq>
+                <rdf:li stEvt:action="created" stEvt:when="2020-01-02T03:04:05Z"/>
+                <rdf:li><stEvt:action>saved</stEvt:action><stEvt:softwareAgent>Tool 1.0</stEvt:softwareAgent></rdf:li>
+            </rdf:Seq></xmpMM:History>
+        </rdf:Description></rdf:RDF></x:xmpmeta>"#;
+        let xmp = parse_xmp(xml);
+        assert_eq!(xmp.creator_tool.as_deref(), Some("Word & Co"));
+        assert_eq!(xmp.producer.as_deref(), Some("Acrobat"));
+        assert_eq!(xmp.create_date.as_deref(), Some("2020-01-02T03:04:05Z"));
+        assert_eq!(xmp.modify_date.as_deref(), Some("2021-01-01T00:00:00Z"));
+        assert_eq!(xmp.title.as_deref(), Some("Invoice"));
+        assert_eq!(xmp.creator.as_deref(), Some("Alice; Bob"));
+        assert_eq!(xmp.history.len(), 2);
+        assert_eq!(xmp.history[0].action.as_deref(), Some("created"));
+        assert_eq!(xmp.history[1].action.as_deref(), Some("saved"));
+        assert_eq!(xmp.history[1].software_agent.as_deref(), Some("Tool 1.0"));
+    }
+}
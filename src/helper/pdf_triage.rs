@@ -0,0 +1,496 @@
+use lopdf::{Dictionary, Document, Object, ObjectId};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use crate::helper::pdf_decode;
+use crate::helper::pdf_load::{file_spec, launch_targets, resolve, string, Loaded, Structure};
+
+/// Names worth flagging, as counted by PDFiD-style triage tools.
+pub const RISKY_NAMES: &[&str] = &[
+    "JS", "JavaScript", "OpenAction", "AA", "Launch", "EmbeddedFile", "RichMedia",
+    "XFA", "AcroForm", "URI", "SubmitForm", "JBIG2Decode",
+];
+
+// JavaScript APIs and idioms seen in exploit kits and droppers.
+const SUSPICIOUS_JS: &[&str] = &[
+    "eval(", "unescape(", "String.fromCharCode", "util.printf", "Collab.getIcon",
+    "Collab.collectEmailInfo", "media.newPlayer", "spell.customDictionaryOpen",
+    "getAnnots", "app.setTimeOut", "this.exportDataObject", "app.launchURL",
+    "submitForm", "%u9090", "\\x90\\x90",
+];
+
+pub struct KeywordHit {
+    pub name: String,
+    pub count: usize,
+    pub objects: BTreeSet<u32>,
+}
+
+pub struct Action {
+    /// What fires the action (OpenAction, AA/O, annotation link, Names tree...).
+    pub trigger: String,
+    /// Object holding the action dictionary, if it is not inline.
+    pub object: Option<u32>,
+    /// Action type from /S.
+    pub kind: String,
+    /// Launched file, URI, form target or script preview.
+    pub target: Option<String>,
+}
+
+pub struct Script {
+    /// Object the /JS entry lives in.
+    pub object: u32,
+    /// "string" or "stream obj N".
+    pub source: String,
+    pub code: String,
+    /// Whether a trigger (open action, additional action, names tree...) reaches it.
+    pub reachable: bool,
+}
+
+pub struct Report {
+    pub keywords: Vec<KeywordHit>,
+    pub actions: Vec<Action>,
+    pub scripts: Vec<Script>,
+    /// Names spelled with #xx escapes, a classic way to dodge keyword scanners.
+    pub obfuscated_names: Vec<String>,
+    pub score: u32,
+    pub reasons: Vec<(u32, String)>,
+}
+
+impl Report {
+    pub fn level(&self) -> &'static str {
+        match self.score {
+            70.. => "HIGH",
+            40..=69 => "MEDIUM",
+            15..=39 => "LOW",
+            _ => "MINIMAL",
+        }
+    }
+
+    fn count(&self, name: &str) -> usize {
+        self.keywords.iter().find(|k| k.name == name).map(|k| k.count).unwrap_or(0)
+    }
+}
+
+pub fn analyze(doc: &Document, raw: &[u8]) -> Report {
+    let mut hits: BTreeMap<&'static str, KeywordHit> = BTreeMap::new();
+    for (id, object) in &doc.objects {
+        count_names(object, id.0, &mut hits);
+    }
+    let keywords: Vec<KeywordHit> = RISKY_NAMES.iter()
+        .filter_map(|name| hits.remove(name))
+        .collect();
+
+    let mut walker = ActionWalker { doc, actions: vec![], reached: HashSet::new(), visited: HashSet::new() };
+    walker.walk_triggers();
+
+    let scripts = collect_scripts(doc, &walker.reached);
+
+    let mut report = Report {
+        keywords,
+        actions: walker.actions,
+        scripts,
+        obfuscated_names: obfuscated_names(raw),
+        score: 0,
+        reasons: vec![],
+    };
+    score(&mut report);
+    report
+}
+
+pub fn print_report(path: &str, report: &Report) {
+    println!("PDF Triage Report for '{}':", path);
+
+    println!("\n=== Keywords ===");
+    if report.keywords.is_empty() {
+        println!("No risky keywords found.");
+    }
+    for hit in &report.keywords {
+        println!("  /{:<14} {:>4}   objects: {}", hit.name, hit.count, join_ids(&hit.objects));
+    }
+
+    println!("\n=== Actions ===");
+    if report.actions.is_empty() {
+        println!("No triggered actions found.");
+    }
+    for action in &report.actions {
+        let location = action.object.map(|o| format!("obj {}", o)).unwrap_or_else(|| "inline".to_string());
+        println!("  [{}] {} -> /{}", action.trigger, location, action.kind);
+        if let Some(target) = &action.target {
+            println!("      {}", target);
+        }
+    }
+
+    println!("\n=== JavaScript ===");
+    if report.scripts.is_empty() {
+        println!("No JavaScript found.");
+    }
+    for script in &report.scripts {
+        println!("  obj {} ({}){}: {} bytes", script.object, script.source,
+            if script.reachable { "" } else { " [not referenced by any trigger]" }, script.code.len());
+        println!("      {}", preview(&script.code, 160));
+    }
+
+    if !report.obfuscated_names.is_empty() {
+        println!("\n=== Obfuscated names ===");
+        for name in &report.obfuscated_names {
+            println!("  {}", name);
+        }
+    }
+
+    println!("\nRisk score: {}/100 ({})", report.score, report.level());
+    for (points, reason) in &report.reasons {
+        println!("  +{:<3} {}", points, reason);
+    }
+}
+
+/// Fold file-structure anomalies (encryption, recovery, updates) into the score.
+pub fn add_structure_reasons(report: &mut Report, loaded: &Loaded, structure: &Structure) {
+    if loaded.encryption.as_ref().is_some_and(|e| e.empty_user_password) {
+        report.reasons.push((10, "Encrypted with an empty user password (hides content from scanners)".to_string()));
+    }
+    if loaded.recovered {
+        report.reasons.push((15, "Broken xref, objects had to be recovered from raw bytes".to_string()));
+    }
+    if structure.updates > 0 {
+        report.reasons.push((5, format!("{} incremental update(s) modify the original document", structure.updates)));
+    }
+    if structure.trailing_bytes > 0 {
+        report.reasons.push((10, format!("{} byte(s) of data after the last %%EOF", structure.trailing_bytes)));
+    }
+    if !structure.unreachable.is_empty() {
+        report.reasons.push((5, format!("{} object(s) unreachable from the trailer", structure.unreachable.len())));
+    }
+    report.score = report.reasons.iter().map(|(p, _)| p).sum::<u32>().min(100);
+}
+
+fn count_names(object: &Object, id: u32, hits: &mut BTreeMap<&'static str, KeywordHit>) {
+    match object {
+        Object::Dictionary(dict) => count_dict(dict, id, hits),
+        Object::Stream(stream) => count_dict(&stream.dict, id, hits),
+        Object::Array(items) => items.iter().for_each(|o| count_names(o, id, hits)),
+        Object::Name(name) => record(name, id, hits),
+        _ => {}
+    }
+}
+
+fn count_dict(dict: &Dictionary, id: u32, hits: &mut BTreeMap<&'static str, KeywordHit>) {
+    for (key, value) in dict.iter() {
+        record(key, id, hits);
+        count_names(value, id, hits);
+    }
+}
+
+fn record(name: &[u8], id: u32, hits: &mut BTreeMap<&'static str, KeywordHit>) {
+    if let Some(known) = RISKY_NAMES.iter().find(|n| n.as_bytes() == name) {
+        let hit = hits.entry(known).or_insert_with(|| KeywordHit {
+            name: known.to_string(),
+            count: 0,
+            objects: BTreeSet::new(),
+        });
+        hit.count += 1;
+        hit.objects.insert(id);
+    }
+}
+
+struct ActionWalker<'a> {
+    doc: &'a Document,
+    actions: Vec<Action>,
+    /// Scripts that some trigger leads to.
+    reached: HashSet<String>,
+    visited: HashSet<ObjectId>,
+}
+
+impl<'a> ActionWalker<'a> {
+    fn walk_triggers(&mut self) {
+        let doc = self.doc;
+        if let Ok(catalog) = doc.catalog() {
+            if let Ok(open) = catalog.get(b"OpenAction") {
+                self.follow("OpenAction", open);
+            }
+            // Document-level scripts run when the file opens.
+            if let Some(tree) = catalog.get(b"Names").ok()
+                .and_then(|n| resolve(doc, n).as_dict().ok())
+                .and_then(|n| n.get(b"JavaScript").ok())
+            {
+                let mut leaves = vec![];
+                name_tree_values(doc, tree, &mut leaves);
+                for (name, action) in leaves {
+                    self.follow(&format!("Names/JavaScript '{}'", name), action);
+                }
+            }
+        }
+
+        for (id, object) in &doc.objects {
+            let dict = match object {
+                Object::Dictionary(d) => d,
+                Object::Stream(s) => &s.dict,
+                _ => continue,
+            };
+            if let Ok(aa) = dict.get(b"AA") {
+                if let Ok(aa) = resolve(doc, aa).as_dict() {
+                    for (event, action) in aa.iter() {
+                        self.follow(&format!("AA/{} on obj {}", String::from_utf8_lossy(event), id.0), action);
+                    }
+                }
+            }
+            let is_annot = matches!(dict.get(b"Type").and_then(|t| t.as_name()), Ok(b"Annot"))
+                || (dict.get(b"Subtype").is_ok() && dict.get(b"Rect").is_ok());
+            let is_outline = dict.get(b"Parent").is_ok() && dict.get(b"Title").is_ok();
+            if is_annot || is_outline {
+                if let Ok(action) = dict.get(b"A") {
+                    let kind = if is_annot { "annotation" } else { "outline" };
+                    self.follow(&format!("{} obj {}", kind, id.0), action);
+                }
+            }
+        }
+    }
+
+    /// Records an action and everything chained after it through /Next. Iterative, so a
+    /// long chain cannot exhaust the stack; references are visited once, so cycles end.
+    fn follow(&mut self, trigger: &str, object: &'a Object) {
+        let doc = self.doc;
+        // (object, /Next hops from the trigger); chains are reported as "-> Next xN" so long
+        // chains do not build ever longer labels.
+        let mut pending = vec![(object, 0usize)];
+        while let Some((object, hops)) = pending.pop() {
+            let id = match object {
+                Object::Reference(r) => {
+                    if !self.visited.insert(*r) {
+                        continue;
+                    }
+                    Some(r.0)
+                }
+                _ => None,
+            };
+            let dict = match resolve(doc, object) {
+                Object::Dictionary(d) => d,
+                // Destinations (arrays) are plain navigation, not actions.
+                _ => continue,
+            };
+            let Ok(kind) = dict.get(b"S").and_then(|s| s.as_name()) else { continue };
+            let kind = String::from_utf8_lossy(kind).into_owned();
+
+            let target = match kind.as_str() {
+                "JavaScript" => dict.get(b"JS").ok().map(|js| {
+                    let code = js_code(doc, js).map(|(code, _)| code).unwrap_or_default();
+                    let target = preview(&code, 160);
+                    self.reached.insert(code);
+                    target
+                }),
+                "Launch" => launch_targets(doc, dict).into_iter().next(),
+                "URI" => dict.get(b"URI").ok().and_then(|u| string(doc, u)),
+                "SubmitForm" | "ImportData" | "GoToR" | "GoToE" => dict.get(b"F").ok().and_then(|f| file_spec(doc, f)),
+                _ => None,
+            };
+            let trigger = match hops {
+                0 => trigger.to_string(),
+                1 => format!("{} -> Next", trigger),
+                n => format!("{} -> Next x{}", trigger, n),
+            };
+            self.actions.push(Action { trigger, object: id, kind, target });
+
+            if let Ok(next) = dict.get(b"Next") {
+                match resolve(doc, next) {
+                    // Reversed so the first /Next entry is reported first.
+                    Object::Array(items) => pending.extend(items.iter().rev().map(|item| (item, hops + 1))),
+                    _ => pending.push((next, hops + 1)),
+                }
+            }
+        }
+    }
+}
+
+fn collect_scripts(doc: &Document, reached: &HashSet<String>) -> Vec<Script> {
+    let mut scripts = vec![];
+    for (id, object) in &doc.objects {
+        let Ok(dict) = object.as_dict() else { continue };
+        let Ok(js) = dict.get(b"JS") else { continue };
+        let Some((code, source)) = js_code(doc, js) else { continue };
+        scripts.push(Script {
+            object: id.0,
+            source,
+            reachable: reached.contains(&code),
+            code,
+        });
+    }
+    scripts
+}
+
+/// JavaScript carried by a /JS entry, either inline as a string or in a (filtered) stream.
+pub fn js_code(doc: &Document, js: &Object) -> Option<(String, String)> {
+    let source = match js {
+        Object::Reference(r) => format!("stream obj {}", r.0),
+        _ => "string".to_string(),
+    };
+    match resolve(doc, js) {
+        Object::String(bytes, _) => Some((pdf_decode::text_string(bytes), source)),
+        Object::Stream(stream) => {
+            let decoded = pdf_decode::decode_stream(doc, stream);
+            Some((pdf_decode::text_string(&decoded.data), source))
+        }
+        _ => None,
+    }
+}
+
+/// Flatten a name tree (/Names leaves under /Kids) into (key, value) pairs.
+pub fn name_tree_values<'a>(doc: &'a Document, node: &'a Object, out: &mut Vec<(String, &'a Object)>) {
+    name_tree_walk(doc, node, out, &mut HashSet::new(), 0);
+}
+
+// Kids may repeat or point back at a parent; each node is read once.
+fn name_tree_walk<'a>(doc: &'a Document, node: &'a Object, out: &mut Vec<(String, &'a Object)>, visited: &mut HashSet<ObjectId>, depth: usize) {
+    if let Object::Reference(id) = node {
+        if !visited.insert(*id) {
+            return;
+        }
+    }
+    let Ok(node) = resolve(doc, node).as_dict() else { return };
+    if depth > 32 {
+        return;
+    }
+    if let Ok(names) = node.get(b"Names").map(|n| resolve(doc, n)).and_then(|n| n.as_array()) {
+        for pair in names.chunks(2) {
+            if let [key, value] = pair {
+                out.push((string(doc, key).unwrap_or_default(), value));
+            }
+        }
+    }
+    if let Ok(kids) = node.get(b"Kids").map(|k| resolve(doc, k)).and_then(|k| k.as_array()) {
+        for kid in kids {
+            name_tree_walk(doc, kid, out, visited, depth + 1);
+        }
+    }
+}
+
+/// Raw scan for names such as /J#61vaScript that decode to a risky keyword.
+fn obfuscated_names(raw: &[u8]) -> Vec<String> {
+    let mut found = BTreeSet::new();
+    let mut i = 0;
+    while i < raw.len() {
+        if raw[i] != b'/' {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        i += 1;
+        while i < raw.len() && !raw[i].is_ascii_whitespace() && !b"/<>[]()%{}".contains(&raw[i]) {
+            i += 1;
+        }
+        let token = &raw[start + 1..i];
+        if !token.contains(&b'#') {
+            continue;
+        }
+        let decoded = decode_name(token);
+        if RISKY_NAMES.iter().any(|n| n.as_bytes() == decoded.as_slice()) {
+            found.insert(format!("/{} -> /{}", String::from_utf8_lossy(token), String::from_utf8_lossy(&decoded)));
+        }
+    }
+    found.into_iter().collect()
+}
+
+fn decode_name(token: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(token.len());
+    let mut i = 0;
+    while i < token.len() {
+        if token[i] == b'#' {
+            if let Some(byte) = token.get(i + 1..i + 3)
+                .and_then(|h| std::str::from_utf8(h).ok())
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+            {
+                out.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(token[i]);
+        i += 1;
+    }
+    out
+}
+
+fn score(report: &mut Report) {
+    let mut reasons: Vec<(u32, String)> = vec![];
+    let js_count = report.scripts.len();
+
+    if js_count > 0 || report.count("JS") > 0 || report.count("JavaScript") > 0 {
+        reasons.push((25, format!("Contains JavaScript ({} script(s))", js_count)));
+    }
+    let auto_js = report.actions.iter()
+        .any(|a| a.kind == "JavaScript" && (a.trigger.starts_with("OpenAction") || a.trigger.starts_with("Names/")));
+    if auto_js {
+        reasons.push((20, "JavaScript runs automatically when the document opens".to_string()));
+    } else if report.count("OpenAction") > 0 {
+        reasons.push((5, "Document has an /OpenAction".to_string()));
+    }
+    if report.count("AA") > 0 {
+        reasons.push((10, "Additional actions (/AA) fire on page, form or document events".to_string()));
+    }
+    if report.count("Launch") > 0 {
+        reasons.push((35, "Launch action can start external programs".to_string()));
+    }
+    if report.count("EmbeddedFile") > 0 {
+        reasons.push((15, "Embedded file(s) present".to_string()));
+    }
+    if report.count("RichMedia") > 0 {
+        reasons.push((15, "RichMedia (Flash/video) content present".to_string()));
+    }
+    if report.count("XFA") > 0 {
+        reasons.push((10, "XFA form present (scriptable, historically exploited)".to_string()));
+    }
+    if report.count("SubmitForm") > 0 {
+        reasons.push((10, "SubmitForm action sends data to a remote target".to_string()));
+    }
+    if report.count("URI") > 0 {
+        reasons.push((5, "URI action(s) link to external resources".to_string()));
+    }
+    if report.count("AcroForm") > 0 {
+        reasons.push((3, "Interactive form (/AcroForm) present".to_string()));
+    }
+    if report.count("JBIG2Decode") > 0 {
+        reasons.push((15, "JBIG2 images present (decoder targeted by known exploits)".to_string()));
+    }
+
+    let mut patterns: BTreeSet<&str> = BTreeSet::new();
+    for script in &report.scripts {
+        for pattern in SUSPICIOUS_JS {
+            if script.code.contains(pattern) {
+                patterns.insert(pattern);
+            }
+        }
+    }
+    if !patterns.is_empty() {
+        let points = (patterns.len() as u32 * 10).min(30);
+        reasons.push((points, format!("Suspicious JavaScript: {}",
+            patterns.into_iter().collect::<Vec<_>>().join(", "))));
+    }
+    if report.scripts.iter().any(|s| !s.reachable) {
+        reasons.push((5, "JavaScript present that no trigger references".to_string()));
+    }
+    if !report.obfuscated_names.is_empty() {
+        reasons.push((20, "Keywords hidden with #xx name escapes".to_string()));
+    }
+
+    report.score = reasons.iter().map(|(p, _)| p).sum::<u32>().min(100);
+    report.reasons = reasons;
+}
+
+fn join_ids(ids: &BTreeSet<u32>) -> String {
+    const MAX: usize = 12;
+    let mut list: Vec<String> = ids.iter().take(MAX).map(|i| i.to_string()).collect();
+    if ids.len() > MAX {
+        list.push(format!("... (+{})", ids.len() - MAX));
+    }
+    list.join(", ")
+}
+
+fn preview(code: &str, max: usize) -> String {
+    let flat: String = code.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .map(|c| if c.is_control() { '.' } else { c })
+        .collect();
+    if flat.chars().count() > max {
+        format!("{}...", flat.chars().take(max).collect::<String>())
+    } else {
+        flat
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::{DateTime, NaiveDateTime};
+use crate::helper::zip;
+
+const RAR4_MAGIC: &[u8] = b"Rar!\x1a\x07\x00";
+const RAR5_MAGIC: &[u8] = b"Rar!\x1a\x07\x01\x00";
+
+pub struct Entry {
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub modified: Option<NaiveDateTime>,
+    pub is_dir: bool,
+    pub encrypted: bool,
+    pub method: String,
+    /// Symlink, junction or hard link target (RAR5 redirection record).
+    pub link: Option<String>,
+}
+
+pub struct Listing {
+    /// 4 for RAR 1.5-4.x, 5 for RAR 5.0+.
+    pub version: u8,
+    /// File names and sizes are encrypted too (rar -hp); nothing can be listed without the password.
+    pub headers_encrypted: bool,
+    pub entries: Vec<Entry>,
+    /// Why listing stopped early, if it did.
+    pub stopped: Option<String>,
+}
+
+pub fn is_rar(data: &[u8]) -> bool {
+    data.starts_with(RAR4_MAGIC) || data.starts_with(RAR5_MAGIC)
+}
+
+/// File headers of a RAR archive; the compressed data itself is never decoded.
+pub fn list(data: &[u8]) -> Listing {
+    if data.starts_with(RAR5_MAGIC) {
+        list_rar5(data)
+    } else {
+        list_rar4(data)
+    }
+}
+
+fn list_rar4(data: &[u8]) -> Listing {
+    let mut listing = Listing { version: 4, headers_encrypted: false, entries: vec![], stopped: None };
+    let mut pos = RAR4_MAGIC.len();
+    while pos < data.len() {
+        let (Some(kind), Some(flags), Some(head_size)) = (data.get(pos + 2), u16_at(data, pos + 3), u16_at(data, pos + 5)) else {
+            listing.stopped = Some(format!("truncated block header at offset {}", pos));
+            break;
+        };
+        if head_size < 7 {
+            listing.stopped = Some(format!("invalid block size at offset {}", pos));
+            break;
+        }
+        let Some(header) = data.get(pos..pos + head_size as usize) else {
+            listing.stopped = Some(format!("truncated block header at offset {}", pos));
+            break;
+        };
+        let mut data_size = if flags & 0x8000 != 0 { u32_at(header, 7).unwrap_or(0) as u64 } else { 0 };
+        match kind {
+            // Main header, MHD_PASSWORD: every following header is encrypted.
+            0x73 if flags & 0x0080 != 0 => {
+                listing.headers_encrypted = true;
+                break;
+            }
+            0x74 => {
+                let Some(entry) = rar4_file(header, flags) else {
+                    listing.stopped = Some(format!("truncated file header at offset {}", pos));
+                    break;
+                };
+                data_size = entry.compressed_size;
+                listing.entries.push(entry);
+            }
+            0x7B => break,
+            _ => {}
+        }
+        match (pos + head_size as usize).checked_add(data_size as usize) {
+            Some(next) => pos = next,
+            None => {
+                listing.stopped = Some(format!("block at offset {} declares an impossible size", pos));
+                break;
+            }
+        }
+    }
+    listing
+}
+
+fn rar4_file(header: &[u8], flags: u16) -> Option<Entry> {
+    let large = flags & 0x0100 != 0;
+    let high = |at: usize| if large { u32_at(header, at).map(|h| (h as u64) << 32) } else { Some(0) };
+    let compressed_size = u32_at(header, 7)? as u64 | high(32)?;
+    let size = u32_at(header, 11)? as u64 | high(36)?;
+    let time = u32_at(header, 20)?;
+    let method = *header.get(25)?;
+    let name_len = u16_at(header, 26)? as usize;
+    let name_start = if large { 40 } else { 32 };
+    let name = header.get(name_start..name_start + name_len)?;
+    // With the unicode flag, an encoded UTF-16 copy follows a NUL after the plain name.
+    let name = name.split(|&b| b == 0).next().unwrap_or_default();
+    Some(Entry {
+        name: String::from_utf8_lossy(name).into_owned(),
+        size,
+        compressed_size,
+        modified: zip::dos_datetime((time >> 16) as u16, time as u16),
+        is_dir: flags & 0x00E0 == 0x00E0,
+        encrypted: flags & 0x0004 != 0,
+        method: match method {
+            0x30 => "stored".to_string(),
+            m @ 0x31..=0x35 => format!("rar m{}", m - 0x30),
+            m => format!("method 0x{:02x}", m),
+        },
+        link: None,
+    })
+}
+
+fn list_rar5(data: &[u8]) -> Listing {
+    let mut listing = Listing { version: 5, headers_encrypted: false, entries: vec![], stopped: None };
+    let mut pos = RAR5_MAGIC.len();
+    while pos < data.len() {
+        // CRC32, then the header size, then the header itself.
+        let mut at = pos + 4;
+        let header = vint(data, &mut at)
+            .and_then(|size| usize::try_from(size).ok())
+            .and_then(|size| at.checked_add(size).and_then(|end| data.get(at..end)));
+        let Some(header) = header else {
+            listing.stopped = Some(format!("truncated block header at offset {}", pos));
+            break;
+        };
+        let header_end = at + header.len();
+        let mut i = 0;
+        let (Some(kind), Some(flags)) = (vint(header, &mut i), vint(header, &mut i)) else {
+            listing.stopped = Some(format!("invalid block header at offset {}", pos));
+            break;
+        };
+        let extra_size = if flags & 0x1 != 0 { vint(header, &mut i).unwrap_or(0) } else { 0 };
+        let data_size = if flags & 0x2 != 0 { vint(header, &mut i).unwrap_or(0) } else { 0 };
+        match kind {
+            // Archive encryption header: everything after it is encrypted.
+            4 => {
+                listing.headers_encrypted = true;
+                break;
+            }
+            2 => {
+                let extra = usize::try_from(extra_size).ok()
+                    .and_then(|e| header.len().checked_sub(e))
+                    .map(|start| &header[start..])
+                    .unwrap_or_default();
+                match rar5_file(header, i, extra, data_size) {
+                    Some(entry) => listing.entries.push(entry),
+                    None => {
+                        listing.stopped = Some(format!("truncated file header at offset {}", pos));
+                        break;
+                    }
+                }
+            }
+            5 => break,
+            _ => {}
+        }
+        match usize::try_from(data_size).ok().and_then(|d| header_end.checked_add(d)) {
+            Some(next) => pos = next,
+            None => {
+                listing.stopped = Some(format!("block at offset {} declares an impossible size", pos));
+                break;
+            }
+        }
+    }
+    listing
+}
+
+fn rar5_file(header: &[u8], mut i: usize, extra: &[u8], compressed_size: u64) -> Option<Entry> {
+    let file_flags = vint(header, &mut i)?;
+    let size = vint(header, &mut i)?;
+    let _attributes = vint(header, &mut i)?;
+    let mut modified = None;
+    if file_flags & 0x2 != 0 {
+        let time = u32_at(header, i)?;
+        modified = DateTime::from_timestamp(time as i64, 0).map(|t| t.naive_utc());
+        i += 4;
+    }
+    if file_flags & 0x4 != 0 {
+        i += 4;
+    }
+    let compression = vint(header, &mut i)?;
+    let _host_os = vint(header, &mut i)?;
+    let name_len = vint(header, &mut i)? as usize;
+    let name = header.get(i..i.checked_add(name_len)?)?;
+
+    let mut entry = Entry {
+        name: String::from_utf8_lossy(name).into_owned(),
+        size,
+        compressed_size,
+        modified,
+        is_dir: file_flags & 0x1 != 0,
+        encrypted: false,
+        method: match (compression >> 7) & 0x7 {
+            0 => "stored".to_string(),
+            m => format!("rar m{}", m),
+        },
+        link: None,
+    };
+
+    // Extra records: size, type, then type-specific data.
+    let mut j = 0;
+    while j < extra.len() {
+        let Some(record_size) = vint(extra, &mut j) else { break };
+        let Some(record) = usize::try_from(record_size).ok()
+            .and_then(|s| j.checked_add(s))
+            .and_then(|end| extra.get(j..end)) else { break };
+        j += record.len();
+        let mut k = 0;
+        match vint(record, &mut k) {
+            Some(1) => entry.encrypted = true,
+            Some(5) => {
+                let _redirection = vint(record, &mut k);
+                let _flags = vint(record, &mut k);
+                if let Some(len) = vint(record, &mut k) {
+                    let target = record.get(k..k + (len as usize).min(record.len() - k)).unwrap_or_default();
+                    entry.link = Some(String::from_utf8_lossy(target).into_owned());
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(entry)
+}
+
+/// RAR5 variable-length integer: 7 bits per byte, least significant first.
+fn vint(data: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..70).step_by(7) {
+        let b = *data.get(*pos)?;
+        *pos += 1;
+        value |= ((b & 0x7F) as u64).checked_shl(shift)?;
+        if b & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Option<u16> {
+    let bytes = data.get(pos..pos + 2)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Option<u32> {
+    let bytes = data.get(pos..pos + 4)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
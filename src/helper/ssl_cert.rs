@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use x509_parser::extensions::{DistributionPointName, GeneralName, ParsedExtension, X509Extension};
+use x509_parser::objects::{oid2sn, oid_registry};
+use x509_parser::parse_x509_certificate;
+use x509_parser::public_key::PublicKey;
+
+pub struct CertInfo {
+    pub der: Vec<u8>,
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+    pub sans: Vec<String>,
+    pub key_type: String,
+    pub key_bits: usize,
+    pub signature_algorithm: String,
+    pub sha256: String,
+    pub is_ca: bool,
+    pub extensions: Vec<(String, String)>,
+}
+
+pub fn parse_der(der: &[u8]) -> Result<CertInfo> {
+    let (_, cert) = parse_x509_certificate(der)
+        .map_err(|e| anyhow!("Failed to parse X.509 certificate: {}", e))?;
+
+    let registry = oid_registry();
+
+    let mut sans = Vec::new();
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in &san.value.general_names {
+            match name {
+                GeneralName::DNSName(dns) => sans.push(dns.to_string()),
+                GeneralName::IPAddress(ip) => sans.push(format_ip(ip)),
+                other => sans.push(other.to_string()),
+            }
+        }
+    }
+
+    let spki = cert.public_key();
+    let key_oid = &spki.algorithm.algorithm;
+    let key_type = match oid2sn(key_oid, registry) {
+        Ok("rsaEncryption") => "RSA".to_string(),
+        Ok("id-ecPublicKey") => "EC".to_string(),
+        Ok("id-dsa") => "DSA".to_string(),
+        Ok(sn) => sn.to_string(),
+        Err(_) => key_oid.to_id_string(),
+    };
+    let key_bits = match spki.parsed() {
+        Ok(PublicKey::RSA(rsa)) => bit_length(rsa.modulus),
+        Ok(key) => key.key_size(),
+        Err(_) => 0,
+    };
+
+    let sig_oid = &cert.signature_algorithm.algorithm;
+    let signature_algorithm = oid2sn(sig_oid, registry)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|_| sig_oid.to_id_string());
+
+    let validity = cert.validity();
+
+    Ok(CertInfo {
+        der: der.to_vec(),
+        subject: cert.subject().to_string(),
+        issuer: cert.issuer().to_string(),
+        serial: cert.raw_serial_as_string(),
+        not_before: to_datetime(validity.not_before.timestamp()),
+        not_after: to_datetime(validity.not_after.timestamp()),
+        sans,
+        key_type,
+        key_bits,
+        signature_algorithm,
+        sha256: fingerprint(der),
+        is_ca: cert.is_ca(),
+        extensions: cert.extensions().iter().map(describe_extension).collect(),
+    })
+}
+
+pub fn print_cert(index: usize, cert: &CertInfo) {
+    println!("Certificate #{}", index);
+    println!("  Subject:         {}", cert.subject);
+    println!("  Issuer:          {}", cert.issuer);
+    println!("  Serial:          {}", cert.serial);
+    println!("  Not Before:      {}", cert.not_before.format("%Y-%m-%d %H:%M:%S UTC"));
+    println!("  Not After:       {}", cert.not_after.format("%Y-%m-%d %H:%M:%S UTC"));
+    println!("  Key:             {} ({} bits)", cert.key_type, cert.key_bits);
+    println!("  Signature:       {}", cert.signature_algorithm);
+    println!("  CA:              {}", cert.is_ca);
+    println!("  SHA-256:         {}", cert.sha256);
+    if cert.sans.is_empty() {
+        println!("  SANs:            (none)");
+    } else {
+        println!("  SANs:");
+        for san in &cert.sans {
+            println!("    - {}", san);
+        }
+    }
+    if !cert.extensions.is_empty() {
+        println!("  Extensions:");
+        for (name, value) in &cert.extensions {
+            println!("    {}: {}", name, value);
+        }
+    }
+}
+
+fn describe_extension(ext: &X509Extension) -> (String, String) {
+    let registry = oid_registry();
+    let mut name = oid2sn(&ext.oid, registry)
+        .map(|s| s.to_string())
+        .unwrap_or_else(|_| ext.oid.to_id_string());
+    if ext.critical {
+        name.push_str(" (critical)");
+    }
+
+    let value = match ext.parsed_extension() {
+        ParsedExtension::KeyUsage(ku) => ku.to_string(),
+        ParsedExtension::ExtendedKeyUsage(eku) => {
+            let mut usages = Vec::new();
+            for (set, label) in [
+                (eku.any, "any"),
+                (eku.server_auth, "serverAuth"),
+                (eku.client_auth, "clientAuth"),
+                (eku.code_signing, "codeSigning"),
+                (eku.email_protection, "emailProtection"),
+                (eku.time_stamping, "timeStamping"),
+                (eku.ocsp_signing, "OCSPSigning"),
+            ] {
+                if set {
+                    usages.push(label.to_string());
+                }
+            }
+            usages.extend(eku.other.iter().map(|oid| oid.to_id_string()));
+            usages.join(", ")
+        }
+        ParsedExtension::BasicConstraints(bc) => match bc.path_len_constraint {
+            Some(len) => format!("CA:{}, pathlen:{}", bc.ca, len),
+            None => format!("CA:{}", bc.ca),
+        },
+        ParsedExtension::AuthorityInfoAccess(aia) => aia.iter()
+            .map(|desc| {
+                let method = oid2sn(&desc.access_method, registry)
+                    .map(|s| s.to_string())
+                    .unwrap_or_else(|_| desc.access_method.to_id_string());
+                format!("{} - {}", method, desc.access_location)
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        ParsedExtension::CRLDistributionPoints(crl) => crl.iter()
+            .filter_map(|point| match &point.distribution_point {
+                Some(DistributionPointName::FullName(names)) => Some(
+                    names.iter().map(|n| n.to_string()).collect::<Vec<_>>().join(", ")),
+                Some(DistributionPointName::NameRelativeToCRLIssuer(_)) => Some("<relative name>".to_string()),
+                None => None,
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        ParsedExtension::SubjectAlternativeName(san) => san.general_names.iter()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        ParsedExtension::SubjectKeyIdentifier(ski) => format!("{:x}", ski),
+        ParsedExtension::AuthorityKeyIdentifier(aki) => match &aki.key_identifier {
+            Some(id) => format!("{:x}", id),
+            None => "<no key identifier>".to_string(),
+        },
+        ParsedExtension::CertificatePolicies(policies) => policies.iter()
+            .map(|p| p.policy_id.to_id_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        ParsedExtension::SCT(scts) => scts.iter()
+            .map(|sct| {
+                let log_id: String = sct.id.key_id.iter().map(|b| format!("{:02x}", b)).collect();
+                let ts = DateTime::from_timestamp_millis(sct.timestamp as i64).unwrap_or_default();
+                format!("[log {} at {}]", log_id, ts.format("%Y-%m-%d %H:%M:%S UTC"))
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        ParsedExtension::ParseError { error } => format!("<parse error: {}>", error),
+        _ => ext.value.iter().map(|b| format!("{:02x}", b)).collect::<String>(),
+    };
+    (name, value)
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn bit_length(bytes: &[u8]) -> usize {
+    let trimmed: Vec<u8> = bytes.iter().copied().skip_while(|&b| b == 0).collect();
+    match trimmed.first() {
+        Some(first) => trimmed.len() * 8 - first.leading_zeros() as usize,
+        None => 0,
+    }
+}
+
+fn format_ip(ip: &[u8]) -> String {
+    match ip.len() {
+        4 => std::net::Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]).to_string(),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(ip);
+            std::net::Ipv6Addr::from(octets).to_string()
+        }
+        _ => format!("{:02x?}", ip),
+    }
+}
+
+fn to_datetime(ts: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(ts, 0).unwrap_or_default()
+}
@@ -0,0 +1,285 @@
+use anyhow::{Result, Context};
+use chrono::{Duration, Utc};
+use openssl::stack::Stack;
+use openssl::x509::store::{X509Store, X509StoreBuilder};
+use openssl::x509::{X509, X509StoreContext, X509VerifyResult};
+use crate::helper::ssl_cert::{self, CertInfo};
+
+const X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY: i32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Critical,
+    High,
+    Medium,
+    Low,
+    Info,
+}
+
+impl Severity {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Critical => "CRITICAL",
+            Severity::High => "HIGH",
+            Severity::Medium => "MEDIUM",
+            Severity::Low => "LOW",
+            Severity::Info => "INFO",
+        }
+    }
+}
+
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+pub fn check_chain(host: &str, chain: &[Vec<u8>], expiry_days: i64) -> Result<Vec<Finding>> {
+    let mut findings = Vec::new();
+
+    if chain.is_empty() {
+        findings.push(finding(Severity::Critical, "Server did not present any certificate."));
+        return Ok(findings);
+    }
+
+    // One unparseable certificate must not hide the findings for the others.
+    let mut certs: Vec<(usize, CertInfo, X509)> = Vec::new();
+    for (i, der) in chain.iter().enumerate() {
+        let parsed = ssl_cert::parse_der(der)
+            .and_then(|info| Ok((info, X509::from_der(der).context("Failed to decode certificate")?)));
+        match parsed {
+            Ok((info, x509)) => certs.push((i, info, x509)),
+            Err(e) => {
+                let severity = if i == 0 { Severity::Critical } else { Severity::High };
+                findings.push(finding(severity, &format!("Certificate #{} could not be parsed: {:#}.", i, e)));
+            }
+        }
+    }
+
+    let store = system_store()?;
+    let now = Utc::now();
+    let leaf = certs.first().filter(|(i, ..)| *i == 0);
+
+    // === Validity ===
+    if let Some((_, leaf, _)) = leaf {
+        if leaf.not_after < now {
+            findings.push(finding(Severity::Critical, &format!(
+                "Leaf certificate expired on {}.", leaf.not_after.format("%Y-%m-%d"))));
+        } else if leaf.not_before > now {
+            findings.push(finding(Severity::Critical, &format!(
+                "Leaf certificate is not valid before {}.", leaf.not_before.format("%Y-%m-%d"))));
+        }
+    }
+    for (i, cert, _) in &certs {
+        if cert.not_after < now {
+            if *i > 0 {
+                findings.push(finding(Severity::High, &format!(
+                    "Chain certificate #{} ({}) expired on {}.", i, cert.subject, cert.not_after.format("%Y-%m-%d"))));
+            }
+        } else if cert.not_after < now + Duration::days(expiry_days) {
+            let left = (cert.not_after - now).num_days();
+            findings.push(finding(Severity::Medium, &format!(
+                "Certificate #{} ({}) expires in {} day(s) on {}.", i, cert.subject, left, cert.not_after.format("%Y-%m-%d"))));
+        }
+    }
+
+    // === Hostname ===
+    if let Some((_, leaf, _)) = leaf {
+        let names: Vec<String> = if leaf.sans.is_empty() {
+            common_name(&leaf.subject).into_iter().collect()
+        } else {
+            leaf.sans.clone()
+        };
+        if !names.iter().any(|n| hostname_matches(n, host)) {
+            findings.push(finding(Severity::High, &format!(
+                "Hostname '{}' does not match certificate names: {}.", host, names.join(", "))));
+        }
+    }
+
+    // === Self-signed / chain completeness ===
+    let leaf_self_signed = leaf.is_some_and(|(_, _, x509)| is_self_signed(x509));
+    if leaf_self_signed {
+        findings.push(finding(Severity::High, "Leaf certificate is self-signed."));
+    }
+    for (i, cert, x509) in &certs {
+        if is_self_signed(x509) {
+            continue;
+        }
+        let issuer_served = certs.iter().any(|(_, c, _)| c.subject == cert.issuer);
+        if !issuer_served && !issuer_trusted(&store, x509)? {
+            findings.push(finding(Severity::High, &format!(
+                "Missing intermediate: issuer '{}' of certificate #{} was not served and is not a trusted root.",
+                cert.issuer, i)));
+        }
+    }
+    if let Some((_, _, leaf_x509)) = leaf.filter(|_| !leaf_self_signed) {
+        let intermediates: Vec<X509> = certs[1..].iter().map(|(_, _, x509)| x509.clone()).collect();
+        let result = verify(&store, leaf_x509, &intermediates)?;
+        if result != X509VerifyResult::OK {
+            findings.push(finding(Severity::High, &format!(
+                "Chain does not verify against system roots: {}.", result.error_string())));
+        }
+    }
+
+    // === Key and signature strength ===
+    for (i, cert, x509) in &certs {
+        if cert.key_type == "RSA" && cert.key_bits < 2048 {
+            findings.push(finding(Severity::High, &format!(
+                "Certificate #{} uses a weak {}-bit RSA key.", i, cert.key_bits)));
+        }
+        let sig = cert.signature_algorithm.to_lowercase();
+        if !is_self_signed(x509) && (sig.contains("sha1") || sig.contains("md5")) {
+            findings.push(finding(Severity::High, &format!(
+                "Certificate #{} is signed with weak algorithm {}.", i, cert.signature_algorithm)));
+        }
+    }
+
+    findings.sort_by_key(|f| f.severity);
+    Ok(findings)
+}
+
+pub fn print_findings(host: &str, findings: &[Finding]) {
+    println!("Summary Report for '{}':", host);
+    if findings.is_empty() {
+        println!("No issues found!");
+    } else {
+        for f in findings {
+            println!("[{:<8}] {}", f.severity.label(), f.message);
+        }
+    }
+}
+
+fn finding(severity: Severity, message: &str) -> Finding {
+    Finding { severity, message: message.to_string() }
+}
+
+fn common_name(subject: &str) -> Option<String> {
+    subject.split(", ")
+        .find_map(|part| part.strip_prefix("CN="))
+        .map(|cn| cn.to_string())
+}
+
+fn hostname_matches(pattern: &str, host: &str) -> bool {
+    let pattern = pattern.trim_end_matches('.').to_lowercase();
+    let host = host.trim_end_matches('.').to_lowercase();
+    match pattern.strip_prefix("*.") {
+        Some(suffix) => match host.split_once('.') {
+            Some((label, rest)) => !label.is_empty() && rest == suffix,
+            None => false,
+        },
+        None => pattern == host,
+    }
+}
+
+fn is_self_signed(cert: &X509) -> bool {
+    let same_name = cert.subject_name().to_der().ok() == cert.issuer_name().to_der().ok();
+    same_name && cert.public_key()
+        .and_then(|key| cert.verify(&key))
+        .unwrap_or(false)
+}
+
+fn system_store() -> Result<X509Store> {
+    let mut builder = X509StoreBuilder::new().context("Failed to create certificate store")?;
+    builder.set_default_paths().context("Failed to load system trust roots")?;
+    Ok(builder.build())
+}
+
+fn verify(store: &X509Store, cert: &X509, intermediates: &[X509]) -> Result<X509VerifyResult> {
+    let mut stack = Stack::new()?;
+    for c in intermediates {
+        stack.push(c.clone())?;
+    }
+    let mut ctx = X509StoreContext::new()?;
+    let result = ctx.init(store, cert, &stack, |c| {
+        c.verify_cert()?;
+        Ok(c.error())
+    })?;
+    Ok(result)
+}
+
+// The issuer is found locally when verification gets past the issuer lookup step.
+fn issuer_trusted(store: &X509Store, cert: &X509) -> Result<bool> {
+    let result = verify(store, cert, &[])?;
+    Ok(result.as_raw() != X509_V_ERR_UNABLE_TO_GET_ISSUER_CERT_LOCALLY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::ssl_test;
+
+    fn messages(findings: &[Finding]) -> Vec<(Severity, &str)> {
+        findings.iter().map(|f| (f.severity, f.message.as_str())).collect()
+    }
+
+    #[test]
+    fn hostname_matching() {
+        assert!(hostname_matches("example.com", "EXAMPLE.com."));
+        assert!(hostname_matches("*.example.com", "www.example.com"));
+        assert!(!hostname_matches("*.example.com", "example.com"));
+        assert!(!hostname_matches("*.example.com", "a.b.example.com"));
+        assert!(!hostname_matches("*.example.com", ".example.com"));
+        assert!(!hostname_matches("example.com", "www.example.com"));
+    }
+
+    #[test]
+    fn san_takes_precedence_over_common_name() {
+        let (cert, _) = ssl_test::certificate("cn.example.com", &["www.example.com", "*.api.example.com"], -1, 365, 2048);
+        let chain = [cert.to_der().unwrap()];
+        for host in ["www.example.com", "v1.api.example.com"] {
+            let findings = check_chain(host, &chain, 30).unwrap();
+            assert!(!findings.iter().any(|f| f.message.starts_with("Hostname")), "{}", host);
+        }
+        let findings = check_chain("cn.example.com", &chain, 30).unwrap();
+        assert!(findings.iter().any(|f| f.severity == Severity::High
+            && f.message == "Hostname 'cn.example.com' does not match certificate names: www.example.com, *.api.example.com."));
+    }
+
+    #[test]
+    fn common_name_is_used_without_san() {
+        let (cert, _) = ssl_test::certificate("host.example.com", &[], -1, 365, 2048);
+        let findings = check_chain("host.example.com", &[cert.to_der().unwrap()], 30).unwrap();
+        assert!(!findings.iter().any(|f| f.message.starts_with("Hostname")));
+        assert!(findings.iter().any(|f| f.message == "Leaf certificate is self-signed."));
+    }
+
+    #[test]
+    fn expired_and_expiring_certificates() {
+        let (expired, _) = ssl_test::certificate("example.com", &["example.com"], -30, -1, 2048);
+        let findings = check_chain("example.com", &[expired.to_der().unwrap()], 30).unwrap();
+        assert_eq!(findings[0].severity, Severity::Critical);
+        assert!(findings[0].message.starts_with("Leaf certificate expired on "));
+
+        let (expiring, _) = ssl_test::certificate("example.com", &["example.com"], -30, 10, 2048);
+        let findings = check_chain("example.com", &[expiring.to_der().unwrap()], 30).unwrap();
+        assert!(findings.iter().any(|f| f.severity == Severity::Medium
+            && f.message.starts_with("Certificate #0 (CN=example.com) expires in ")));
+        let findings = check_chain("example.com", &[expiring.to_der().unwrap()], 5).unwrap();
+        assert!(!findings.iter().any(|f| f.severity == Severity::Medium));
+    }
+
+    #[test]
+    fn weak_rsa_key() {
+        let (cert, _) = ssl_test::certificate("example.com", &["example.com"], -1, 365, 1024);
+        let findings = check_chain("example.com", &[cert.to_der().unwrap()], 30).unwrap();
+        assert!(messages(&findings).contains(&(Severity::High, "Certificate #0 uses a weak 1024-bit RSA key.")));
+    }
+
+    #[test]
+    fn unparseable_certificate_does_not_stop_the_check() {
+        let (cert, _) = ssl_test::certificate("example.com", &["example.com"], -1, 365, 1024);
+        let chain = [cert.to_der().unwrap(), b"not a certificate".to_vec()];
+        let findings = check_chain("example.com", &chain, 30).unwrap();
+        assert!(findings.iter().any(|f| f.severity == Severity::High && f.message.starts_with("Certificate #1 could not be parsed")));
+        assert!(messages(&findings).contains(&(Severity::High, "Certificate #0 uses a weak 1024-bit RSA key.")));
+
+        let findings = check_chain("example.com", &[b"garbage".to_vec()], 30).unwrap();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Critical);
+    }
+
+    #[test]
+    fn empty_chain() {
+        let findings = check_chain("example.com", &[], 30).unwrap();
+        assert_eq!(messages(&findings), [(Severity::Critical, "Server did not present any certificate.")]);
+    }
+}
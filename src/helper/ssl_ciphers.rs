@@ -0,0 +1,524 @@
+use anyhow::{bail, Result};
+use std::io::{Read, Write};
+use crate::helper::ssl_check::{Finding, Severity};
+use crate::helper::ssl_live;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Protocol {
+    Tls10,
+    Tls11,
+    Tls12,
+    Tls13,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 4] = [Protocol::Tls10, Protocol::Tls11, Protocol::Tls12, Protocol::Tls13];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Protocol::Tls10 => "TLSv1.0",
+            Protocol::Tls11 => "TLSv1.1",
+            Protocol::Tls12 => "TLSv1.2",
+            Protocol::Tls13 => "TLSv1.3",
+        }
+    }
+
+    fn wire(&self) -> u16 {
+        match self {
+            Protocol::Tls10 => 0x0301,
+            Protocol::Tls11 => 0x0302,
+            Protocol::Tls12 => 0x0303,
+            Protocol::Tls13 => 0x0304,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Grade {
+    Strong,
+    Weak,
+    Insecure,
+}
+
+impl Grade {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Grade::Strong => "strong",
+            Grade::Weak => "weak",
+            Grade::Insecure => "insecure",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CipherSuite {
+    pub id: u16,
+    pub name: &'static str,
+}
+
+impl CipherSuite {
+    pub fn is_tls13(&self) -> bool {
+        self.id >> 8 == 0x13
+    }
+
+    pub fn forward_secrecy(&self) -> bool {
+        self.is_tls13() || self.name.starts_with("TLS_ECDHE_") || self.name.starts_with("TLS_DHE_")
+    }
+
+    pub fn grade(&self) -> Grade {
+        let n = self.name;
+        if n.contains("NULL") || n.contains("EXPORT") || n.contains("anon")
+            || n.contains("RC4") || n.contains("_DES_CBC") || n.ends_with("MD5")
+        {
+            Grade::Insecure
+        } else if n.contains("3DES") || n.contains("CBC") || !self.forward_secrecy() {
+            Grade::Weak
+        } else {
+            Grade::Strong
+        }
+    }
+}
+
+const SUITES: &[CipherSuite] = &[
+    // TLS 1.3
+    CipherSuite { id: 0x1301, name: "TLS_AES_128_GCM_SHA256" },
+    CipherSuite { id: 0x1302, name: "TLS_AES_256_GCM_SHA384" },
+    CipherSuite { id: 0x1303, name: "TLS_CHACHA20_POLY1305_SHA256" },
+    CipherSuite { id: 0x1304, name: "TLS_AES_128_CCM_SHA256" },
+    CipherSuite { id: 0x1305, name: "TLS_AES_128_CCM_8_SHA256" },
+    // ECDHE-ECDSA
+    CipherSuite { id: 0xc02b, name: "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256" },
+    CipherSuite { id: 0xc02c, name: "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384" },
+    CipherSuite { id: 0xcca9, name: "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256" },
+    CipherSuite { id: 0xc023, name: "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA256" },
+    CipherSuite { id: 0xc024, name: "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA384" },
+    CipherSuite { id: 0xc009, name: "TLS_ECDHE_ECDSA_WITH_AES_128_CBC_SHA" },
+    CipherSuite { id: 0xc00a, name: "TLS_ECDHE_ECDSA_WITH_AES_256_CBC_SHA" },
+    CipherSuite { id: 0xc008, name: "TLS_ECDHE_ECDSA_WITH_3DES_EDE_CBC_SHA" },
+    CipherSuite { id: 0xc007, name: "TLS_ECDHE_ECDSA_WITH_RC4_128_SHA" },
+    CipherSuite { id: 0xc006, name: "TLS_ECDHE_ECDSA_WITH_NULL_SHA" },
+    // ECDHE-RSA
+    CipherSuite { id: 0xc02f, name: "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256" },
+    CipherSuite { id: 0xc030, name: "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384" },
+    CipherSuite { id: 0xcca8, name: "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256" },
+    CipherSuite { id: 0xc027, name: "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA256" },
+    CipherSuite { id: 0xc028, name: "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA384" },
+    CipherSuite { id: 0xc013, name: "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA" },
+    CipherSuite { id: 0xc014, name: "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA" },
+    CipherSuite { id: 0xc012, name: "TLS_ECDHE_RSA_WITH_3DES_EDE_CBC_SHA" },
+    CipherSuite { id: 0xc011, name: "TLS_ECDHE_RSA_WITH_RC4_128_SHA" },
+    CipherSuite { id: 0xc010, name: "TLS_ECDHE_RSA_WITH_NULL_SHA" },
+    // DHE-RSA
+    CipherSuite { id: 0x009e, name: "TLS_DHE_RSA_WITH_AES_128_GCM_SHA256" },
+    CipherSuite { id: 0x009f, name: "TLS_DHE_RSA_WITH_AES_256_GCM_SHA384" },
+    CipherSuite { id: 0xccaa, name: "TLS_DHE_RSA_WITH_CHACHA20_POLY1305_SHA256" },
+    CipherSuite { id: 0x0067, name: "TLS_DHE_RSA_WITH_AES_128_CBC_SHA256" },
+    CipherSuite { id: 0x006b, name: "TLS_DHE_RSA_WITH_AES_256_CBC_SHA256" },
+    CipherSuite { id: 0x0033, name: "TLS_DHE_RSA_WITH_AES_128_CBC_SHA" },
+    CipherSuite { id: 0x0039, name: "TLS_DHE_RSA_WITH_AES_256_CBC_SHA" },
+    CipherSuite { id: 0x0045, name: "TLS_DHE_RSA_WITH_CAMELLIA_128_CBC_SHA" },
+    CipherSuite { id: 0x0088, name: "TLS_DHE_RSA_WITH_CAMELLIA_256_CBC_SHA" },
+    CipherSuite { id: 0x0016, name: "TLS_DHE_RSA_WITH_3DES_EDE_CBC_SHA" },
+    CipherSuite { id: 0x0015, name: "TLS_DHE_RSA_WITH_DES_CBC_SHA" },
+    CipherSuite { id: 0x0014, name: "TLS_DHE_RSA_EXPORT_WITH_DES40_CBC_SHA" },
+    // Static RSA key exchange
+    CipherSuite { id: 0x009c, name: "TLS_RSA_WITH_AES_128_GCM_SHA256" },
+    CipherSuite { id: 0x009d, name: "TLS_RSA_WITH_AES_256_GCM_SHA384" },
+    CipherSuite { id: 0x003c, name: "TLS_RSA_WITH_AES_128_CBC_SHA256" },
+    CipherSuite { id: 0x003d, name: "TLS_RSA_WITH_AES_256_CBC_SHA256" },
+    CipherSuite { id: 0x002f, name: "TLS_RSA_WITH_AES_128_CBC_SHA" },
+    CipherSuite { id: 0x0035, name: "TLS_RSA_WITH_AES_256_CBC_SHA" },
+    CipherSuite { id: 0x0041, name: "TLS_RSA_WITH_CAMELLIA_128_CBC_SHA" },
+    CipherSuite { id: 0x0084, name: "TLS_RSA_WITH_CAMELLIA_256_CBC_SHA" },
+    CipherSuite { id: 0x000a, name: "TLS_RSA_WITH_3DES_EDE_CBC_SHA" },
+    CipherSuite { id: 0x0009, name: "TLS_RSA_WITH_DES_CBC_SHA" },
+    CipherSuite { id: 0x0005, name: "TLS_RSA_WITH_RC4_128_SHA" },
+    CipherSuite { id: 0x0004, name: "TLS_RSA_WITH_RC4_128_MD5" },
+    CipherSuite { id: 0x0003, name: "TLS_RSA_EXPORT_WITH_RC4_40_MD5" },
+    CipherSuite { id: 0x0008, name: "TLS_RSA_EXPORT_WITH_DES40_CBC_SHA" },
+    CipherSuite { id: 0x003b, name: "TLS_RSA_WITH_NULL_SHA256" },
+    CipherSuite { id: 0x0002, name: "TLS_RSA_WITH_NULL_SHA" },
+    CipherSuite { id: 0x0001, name: "TLS_RSA_WITH_NULL_MD5" },
+    // Anonymous
+    CipherSuite { id: 0x0034, name: "TLS_DH_anon_WITH_AES_128_CBC_SHA" },
+    CipherSuite { id: 0x003a, name: "TLS_DH_anon_WITH_AES_256_CBC_SHA" },
+    CipherSuite { id: 0x001b, name: "TLS_DH_anon_WITH_3DES_EDE_CBC_SHA" },
+    CipherSuite { id: 0x0018, name: "TLS_DH_anon_WITH_RC4_128_MD5" },
+    CipherSuite { id: 0xc018, name: "TLS_ECDH_anon_WITH_AES_128_CBC_SHA" },
+    CipherSuite { id: 0xc019, name: "TLS_ECDH_anon_WITH_AES_256_CBC_SHA" },
+];
+
+pub struct ProtocolResult {
+    pub protocol: Protocol,
+    pub suites: Vec<CipherSuite>,
+    /// Set when a probe failed to connect; `suites` holds what was accepted before that.
+    pub error: Option<String>,
+}
+
+/// Enumerate accepted suites per protocol. Each round offers every suite not yet
+/// seen and records the one the server picks, so the cost is one connection per
+/// accepted suite rather than per candidate.
+pub fn scan(host: &str, port: u16) -> Result<Vec<ProtocolResult>> {
+    let mut results = Vec::new();
+    for protocol in Protocol::ALL {
+        let mut remaining: Vec<CipherSuite> = SUITES.iter()
+            .filter(|s| s.is_tls13() == (protocol == Protocol::Tls13))
+            .copied()
+            .collect();
+        let mut accepted = Vec::new();
+        let mut error = None;
+
+        while !remaining.is_empty() {
+            // A reset or refused probe ends this protocol only, the others are still scanned.
+            let chosen = match try_hello(host, port, protocol, &remaining) {
+                Ok(Some(chosen)) => chosen,
+                Ok(None) => break,
+                Err(e) => {
+                    error = Some(format!("{:#}", e));
+                    break;
+                }
+            };
+            match remaining.iter().position(|s| s.id == chosen) {
+                Some(pos) => accepted.push(remaining.remove(pos)),
+                None => break,
+            }
+        }
+        results.push(ProtocolResult { protocol, suites: accepted, error });
+    }
+    if results.iter().all(|r| r.suites.is_empty()) {
+        if let Some(error) = results.iter().find_map(|r| r.error.as_ref()) {
+            bail!("{}", error);
+        }
+    }
+    Ok(results)
+}
+
+pub fn findings(results: &[ProtocolResult]) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for r in results {
+        if let Some(error) = &r.error {
+            findings.push(Finding {
+                severity: Severity::Low,
+                message: format!("Probing {} failed, its results may be incomplete: {}.", r.protocol.name(), error),
+            });
+        }
+    }
+    let enabled: Vec<&ProtocolResult> = results.iter().filter(|r| !r.suites.is_empty()).collect();
+
+    if enabled.is_empty() {
+        findings.push(Finding {
+            severity: Severity::Critical,
+            message: "Server did not accept any tested protocol or cipher suite.".to_string(),
+        });
+        return findings;
+    }
+
+    for r in &enabled {
+        if matches!(r.protocol, Protocol::Tls10 | Protocol::Tls11) {
+            findings.push(Finding {
+                severity: Severity::High,
+                message: format!("Deprecated protocol {} is enabled.", r.protocol.name()),
+            });
+        }
+    }
+    if !enabled.iter().any(|r| r.protocol == Protocol::Tls13) {
+        findings.push(Finding {
+            severity: Severity::Low,
+            message: "TLSv1.3 is not supported.".to_string(),
+        });
+    }
+
+    let all: Vec<&CipherSuite> = enabled.iter().flat_map(|r| r.suites.iter()).collect();
+    let mut insecure: Vec<&str> = all.iter().filter(|s| s.grade() == Grade::Insecure).map(|s| s.name).collect();
+    insecure.sort();
+    insecure.dedup();
+    if !insecure.is_empty() {
+        findings.push(Finding {
+            severity: Severity::Critical,
+            message: format!("Insecure cipher suites accepted: {}.", insecure.join(", ")),
+        });
+    }
+
+    let mut no_fs: Vec<&str> = all.iter().filter(|s| !s.forward_secrecy()).map(|s| s.name).collect();
+    no_fs.sort();
+    no_fs.dedup();
+    if !all.iter().any(|s| s.forward_secrecy()) {
+        findings.push(Finding {
+            severity: Severity::High,
+            message: "No cipher suite with forward secrecy is accepted.".to_string(),
+        });
+    } else if !no_fs.is_empty() {
+        findings.push(Finding {
+            severity: Severity::Medium,
+            message: format!("{} suite(s) without forward secrecy accepted: {}.", no_fs.len(), no_fs.join(", ")),
+        });
+    }
+
+    findings.sort_by_key(|f| f.severity);
+    findings
+}
+
+fn try_hello(host: &str, port: u16, protocol: Protocol, suites: &[CipherSuite]) -> Result<Option<u16>> {
+    let mut stream = ssl_live::connect(host, port)?;
+    let hello = client_hello(host, protocol, suites)?;
+    if stream.write_all(&hello).is_err() {
+        return Ok(None);
+    }
+
+    let Some(server_hello) = read_server_hello(&mut stream) else {
+        return Ok(None);
+    };
+    match parse_server_hello(&server_hello) {
+        Some((version, cipher)) if version == protocol.wire() => Ok(Some(cipher)),
+        _ => Ok(None),
+    }
+}
+
+/// Negotiated version and cipher suite from a ServerHello body.
+fn parse_server_hello(server_hello: &[u8]) -> Option<(u16, u16)> {
+    if server_hello.len() < 38 {
+        return None;
+    }
+
+    let mut version = u16::from_be_bytes([server_hello[0], server_hello[1]]);
+    let sid_len = server_hello[34] as usize;
+    let pos = 35 + sid_len;
+    if server_hello.len() < pos + 3 {
+        return None;
+    }
+    let cipher = u16::from_be_bytes([server_hello[pos], server_hello[pos + 1]]);
+
+    // TLS 1.3 negotiates through the supported_versions extension.
+    let mut ext = pos + 3;
+    if server_hello.len() >= ext + 2 {
+        ext += 2;
+        while ext + 4 <= server_hello.len() {
+            let ext_type = u16::from_be_bytes([server_hello[ext], server_hello[ext + 1]]);
+            let ext_len = u16::from_be_bytes([server_hello[ext + 2], server_hello[ext + 3]]) as usize;
+            if ext_type == 0x002b && ext_len == 2 && ext + 6 <= server_hello.len() {
+                version = u16::from_be_bytes([server_hello[ext + 4], server_hello[ext + 5]]);
+            }
+            ext += 4 + ext_len;
+        }
+    }
+    Some((version, cipher))
+}
+
+fn client_hello(host: &str, protocol: Protocol, suites: &[CipherSuite]) -> Result<Vec<u8>> {
+    let mut random = [0u8; 32];
+    openssl::rand::rand_bytes(&mut random)?;
+
+    let mut body = Vec::new();
+    body.extend_from_slice(&protocol.wire().min(0x0303).to_be_bytes());
+    body.extend_from_slice(&random);
+    body.push(0); // session id
+    push_u16(&mut body, (suites.len() * 2) as u16);
+    for s in suites {
+        body.extend_from_slice(&s.id.to_be_bytes());
+    }
+    body.extend_from_slice(&[1, 0]); // null compression only
+
+    let mut exts = Vec::new();
+    if host.parse::<std::net::IpAddr>().is_err() {
+        let name = host.as_bytes();
+        let mut sni = Vec::new();
+        push_u16(&mut sni, (name.len() + 3) as u16);
+        sni.push(0);
+        push_u16(&mut sni, name.len() as u16);
+        sni.extend_from_slice(name);
+        push_ext(&mut exts, 0x0000, &sni);
+    }
+    push_ext(&mut exts, 0x000a, &[0x00, 0x0a, 0x00, 0x1d, 0x00, 0x17, 0x00, 0x18, 0x00, 0x19, 0x01, 0x00]);
+    push_ext(&mut exts, 0x000b, &[0x01, 0x00]);
+    push_ext(&mut exts, 0xff01, &[0x00]);
+    if matches!(protocol, Protocol::Tls12 | Protocol::Tls13) {
+        let algs: [u16; 11] = [0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0807, 0x0401, 0x0501, 0x0601, 0x0201];
+        let mut data = Vec::new();
+        push_u16(&mut data, (algs.len() * 2) as u16);
+        for alg in algs {
+            push_u16(&mut data, alg);
+        }
+        push_ext(&mut exts, 0x000d, &data);
+    }
+    if protocol == Protocol::Tls13 {
+        push_ext(&mut exts, 0x002b, &[0x02, 0x03, 0x04]);
+        push_ext(&mut exts, 0x002d, &[0x01, 0x01]);
+        let mut key = [0u8; 32];
+        openssl::rand::rand_bytes(&mut key)?;
+        let mut share = Vec::new();
+        push_u16(&mut share, 36);
+        push_u16(&mut share, 0x001d);
+        push_u16(&mut share, 32);
+        share.extend_from_slice(&key);
+        push_ext(&mut exts, 0x0033, &share);
+    }
+    push_u16(&mut body, exts.len() as u16);
+    body.extend_from_slice(&exts);
+
+    let mut handshake = vec![0x01];
+    handshake.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    handshake.extend_from_slice(&body);
+
+    let mut record = vec![0x16, 0x03, 0x01];
+    push_u16(&mut record, handshake.len() as u16);
+    record.extend_from_slice(&handshake);
+    Ok(record)
+}
+
+// Returns the ServerHello body, or None on alert / disconnect / anything unexpected.
+fn read_server_hello<R: Read>(stream: &mut R) -> Option<Vec<u8>> {
+    let mut handshake = Vec::new();
+    loop {
+        let mut header = [0u8; 5];
+        stream.read_exact(&mut header).ok()?;
+        let len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let mut payload = vec![0u8; len];
+        stream.read_exact(&mut payload).ok()?;
+        if header[0] != 0x16 {
+            return None;
+        }
+        handshake.extend_from_slice(&payload);
+
+        if handshake.len() >= 4 {
+            if handshake[0] != 0x02 {
+                return None;
+            }
+            let msg_len = u32::from_be_bytes([0, handshake[1], handshake[2], handshake[3]]) as usize;
+            if handshake.len() >= 4 + msg_len {
+                return Some(handshake[4..4 + msg_len].to_vec());
+            }
+        }
+    }
+}
+
+fn push_u16(buf: &mut Vec<u8>, v: u16) {
+    buf.extend_from_slice(&v.to_be_bytes());
+}
+
+fn push_ext(buf: &mut Vec<u8>, ext_type: u16, data: &[u8]) {
+    push_u16(buf, ext_type);
+    push_u16(buf, data.len() as u16);
+    buf.extend_from_slice(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::ssl_test;
+    use std::io::Cursor;
+
+    fn server_hello(version: u16, cipher: u16, extensions: &[u8]) -> Vec<u8> {
+        let mut body = version.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0x42; 32]);
+        body.push(32);
+        body.extend_from_slice(&[0x07; 32]);
+        body.extend_from_slice(&cipher.to_be_bytes());
+        body.push(0);
+        push_u16(&mut body, extensions.len() as u16);
+        body.extend_from_slice(extensions);
+        body
+    }
+
+    fn record(content_type: u8, payload: &[u8]) -> Vec<u8> {
+        let mut record = vec![content_type, 0x03, 0x03];
+        push_u16(&mut record, payload.len() as u16);
+        record.extend_from_slice(payload);
+        record
+    }
+
+    fn handshake(body: &[u8]) -> Vec<u8> {
+        let mut message = vec![0x02];
+        message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        message.extend_from_slice(body);
+        message
+    }
+
+    #[test]
+    fn reads_server_hello_across_records() {
+        let body = server_hello(0x0303, 0xc02f, &[]);
+        let message = handshake(&body);
+        let (first, second) = message.split_at(10);
+        let mut wire = record(0x16, first);
+        wire.extend(record(0x16, second));
+        assert_eq!(read_server_hello(&mut Cursor::new(wire)), Some(body));
+    }
+
+    #[test]
+    fn rejects_alerts_and_truncated_records() {
+        assert_eq!(read_server_hello(&mut Cursor::new(record(0x15, &[2, 70]))), None);
+        let message = handshake(&server_hello(0x0303, 0xc02f, &[]));
+        let wire = record(0x16, &message);
+        assert_eq!(read_server_hello(&mut Cursor::new(&wire[..wire.len() - 1])), None);
+        // Certificate instead of ServerHello.
+        let mut certificate = message.clone();
+        certificate[0] = 0x0b;
+        assert_eq!(read_server_hello(&mut Cursor::new(record(0x16, &certificate))), None);
+    }
+
+    #[test]
+    fn parses_version_and_cipher() {
+        assert_eq!(parse_server_hello(&server_hello(0x0303, 0xc02f, &[])), Some((0x0303, 0xc02f)));
+        // TLS 1.3 keeps 0x0303 in the legacy field and puts the real version in supported_versions.
+        let supported_versions = [0x00, 0x2b, 0x00, 0x02, 0x03, 0x04];
+        assert_eq!(parse_server_hello(&server_hello(0x0303, 0x1302, &supported_versions)), Some((0x0304, 0x1302)));
+        assert_eq!(parse_server_hello(&[0x03; 37]), None);
+        let mut bad_session = server_hello(0x0303, 0xc02f, &[]);
+        bad_session[34] = 0xff;
+        assert_eq!(parse_server_hello(&bad_session), None);
+    }
+
+    #[test]
+    fn scans_local_server() {
+        let (cert, key) = ssl_test::certificate("localhost", &["localhost"], -1, 30, 2048);
+        let port = ssl_test::tls_server(&cert, &key, usize::MAX);
+        let results = scan("127.0.0.1", port).unwrap();
+
+        let accepted = |protocol| results.iter().find(|r| r.protocol == protocol).unwrap();
+        assert!(accepted(Protocol::Tls10).suites.is_empty());
+        assert!(accepted(Protocol::Tls11).suites.is_empty());
+        assert!(accepted(Protocol::Tls12).suites.iter().any(|s| s.name == "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256"));
+        assert!(accepted(Protocol::Tls13).suites.iter().any(|s| s.name == "TLS_AES_256_GCM_SHA384"));
+        assert!(results.iter().all(|r| r.error.is_none()));
+    }
+
+    #[test]
+    fn refused_probe_does_not_abort_the_scan() {
+        // TLS 1.0 and 1.1 are rejected, the first TLS 1.2 probe succeeds, then the port closes.
+        let (cert, key) = ssl_test::certificate("localhost", &["localhost"], -1, 30, 2048);
+        let port = ssl_test::tls_server(&cert, &key, 3);
+        let results = scan("127.0.0.1", port).unwrap();
+
+        let tls12 = &results[2];
+        assert_eq!(tls12.suites.len(), 1);
+        assert!(tls12.error.is_some());
+        assert!(results[3].suites.is_empty() && results[3].error.is_some());
+        assert!(findings(&results).iter().any(|f| f.severity == Severity::Low && f.message.starts_with("Probing TLSv1.3 failed")));
+    }
+
+    #[test]
+    fn unreachable_server_is_an_error() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        assert!(scan("127.0.0.1", port).is_err());
+    }
+
+    #[test]
+    fn grades_and_findings() {
+        let suite = |name| *SUITES.iter().find(|s| s.name == name).unwrap();
+        assert_eq!(suite("TLS_RSA_WITH_RC4_128_MD5").grade(), Grade::Insecure);
+        assert_eq!(suite("TLS_RSA_WITH_AES_128_GCM_SHA256").grade(), Grade::Weak);
+        assert_eq!(suite("TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA").grade(), Grade::Weak);
+        assert_eq!(suite("TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256").grade(), Grade::Strong);
+
+        let results = [
+            ProtocolResult { protocol: Protocol::Tls10, suites: vec![suite("TLS_RSA_WITH_RC4_128_MD5")], error: None },
+            ProtocolResult { protocol: Protocol::Tls12, suites: vec![suite("TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256")], error: None },
+        ];
+        let messages: Vec<(Severity, String)> = findings(&results).into_iter().map(|f| (f.severity, f.message)).collect();
+        assert_eq!(messages, [
+            (Severity::Critical, "Insecure cipher suites accepted: TLS_RSA_WITH_RC4_128_MD5.".to_string()),
+            (Severity::High, "Deprecated protocol TLSv1.0 is enabled.".to_string()),
+            (Severity::Medium, "1 suite(s) without forward secrecy accepted: TLS_RSA_WITH_RC4_128_MD5.".to_string()),
+            (Severity::Low, "TLSv1.3 is not supported.".to_string()),
+        ]);
+    }
+}
@@ -0,0 +1,58 @@
+use anyhow::{Result, Context, bail};
+use openssl::pkcs12::Pkcs12;
+use openssl::pkcs7::Pkcs7;
+use openssl::x509::{X509, X509Ref};
+
+pub struct Decoded {
+    pub format: &'static str,
+    pub certs: Vec<Vec<u8>>,
+    pub has_private_key: bool,
+}
+
+pub fn decode(data: &[u8], password: &str) -> Result<Decoded> {
+    let text = String::from_utf8_lossy(data);
+    if text.contains("-----BEGIN") {
+        let has_private_key = text.contains("PRIVATE KEY-----");
+        if text.contains("-----BEGIN PKCS7-----") {
+            let p7 = Pkcs7::from_pem(data).context("Failed to decode PEM PKCS#7 container")?;
+            return Ok(Decoded { format: "PEM PKCS#7", certs: pkcs7_certs(&p7)?, has_private_key });
+        }
+        let certs = X509::stack_from_pem(data).context("Failed to decode PEM certificates")?;
+        return Ok(Decoded { format: "PEM", certs: to_der(certs.iter().map(|c| &**c))?, has_private_key });
+    }
+
+    if let Ok(cert) = X509::from_der(data) {
+        return Ok(Decoded { format: "DER", certs: vec![cert.to_der()?], has_private_key: false });
+    }
+    if let Ok(p7) = Pkcs7::from_der(data) {
+        return Ok(Decoded { format: "DER PKCS#7", certs: pkcs7_certs(&p7)?, has_private_key: false });
+    }
+    if let Ok(p12) = Pkcs12::from_der(data) {
+        let parsed = p12.parse2(password)
+            .context("Failed to open PKCS#12 container (wrong password?)")?;
+        let mut certs = Vec::new();
+        if let Some(cert) = parsed.cert {
+            certs.push(cert.to_der()?);
+        }
+        if let Some(ca) = parsed.ca {
+            certs.extend(to_der(ca.iter())?);
+        }
+        return Ok(Decoded { format: "PKCS#12", certs, has_private_key: parsed.pkey.is_some() });
+    }
+
+    bail!("Unrecognized certificate format (expected PEM, DER, PKCS#7 or PKCS#12)")
+}
+
+fn pkcs7_certs(p7: &Pkcs7) -> Result<Vec<Vec<u8>>> {
+    match p7.signed().and_then(|signed| signed.certificates()) {
+        Some(stack) => to_der(stack.iter()),
+        None => Ok(vec![]),
+    }
+}
+
+fn to_der<'a, I>(certs: I) -> Result<Vec<Vec<u8>>>
+where
+    I: Iterator<Item = &'a X509Ref>,
+{
+    certs.map(|c| c.to_der().context("Failed to encode certificate")).collect()
+}
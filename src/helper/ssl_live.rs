@@ -0,0 +1,91 @@
+use anyhow::{Result, Context};
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use crate::helper::ssl_starttls::Starttls;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Handshake {
+    pub protocol: String,
+    pub cipher: String,
+    pub chain: Vec<Vec<u8>>,
+}
+
+pub fn connect(host: &str, port: u16) -> Result<TcpStream> {
+    let addr = (host, port).to_socket_addrs()
+        .with_context(|| format!("Failed to resolve {}:{}", host, port))?
+        .next()
+        .with_context(|| format!("No address found for {}:{}", host, port))?;
+    let stream = TcpStream::connect_timeout(&addr, TIMEOUT)
+        .with_context(|| format!("Failed to connect to {}", addr))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+fn handshake(host: &str, stream: TcpStream) -> Result<Handshake> {
+    let mut builder = SslConnector::builder(SslMethod::tls_client())
+        .context("Failed to create TLS connector")?;
+    // We want to see whatever the server presents, even if it does not verify.
+    builder.set_verify(SslVerifyMode::NONE);
+    let connector = builder.build();
+
+    let mut config = connector.configure()
+        .context("Failed to configure TLS connector")?;
+    config.set_verify_hostname(false);
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        config.set_use_server_name_indication(false);
+    }
+
+    let tls = config.connect(host, stream)
+        .map_err(|e| anyhow::anyhow!("TLS handshake with {} failed: {}", host, e))?;
+
+    let ssl = tls.ssl();
+    let mut chain = Vec::new();
+    if let Some(certs) = ssl.peer_cert_chain() {
+        for cert in certs {
+            chain.push(cert.to_der().context("Failed to encode peer certificate")?);
+        }
+    } else if let Some(cert) = ssl.peer_certificate() {
+        chain.push(cert.to_der().context("Failed to encode peer certificate")?);
+    }
+
+    Ok(Handshake {
+        protocol: ssl.version_str().to_string(),
+        cipher: ssl.current_cipher().map(|c| c.name().to_string()).unwrap_or_default(),
+        chain,
+    })
+}
+
+pub fn fetch_chain(host: &str, port: u16, starttls: Option<Starttls>) -> Result<Handshake> {
+    let mut stream = connect(host, port)?;
+    if let Some(proto) = starttls {
+        proto.negotiate(&mut stream, host)
+            .with_context(|| format!("STARTTLS ({:?}) negotiation with {} failed", proto, host))?;
+    }
+    handshake(host, stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::ssl_test;
+
+    #[test]
+    fn fetches_chain_from_local_server() {
+        let (cert, key) = ssl_test::certificate("localhost", &["localhost"], -1, 30, 2048);
+        let port = ssl_test::tls_server(&cert, &key, 1);
+
+        let handshake = fetch_chain("127.0.0.1", port, None).unwrap();
+        assert!(handshake.protocol.starts_with("TLSv1."));
+        assert!(!handshake.cipher.is_empty());
+        assert_eq!(handshake.chain, vec![cert.to_der().unwrap()]);
+    }
+
+    #[test]
+    fn refused_connection_is_an_error() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        assert!(fetch_chain("127.0.0.1", port, None).is_err());
+    }
+}
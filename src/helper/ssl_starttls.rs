@@ -0,0 +1,247 @@
+use anyhow::{Result, Context, bail};
+use std::io::{Read, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Starttls {
+    Smtp,
+    Imap,
+    Pop3,
+    Ftp,
+    Xmpp,
+}
+
+impl Starttls {
+    pub fn parse(name: &str) -> Result<Starttls> {
+        match name.to_lowercase().as_str() {
+            "smtp" => Ok(Starttls::Smtp),
+            "imap" => Ok(Starttls::Imap),
+            "pop3" => Ok(Starttls::Pop3),
+            "ftp" => Ok(Starttls::Ftp),
+            "xmpp" => Ok(Starttls::Xmpp),
+            other => bail!("Unknown STARTTLS protocol '{}' (expected smtp, imap, pop3, ftp or xmpp)", other),
+        }
+    }
+
+    pub fn default_port(&self) -> u16 {
+        match self {
+            Starttls::Smtp => 25,
+            Starttls::Imap => 143,
+            Starttls::Pop3 => 110,
+            Starttls::Ftp => 21,
+            Starttls::Xmpp => 5222,
+        }
+    }
+
+    /// Run the plaintext exchange that upgrades the connection, leaving the
+    /// stream ready for a TLS ClientHello.
+    pub fn negotiate<S: Read + Write>(&self, stream: &mut S, host: &str) -> Result<()> {
+        match self {
+            Starttls::Smtp => {
+                expect_reply(stream, "220")?;
+                send(stream, "EHLO rex.local\r\n")?;
+                expect_reply(stream, "250")?;
+                send(stream, "STARTTLS\r\n")?;
+                expect_reply(stream, "220")
+            }
+            Starttls::Ftp => {
+                expect_reply(stream, "220")?;
+                send(stream, "AUTH TLS\r\n")?;
+                expect_reply(stream, "234")
+            }
+            Starttls::Pop3 => {
+                expect_line(stream, "+OK")?;
+                send(stream, "STLS\r\n")?;
+                expect_line(stream, "+OK")
+            }
+            Starttls::Imap => {
+                expect_line(stream, "* OK")?;
+                send(stream, "a001 STARTTLS\r\n")?;
+                loop {
+                    let line = read_line(stream)?;
+                    if line.starts_with("a001 ") {
+                        if line.starts_with("a001 OK") {
+                            return Ok(());
+                        }
+                        bail!("IMAP server refused STARTTLS: {}", line);
+                    }
+                }
+            }
+            Starttls::Xmpp => {
+                send(stream, &format!(
+                    "<?xml version='1.0'?><stream:stream xmlns='jabber:client' \
+                     xmlns:stream='http://etherx.jabber.org/streams' to='{}' version='1.0'>",
+                    host
+                ))?;
+                let features = read_until(stream, "</stream:features>")?;
+                if !features.contains("starttls") {
+                    bail!("XMPP server does not offer STARTTLS");
+                }
+                send(stream, "<starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>")?;
+                let reply = read_until(stream, ">")?;
+                if !reply.contains("<proceed") {
+                    bail!("XMPP server refused STARTTLS: {}", reply);
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+fn send<W: Write>(stream: &mut W, data: &str) -> Result<()> {
+    stream.write_all(data.as_bytes()).context("Failed to send STARTTLS command")
+}
+
+// Reads byte by byte so nothing past the server reply is consumed before the handshake.
+fn read_line<R: Read>(stream: &mut R) -> Result<String> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        let n = stream.read(&mut byte).context("Failed to read server reply")?;
+        if n == 0 {
+            bail!("Connection closed during STARTTLS negotiation");
+        }
+        if byte[0] == b'\n' {
+            break;
+        }
+        line.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end().to_string())
+}
+
+fn read_until<R: Read>(stream: &mut R, marker: &str) -> Result<String> {
+    let mut data = Vec::new();
+    let mut byte = [0u8; 1];
+    while !data.ends_with(marker.as_bytes()) {
+        let n = stream.read(&mut byte).context("Failed to read server reply")?;
+        if n == 0 {
+            bail!("Connection closed during STARTTLS negotiation");
+        }
+        data.push(byte[0]);
+    }
+    Ok(String::from_utf8_lossy(&data).to_string())
+}
+
+fn expect_line<R: Read>(stream: &mut R, prefix: &str) -> Result<()> {
+    let line = read_line(stream)?;
+    if !line.starts_with(prefix) {
+        bail!("Unexpected server reply (wanted '{}'): {}", prefix, line);
+    }
+    Ok(())
+}
+
+// SMTP/FTP style replies: "250-..." continuation lines end with "250 ...".
+fn expect_reply<R: Read>(stream: &mut R, code: &str) -> Result<()> {
+    loop {
+        let line = read_line(stream)?;
+        if !line.starts_with(code) {
+            bail!("Unexpected server reply (wanted {}): {}", code, line);
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::{ssl_live, ssl_test};
+    use std::io::{BufRead, BufReader, Cursor};
+
+    /// Replays a canned server transcript and records what the client sent.
+    struct Script {
+        server: Cursor<Vec<u8>>,
+        client: Vec<u8>,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            self.server.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.client.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn run(proto: Starttls, server: &str) -> (Result<()>, String, Script) {
+        let mut script = Script { server: Cursor::new(server.as_bytes().to_vec()), client: vec![] };
+        let result = proto.negotiate(&mut script, "mail.example.com");
+        let sent = String::from_utf8_lossy(&script.client).into_owned();
+        (result, sent, script)
+    }
+
+    #[test]
+    fn smtp_multiline_replies() {
+        let (result, sent, mut script) = run(Starttls::Smtp,
+            "220-mail.example.com ESMTP\r\n220 ready\r\n250-mail.example.com\r\n250-SIZE 1000\r\n250 STARTTLS\r\n220 go ahead\r\nTLS");
+        result.unwrap();
+        assert_eq!(sent, "EHLO rex.local\r\nSTARTTLS\r\n");
+        // Nothing past the last reply is consumed, the TLS handshake starts there.
+        let mut rest = String::new();
+        script.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "TLS");
+    }
+
+    #[test]
+    fn smtp_refusal_and_disconnect() {
+        let (result, _, _) = run(Starttls::Smtp, "220 ready\r\n250 ok\r\n454 TLS not available\r\n");
+        assert!(result.unwrap_err().to_string().contains("454 TLS not available"));
+        let (result, _, _) = run(Starttls::Smtp, "220 ready\r\n250-mail.example.com\r\n");
+        assert!(result.unwrap_err().to_string().contains("Connection closed"));
+    }
+
+    #[test]
+    fn imap_waits_for_tagged_reply() {
+        let (result, sent, _) = run(Starttls::Imap, "* OK IMAP4rev1\r\n* CAPABILITY IMAP4rev1 STARTTLS\r\na001 OK Begin TLS\r\n");
+        result.unwrap();
+        assert_eq!(sent, "a001 STARTTLS\r\n");
+        let (result, _, _) = run(Starttls::Imap, "* OK IMAP4rev1\r\na001 NO not now\r\n");
+        assert!(result.unwrap_err().to_string().contains("a001 NO not now"));
+    }
+
+    #[test]
+    fn pop3_ftp_and_xmpp() {
+        let (result, sent, _) = run(Starttls::Pop3, "+OK POP3 ready\r\n+OK Begin TLS\r\n");
+        result.unwrap();
+        assert_eq!(sent, "STLS\r\n");
+        let (result, _, _) = run(Starttls::Pop3, "+OK POP3 ready\r\n-ERR no\r\n");
+        assert!(result.is_err());
+
+        let (result, sent, _) = run(Starttls::Ftp, "220-Welcome\r\n220 FTP\r\n234 AUTH TLS ok\r\n");
+        result.unwrap();
+        assert_eq!(sent, "AUTH TLS\r\n");
+
+        let features = "<stream:stream><stream:features><starttls xmlns='urn:ietf:params:xml:ns:xmpp-tls'/></stream:features>";
+        let (result, sent, _) = run(Starttls::Xmpp, &format!("{}<proceed xmlns='urn:ietf:params:xml:ns:xmpp-tls'/>", features));
+        result.unwrap();
+        assert!(sent.contains("to='mail.example.com'"));
+        let (result, _, _) = run(Starttls::Xmpp, "<stream:stream><stream:features></stream:features>");
+        assert!(result.unwrap_err().to_string().contains("does not offer STARTTLS"));
+    }
+
+    #[test]
+    fn upgrades_fake_smtp_server() {
+        let (cert, key) = ssl_test::certificate("localhost", &["localhost"], -1, 30, 2048);
+        let acceptor = ssl_test::acceptor(&cert, &key);
+        let port = ssl_test::serve(1, move |mut stream| {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut line = String::new();
+            stream.write_all(b"220 fake ESMTP\r\n").unwrap();
+            reader.read_line(&mut line).unwrap();
+            stream.write_all(b"250-fake\r\n250 STARTTLS\r\n").unwrap();
+            reader.read_line(&mut line).unwrap();
+            stream.write_all(b"220 go ahead\r\n").unwrap();
+            let _ = acceptor.accept(stream);
+        });
+
+        let handshake = ssl_live::fetch_chain("127.0.0.1", port, Some(Starttls::Smtp)).unwrap();
+        assert_eq!(handshake.chain, vec![cert.to_der().unwrap()]);
+    }
+}
@@ -0,0 +1,74 @@
+use openssl::asn1::Asn1Time;
+use openssl::bn::BigNum;
+use openssl::hash::MessageDigest;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::ssl::{SslAcceptor, SslMethod};
+use openssl::x509::extension::SubjectAlternativeName;
+use openssl::x509::{X509, X509NameBuilder};
+use std::net::{TcpListener, TcpStream};
+
+const DAY: i64 = 24 * 60 * 60;
+
+/// Self-signed certificate valid from `from_days` to `to_days` relative to now.
+pub fn certificate(cn: &str, sans: &[&str], from_days: i64, to_days: i64, rsa_bits: u32) -> (X509, PKey<Private>) {
+    let key = PKey::from_rsa(Rsa::generate(rsa_bits).unwrap()).unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_text("CN", cn).unwrap();
+    let name = name.build();
+
+    let now = chrono::Utc::now().timestamp();
+    let mut builder = X509::builder().unwrap();
+    builder.set_version(2).unwrap();
+    builder.set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap()).unwrap();
+    builder.set_subject_name(&name).unwrap();
+    builder.set_issuer_name(&name).unwrap();
+    builder.set_pubkey(&key).unwrap();
+    builder.set_not_before(&Asn1Time::from_unix(now + from_days * DAY).unwrap()).unwrap();
+    builder.set_not_after(&Asn1Time::from_unix(now + to_days * DAY).unwrap()).unwrap();
+    if !sans.is_empty() {
+        let mut san = SubjectAlternativeName::new();
+        for name in sans {
+            san.dns(name);
+        }
+        let san = san.build(&builder.x509v3_context(None, None)).unwrap();
+        builder.append_extension(san).unwrap();
+    }
+    builder.sign(&key, MessageDigest::sha256()).unwrap();
+    (builder.build(), key)
+}
+
+pub fn acceptor(cert: &X509, key: &PKey<Private>) -> SslAcceptor {
+    let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    builder.set_certificate(cert).unwrap();
+    builder.set_private_key(key).unwrap();
+    builder.build()
+}
+
+/// Runs `handle` for `connections` loopback connections, then stops listening, and returns
+/// the port. The listener is closed before the last connection is handled, so any later
+/// connection attempt is refused.
+pub fn serve(connections: usize, handle: impl Fn(TcpStream) + Send + 'static) -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+        for _ in 1..connections {
+            if let Ok((stream, _)) = listener.accept() {
+                handle(stream);
+            }
+        }
+        if let Ok((stream, _)) = listener.accept() {
+            drop(listener);
+            handle(stream);
+        }
+    });
+    port
+}
+
+/// TLS server answering every handshake with `cert`.
+pub fn tls_server(cert: &X509, key: &PKey<Private>, connections: usize) -> u16 {
+    let acceptor = acceptor(cert, key);
+    serve(connections, move |stream| {
+        let _ = acceptor.accept(stream);
+    })
+}
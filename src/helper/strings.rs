@@ -0,0 +1,109 @@
+use serde::Serialize;
+use crate::helper::ioc;
+
+// Longer runs are split, a disk image can hold megabytes of text without a NUL.
+const MAX_RUN: usize = 4096;
+
+#[derive(Debug, Serialize)]
+pub struct Found {
+    pub offset: u64,
+    /// ascii or utf16le.
+    pub encoding: &'static str,
+    pub value: String,
+}
+
+/// A string with whatever `ioc::classify` recognised in it.
+#[derive(Debug, Serialize)]
+pub struct Row {
+    #[serde(flatten)]
+    pub found: Found,
+    pub indicators: Vec<ioc::Indicator>,
+}
+
+#[derive(Default)]
+struct Run {
+    start: u64,
+    text: String,
+}
+
+/// Streaming extractor for printable ASCII and UTF-16LE runs, fed chunk by chunk so
+/// arbitrarily large inputs (disk images, memory dumps) never sit in memory.
+pub struct Scanner {
+    min_len: usize,
+    ascii: bool,
+    wide: bool,
+    pos: u64,
+    prev: Option<u8>,
+    ascii_run: Run,
+    /// UTF-16LE runs starting at even and odd offsets.
+    wide_runs: [Run; 2],
+}
+
+impl Scanner {
+    pub fn new(min_len: usize, ascii: bool, wide: bool) -> Scanner {
+        Scanner {
+            min_len: min_len.max(1),
+            ascii,
+            wide,
+            pos: 0,
+            prev: None,
+            ascii_run: Run::default(),
+            wide_runs: [Run::default(), Run::default()],
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8], out: &mut impl FnMut(Found)) {
+        for &b in chunk {
+            if self.ascii {
+                if is_printable(b) {
+                    push(&mut self.ascii_run, self.pos, b, self.min_len, "ascii", out);
+                } else {
+                    flush(&mut self.ascii_run, self.min_len, "ascii", out);
+                }
+            }
+            if self.wide {
+                if let Some(prev) = self.prev {
+                    // The pair (prev, b) is one UTF-16LE unit starting at pos - 1.
+                    let start = self.pos - 1;
+                    let run = &mut self.wide_runs[(start & 1) as usize];
+                    if is_printable(prev) && b == 0 {
+                        push(run, start, prev, self.min_len, "utf16le", out);
+                    } else {
+                        flush(run, self.min_len, "utf16le", out);
+                    }
+                }
+                self.prev = Some(b);
+            }
+            self.pos += 1;
+        }
+    }
+
+    pub fn finish(&mut self, out: &mut impl FnMut(Found)) {
+        flush(&mut self.ascii_run, self.min_len, "ascii", out);
+        for run in &mut self.wide_runs {
+            flush(run, self.min_len, "utf16le", out);
+        }
+    }
+}
+
+fn is_printable(b: u8) -> bool {
+    b == b'\t' || (0x20..0x7F).contains(&b)
+}
+
+fn push(run: &mut Run, at: u64, b: u8, min_len: usize, encoding: &'static str, out: &mut impl FnMut(Found)) {
+    if run.text.is_empty() {
+        run.start = at;
+    }
+    run.text.push(b as char);
+    if run.text.len() >= MAX_RUN {
+        flush(run, min_len, encoding, out);
+    }
+}
+
+fn flush(run: &mut Run, min_len: usize, encoding: &'static str, out: &mut impl FnMut(Found)) {
+    if run.text.len() >= min_len {
+        out(Found { offset: run.start, encoding, value: std::mem::take(&mut run.text) });
+    } else {
+        run.text.clear();
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, NaiveDateTime};
+
+const BLOCK: usize = 512;
+
+pub struct Entry {
+    pub name: String,
+    pub size: u64,
+    pub mode: u32,
+    pub modified: Option<NaiveDateTime>,
+    /// Header typeflag: b'0' file, b'1' hard link, b'2' symlink, b'5' directory...
+    pub kind: u8,
+    /// Target of a hard or symbolic link.
+    pub link: Option<String>,
+    /// Offset of the entry data in the tar bytes.
+    pub offset: usize,
+}
+
+impl Entry {
+    pub fn is_dir(&self) -> bool {
+        self.kind == b'5' || self.name.ends_with('/')
+    }
+
+    /// Regular files; links, devices and FIFOs have no content of their own.
+    pub fn is_file(&self) -> bool {
+        matches!(self.kind, 0 | b'0' | b'7') && !self.is_dir()
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            b'1' => "hard link",
+            b'2' => "symlink",
+            b'3' => "character device",
+            b'4' => "block device",
+            b'5' => "directory",
+            b'6' => "fifo",
+            _ => "file",
+        }
+    }
+}
+
+/// POSIX ustar/GNU magic, or a v7 header whose checksum adds up.
+pub fn is_tar(data: &[u8]) -> bool {
+    data.get(257..262) == Some(b"ustar") || data.get(..BLOCK).is_some_and(checksum_ok)
+}
+
+/// Entries in archive order, with GNU long names and pax path/linkpath/size records applied.
+/// The second value says why listing stopped early, if it did.
+pub fn entries(data: &[u8]) -> (Vec<Entry>, Option<String>) {
+    let mut out = vec![];
+    let mut pos = 0usize;
+    let mut long_name: Option<String> = None;
+    let mut long_link: Option<String> = None;
+    let mut pax: Vec<(String, String)> = vec![];
+
+    while let Some(header) = pos.checked_add(BLOCK).and_then(|end| data.get(pos..end)) {
+        if header.iter().all(|&b| b == 0) {
+            return (out, None);
+        }
+        if !checksum_ok(header) {
+            return (out, Some(format!("bad header checksum at offset {}", pos)));
+        }
+        let Some(mut size) = number(&header[124..136]) else {
+            return (out, Some(format!("bad size field at offset {}", pos)));
+        };
+        let kind = header[156];
+        // A pax size record replaces the header field for sizes it cannot hold.
+        if !matches!(kind, b'L' | b'K' | b'x' | b'g') {
+            if let Some(pax_size) = pax.iter().find(|(k, _)| k == "size").and_then(|(_, v)| v.parse().ok()) {
+                size = pax_size;
+            }
+        }
+        let start = pos + BLOCK;
+        let padded = usize::try_from(size).ok()
+            .and_then(|s| s.checked_add(BLOCK - 1))
+            .map(|s| s / BLOCK * BLOCK);
+        let Some(next) = padded.and_then(|p| start.checked_add(p)) else {
+            return (out, Some(format!("entry at offset {} declares an impossible size", pos)));
+        };
+        let content = data.get(start..).map(|d| &d[..d.len().min(size as usize)]).unwrap_or_default();
+
+        match kind {
+            b'L' => long_name = Some(text(content)),
+            b'K' => long_link = Some(text(content)),
+            b'x' => pax = pax_records(content),
+            // Global pax headers only carry defaults, nothing per-entry.
+            b'g' => {}
+            _ => {
+                let mut name = text(&header[..100]);
+                if &header[257..262] == b"ustar" {
+                    let prefix = text(&header[345..500]);
+                    if !prefix.is_empty() {
+                        name = format!("{}/{}", prefix, name);
+                    }
+                }
+                let mut link = Some(text(&header[157..257])).filter(|l| !l.is_empty());
+                if let Some(long) = long_name.take() {
+                    name = long;
+                }
+                if let Some(long) = long_link.take() {
+                    link = Some(long);
+                }
+                for (key, value) in pax.drain(..) {
+                    match key.as_str() {
+                        "path" => name = value,
+                        "linkpath" => link = Some(value),
+                        _ => {}
+                    }
+                }
+                out.push(Entry {
+                    name,
+                    size,
+                    mode: number(&header[100..108]).unwrap_or(0) as u32,
+                    modified: number(&header[136..148])
+                        .filter(|&t| t > 0)
+                        .and_then(|t| DateTime::from_timestamp(t as i64, 0))
+                        .map(|t| t.naive_utc()),
+                    kind,
+                    link,
+                    offset: start,
+                });
+            }
+        }
+        pos = next;
+    }
+    if pos < data.len() {
+        return (out, Some(format!("truncated header at offset {}", pos)));
+    }
+    (out, None)
+}
+
+/// Entry data, cut short when the archive is truncated.
+pub fn read<'a>(data: &'a [u8], entry: &Entry) -> &'a [u8] {
+    let end = entry.offset.saturating_add(entry.size.min(usize::MAX as u64) as usize).min(data.len());
+    data.get(entry.offset..end).unwrap_or_default()
+}
+
+fn checksum_ok(header: &[u8]) -> bool {
+    let Some(expected) = number(&header[148..156]) else { return false };
+    // The checksum field itself counts as eight spaces.
+    let sum: u64 = header.iter().enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum();
+    sum == expected
+}
+
+/// Octal field, or GNU base-256 when the high bit of the first byte is set.
+fn number(field: &[u8]) -> Option<u64> {
+    if field.first().is_some_and(|b| b & 0x80 != 0) {
+        // Negative values and anything past 64 bits are rejected.
+        let bytes = &field[1..];
+        if field[0] & 0x7F != 0 || bytes.iter().rev().skip(8).any(|&b| b != 0) {
+            return None;
+        }
+        return Some(bytes.iter().fold(0, |n, &b| n << 8 | b as u64));
+    }
+    let digits = std::str::from_utf8(field).ok()?.trim_matches(|c: char| c == '\0' || c == ' ');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+fn text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).into_owned()
+}
+
+/// "<length> <key>=<value>\n" records.
+fn pax_records(data: &[u8]) -> Vec<(String, String)> {
+    let mut out = vec![];
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|&b| b == b' ') {
+        let Some(len) = std::str::from_utf8(&rest[..space]).ok().and_then(|l| l.parse::<usize>().ok()) else { break };
+        let Some(record) = rest.get(space + 1..len) else { break };
+        let record = String::from_utf8_lossy(record.strip_suffix(b"\n").unwrap_or(record));
+        if let Some((key, value)) = record.split_once('=') {
+            out.push((key.to_string(), value.to_string()));
+        }
+        rest = &rest[len..];
+    }
+    out
+}
@@ -0,0 +1,557 @@
+use anyhow::{bail, Context, Result};
+use regex::bytes::{Regex, RegexBuilder};
+use std::collections::HashMap;
+use crate::helper::yara_parse::{Expr, Iter, MetaValue, Op, Pattern, Quant, Rule, RuleFile, StringDef};
+
+// YARA stops recording a string after this many hits as well.
+const MAX_MATCHES: usize = 1_000_000;
+const PREVIEW: usize = 48;
+
+pub struct CompiledRule {
+    pub rule: Rule,
+    /// One regex per string and encoding; the flag marks UTF-16LE variants for fullword checks.
+    patterns: Vec<Vec<(Regex, bool)>>,
+}
+
+/// The rules of one file; rule references and `global` rules stay within it.
+pub struct Namespace {
+    pub name: String,
+    pub rules: Vec<CompiledRule>,
+}
+
+pub struct Hit {
+    pub id: String,
+    pub offset: usize,
+    pub length: usize,
+    pub data: Vec<u8>,
+}
+
+pub struct RuleMatch {
+    pub namespace: String,
+    pub rule: String,
+    pub tags: Vec<String>,
+    pub meta: Vec<(String, String)>,
+    pub hits: Vec<Hit>,
+}
+
+/// Compiles every rule of a parsed file; rules using unsupported features are skipped with a warning.
+pub fn compile(name: &str, file: RuleFile) -> (Namespace, Vec<String>) {
+    let mut warnings = vec![];
+    let mut rules: Vec<CompiledRule> = vec![];
+    for rule in file.rules {
+        let known: Vec<&str> = rules.iter().map(|r| r.rule.name.as_str()).collect();
+        match compile_rule(&rule, &known) {
+            Ok(patterns) => rules.push(CompiledRule { rule, patterns }),
+            Err(e) => warnings.push(format!("{}:{}: skipping rule '{}': {:#}", name, rule.line, rule.name, e)),
+        }
+    }
+    (Namespace { name: name.to_string(), rules }, warnings)
+}
+
+fn compile_rule(rule: &Rule, known: &[&str]) -> Result<Vec<Vec<(Regex, bool)>>> {
+    if known.contains(&rule.name.as_str()) {
+        bail!("duplicate rule name");
+    }
+    let mut patterns = vec![];
+    for def in &rule.strings {
+        if def.id != "$" && rule.strings.iter().filter(|s| s.id == def.id).count() > 1 {
+            bail!("duplicate string identifier {}", def.id);
+        }
+        patterns.push(build_patterns(def).with_context(|| format!("string {}", def.id))?);
+    }
+    check(&rule.condition, &rule.strings, known, &mut vec![], false)?;
+    Ok(patterns)
+}
+
+fn build_patterns(def: &StringDef) -> Result<Vec<(Regex, bool)>> {
+    let m = &def.modifiers;
+    if let Some(name) = m.unsupported.first() {
+        bail!("the {} modifier is not supported", name);
+    }
+    let mut sources = vec![];
+    match &def.pattern {
+        Pattern::Text(text) => {
+            if text.is_empty() {
+                bail!("empty string");
+            }
+            let flags = if m.nocase { "(?si)" } else { "(?s)" };
+            if m.ascii || !m.wide {
+                sources.push((format!("{}{}", flags, escape(text, false)), false));
+            }
+            if m.wide {
+                sources.push((format!("{}{}", flags, escape(text, true)), true));
+            }
+        }
+        Pattern::Hex(body) => {
+            if m.nocase || m.wide || m.ascii || m.fullword {
+                bail!("hex strings only accept the private modifier");
+            }
+            sources.push((format!("(?s){}", hex_to_regex(body)?), false));
+        }
+        Pattern::Regex { source, nocase, dotall } => {
+            if m.wide {
+                bail!("wide regular expressions are not supported");
+            }
+            let mut flags = String::new();
+            if *nocase || m.nocase {
+                flags.push('i');
+            }
+            if *dotall {
+                flags.push('s');
+            }
+            let source = if flags.is_empty() { source.clone() } else { format!("(?{}){}", flags, source) };
+            sources.push((source, false));
+        }
+    }
+    sources.into_iter()
+        .map(|(source, wide)| {
+            let regex = RegexBuilder::new(&source)
+                .unicode(false)
+                .size_limit(64 << 20)
+                .build()
+                .with_context(|| format!("invalid pattern /{}/", source))?;
+            Ok((regex, wide))
+        })
+        .collect()
+}
+
+fn escape(text: &[u8], wide: bool) -> String {
+    let mut out = String::new();
+    for &b in text {
+        if b.is_ascii_alphanumeric() {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("\\x{:02X}", b));
+        }
+        if wide {
+            out.push_str("\\x00");
+        }
+    }
+    out
+}
+
+/// Translates a hex string body (`4D 5A ?? [2-4] (0A | 0D 0A) ~00 4?`) to a byte regex.
+fn hex_to_regex(body: &str) -> Result<String> {
+    let chars: Vec<char> = body.chars().filter(|c| !c.is_whitespace()).collect();
+    let mut out = String::new();
+    let mut i = 0;
+    let mut bytes = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '(' => {
+                out.push_str("(?:");
+                i += 1;
+            }
+            ')' | '|' => {
+                out.push(chars[i]);
+                i += 1;
+            }
+            '[' => {
+                let end = chars[i..].iter().position(|&c| c == ']').context("unterminated jump")? + i;
+                let jump: String = chars[i + 1..end].iter().collect();
+                let bounds = |s: &str| -> Result<String> {
+                    if s.is_empty() {
+                        return Ok(String::new());
+                    }
+                    s.parse::<u32>().map(|n| n.to_string()).map_err(|_| anyhow::anyhow!("invalid jump [{}]", jump))
+                };
+                out.push_str(&match jump.split_once('-') {
+                    None => format!(".{{{}}}", bounds(&jump)?),
+                    Some((lo, hi)) => {
+                        let lo = if lo.is_empty() { "0".to_string() } else { bounds(lo)? };
+                        format!(".{{{},{}}}?", lo, bounds(hi)?)
+                    }
+                });
+                i = end + 1;
+            }
+            c => {
+                let negate = c == '~';
+                let start = if negate { i + 1 } else { i };
+                let (Some(&hi), Some(&lo)) = (chars.get(start), chars.get(start + 1)) else {
+                    bail!("truncated byte in hex string");
+                };
+                out.push_str(&hex_byte(hi, lo, negate)?);
+                bytes += 1;
+                i = start + 2;
+            }
+        }
+    }
+    if bytes == 0 {
+        bail!("hex string has no bytes");
+    }
+    Ok(out)
+}
+
+fn hex_byte(hi: char, lo: char, negate: bool) -> Result<String> {
+    let nibble = |c: char| -> Result<Option<u8>> {
+        match c {
+            '?' => Ok(None),
+            c => c.to_digit(16).map(|d| Some(d as u8)).context(format!("invalid hex digit '{}'", c)),
+        }
+    };
+    let values: Vec<u8> = match (nibble(hi)?, nibble(lo)?) {
+        (None, None) if negate => bail!("~?? never matches"),
+        (None, None) => return Ok(".".to_string()),
+        (Some(h), Some(l)) => vec![h << 4 | l],
+        (Some(h), None) => (0..16).map(|l| h << 4 | l).collect(),
+        (None, Some(l)) => (0..16).map(|h| h << 4 | l).collect(),
+    };
+    let class: String = values.iter().map(|v| format!("\\x{:02X}", v)).collect();
+    Ok(match (values.len(), negate) {
+        (1, false) => class,
+        (_, false) => format!("[{}]", class),
+        (_, true) => format!("[^{}]", class),
+    })
+}
+
+/// Rejects unsupported features and unknown identifiers before anything is scanned.
+fn check(e: &Expr, strings: &[StringDef], rules: &[&str], vars: &mut Vec<String>, in_for_of: bool) -> Result<()> {
+    let string = |id: &Option<String>| -> Result<()> {
+        match id {
+            None if in_for_of => Ok(()),
+            None => bail!("anonymous string reference outside 'for ... of'"),
+            Some(name) if strings.iter().any(|s| s.id == format!("${}", name)) => Ok(()),
+            Some(name) => bail!("undefined string ${}", name),
+        }
+    };
+    let set = |set: &[String]| -> Result<()> {
+        for item in set {
+            if resolve_set(strings, std::slice::from_ref(item)).is_empty() {
+                bail!("no string matches {}", item);
+            }
+        }
+        Ok(())
+    };
+    let mut sub = |e: &Expr, vars: &mut Vec<String>| check(e, strings, rules, vars, in_for_of);
+
+    match e {
+        Expr::Bool(_) | Expr::Int(_) | Expr::Filesize => Ok(()),
+        Expr::Match { id, at, range } => {
+            string(id)?;
+            if let Some(at) = at {
+                sub(at, vars)?;
+            }
+            if let Some((lo, hi)) = range {
+                sub(lo, vars)?;
+                sub(hi, vars)?;
+            }
+            Ok(())
+        }
+        Expr::Count { id, range } => {
+            string(id)?;
+            if let Some((lo, hi)) = range {
+                sub(lo, vars)?;
+                sub(hi, vars)?;
+            }
+            Ok(())
+        }
+        Expr::Offset { id, index } | Expr::Length { id, index } => {
+            string(id)?;
+            sub(index, vars)
+        }
+        Expr::Read { addr, .. } => sub(addr, vars),
+        Expr::Not(inner) | Expr::Neg(inner) | Expr::BitNot(inner) => sub(inner, vars),
+        Expr::And(a, b) | Expr::Or(a, b) | Expr::Binary(_, a, b) => {
+            sub(a, vars)?;
+            sub(b, vars)
+        }
+        Expr::Of { quant, set: items } => {
+            check_quant(quant, &mut sub, vars)?;
+            set(items)
+        }
+        Expr::ForOf { quant, set: items, body } => {
+            check_quant(quant, &mut sub, vars)?;
+            set(items)?;
+            check(body, strings, rules, vars, true)
+        }
+        Expr::ForIn { quant, var, iter, body } => {
+            check_quant(quant, &mut sub, vars)?;
+            match iter {
+                Iter::Range(lo, hi) => {
+                    sub(lo, vars)?;
+                    sub(hi, vars)?;
+                }
+                Iter::List(items) => {
+                    for item in items {
+                        sub(item, vars)?;
+                    }
+                }
+            }
+            vars.push(var.clone());
+            let result = sub(body, vars);
+            vars.pop();
+            result
+        }
+        Expr::Ident(name) if vars.contains(name) || rules.contains(&name.as_str()) => Ok(()),
+        Expr::Ident(name) => bail!("unknown identifier '{}' (rules can only reference rules defined above them)", name),
+        Expr::Unsupported(what) => bail!("{} is not supported", what),
+    }
+}
+
+fn check_quant(quant: &Quant, sub: &mut impl FnMut(&Expr, &mut Vec<String>) -> Result<()>, vars: &mut Vec<String>) -> Result<()> {
+    match quant {
+        Quant::Count(n) | Quant::Percent(n) => sub(n, vars),
+        _ => Ok(()),
+    }
+}
+
+/// Indexes of the strings selected by `($a, $b*)`; `$*` (them) selects everything.
+fn resolve_set(strings: &[StringDef], set: &[String]) -> Vec<usize> {
+    strings.iter()
+        .enumerate()
+        .filter(|(_, s)| {
+            set.iter().any(|item| match item.strip_suffix('*') {
+                Some(prefix) => s.id.starts_with(prefix),
+                None => s.id == *item,
+            })
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Runs every namespace against `data` and returns the public rules that matched.
+pub fn scan(namespaces: &[Namespace], data: &[u8]) -> Vec<RuleMatch> {
+    let mut out = vec![];
+    for ns in namespaces {
+        let mut results: HashMap<&str, bool> = HashMap::new();
+        let mut matched = vec![];
+        let mut globals_ok = true;
+        for compiled in &ns.rules {
+            let rule = &compiled.rule;
+            let matches: Vec<Vec<(usize, usize)>> = compiled.patterns.iter()
+                .zip(&rule.strings)
+                .map(|(variants, def)| find_all(variants, def.modifiers.fullword, data))
+                .collect();
+            let mut scope = Scope { data, strings: &rule.strings, matches: &matches, rules: &results, vars: vec![], current: None };
+            let ok = truthy(scope.eval(&rule.condition));
+            results.insert(&rule.name, ok);
+            if rule.global && !ok {
+                globals_ok = false;
+            }
+            if ok && !rule.private {
+                matched.push((rule, matches));
+            }
+        }
+        if !globals_ok {
+            continue;
+        }
+        for (rule, matches) in matched {
+            let mut hits = vec![];
+            for (def, found) in rule.strings.iter().zip(matches) {
+                if def.modifiers.private {
+                    continue;
+                }
+                hits.extend(found.into_iter().map(|(offset, length)| Hit {
+                    id: def.id.clone(),
+                    offset,
+                    length,
+                    data: data[offset..offset + length.min(PREVIEW)].to_vec(),
+                }));
+            }
+            out.push(RuleMatch {
+                namespace: ns.name.clone(),
+                rule: rule.name.clone(),
+                tags: rule.tags.clone(),
+                meta: rule.meta.iter()
+                    .map(|(k, v)| (k.clone(), match v {
+                        MetaValue::Text(s) => s.clone(),
+                        other => other.to_string(),
+                    }))
+                    .collect(),
+                hits,
+            });
+        }
+    }
+    out
+}
+
+/// Every match start, overlapping ones included, as (offset, length).
+fn find_all(variants: &[(Regex, bool)], fullword: bool, data: &[u8]) -> Vec<(usize, usize)> {
+    let mut out = vec![];
+    for (regex, wide) in variants {
+        let mut pos = 0;
+        while pos < data.len() && out.len() < MAX_MATCHES {
+            let Some(m) = regex.find_at(data, pos) else { break };
+            if !m.is_empty() && (!fullword || is_fullword(data, m.start(), m.end(), *wide)) {
+                out.push((m.start(), m.len()));
+            }
+            pos = m.start() + 1;
+        }
+    }
+    if variants.len() > 1 {
+        out.sort();
+        out.dedup_by_key(|m| m.0);
+    }
+    out
+}
+
+/// `fullword` strings must not touch an alphanumeric character on either side.
+fn is_fullword(data: &[u8], start: usize, end: usize, wide: bool) -> bool {
+    let word = |i: usize| data.get(i).is_some_and(|b| b.is_ascii_alphanumeric());
+    if wide {
+        let before = start >= 2 && word(start - 2) && data[start - 1] == 0;
+        let after = word(end) && data.get(end + 1) == Some(&0);
+        !before && !after
+    } else {
+        let before = start > 0 && word(start - 1);
+        !before && !word(end)
+    }
+}
+
+fn truthy(value: Option<i64>) -> bool {
+    value.is_some_and(|v| v != 0)
+}
+
+struct Scope<'a> {
+    data: &'a [u8],
+    strings: &'a [StringDef],
+    matches: &'a [Vec<(usize, usize)>],
+    rules: &'a HashMap<&'a str, bool>,
+    vars: Vec<(String, i64)>,
+    /// The string bound to `$` inside `for ... of`.
+    current: Option<usize>,
+}
+
+impl Scope<'_> {
+    fn string(&self, id: &Option<String>) -> Option<usize> {
+        match id {
+            None => self.current,
+            Some(name) => self.strings.iter().position(|s| s.id.strip_prefix('$') == Some(name.as_str())),
+        }
+    }
+
+    fn hits(&self, id: &Option<String>) -> &[(usize, usize)] {
+        self.string(id).map(|i| self.matches[i].as_slice()).unwrap_or(&[])
+    }
+
+    fn range(&mut self, lo: &Expr, hi: &Expr) -> Option<(i64, i64)> {
+        Some((self.eval(lo)?, self.eval(hi)?))
+    }
+
+    /// Integers and booleans share i64; `None` is YARA's undefined (reads past the end, missing matches).
+    fn eval(&mut self, e: &Expr) -> Option<i64> {
+        let flag = |b: bool| Some(b as i64);
+        match e {
+            Expr::Bool(b) => flag(*b),
+            Expr::Int(n) => Some(*n),
+            Expr::Filesize => Some(self.data.len() as i64),
+            Expr::Match { id, at, range } => {
+                if let Some(at) = at {
+                    let at = self.eval(at)?;
+                    return flag(self.hits(id).iter().any(|m| m.0 as i64 == at));
+                }
+                if let Some((lo, hi)) = range {
+                    let (lo, hi) = self.range(lo, hi)?;
+                    return flag(self.hits(id).iter().any(|m| (lo..=hi).contains(&(m.0 as i64))));
+                }
+                flag(!self.hits(id).is_empty())
+            }
+            Expr::Count { id, range } => match range {
+                Some((lo, hi)) => {
+                    let (lo, hi) = self.range(lo, hi)?;
+                    Some(self.hits(id).iter().filter(|m| (lo..=hi).contains(&(m.0 as i64))).count() as i64)
+                }
+                None => Some(self.hits(id).len() as i64),
+            },
+            Expr::Offset { id, index } | Expr::Length { id, index } => {
+                let index = self.eval(index)?;
+                let hit = self.hits(id).get(usize::try_from(index).ok()?.checked_sub(1)?)?;
+                Some(if matches!(e, Expr::Offset { .. }) { hit.0 } else { hit.1 } as i64)
+            }
+            Expr::Read { width, signed, big_endian, addr } => {
+                let addr = usize::try_from(self.eval(addr)?).ok()?;
+                let bytes = self.data.get(addr..addr.checked_add(*width)?)?;
+                let mut value: u64 = 0;
+                for i in 0..*width {
+                    let b = if *big_endian { bytes[i] } else { bytes[width - 1 - i] };
+                    value = value << 8 | b as u64;
+                }
+                Some(match (*signed, width) {
+                    (true, 1) => value as u8 as i8 as i64,
+                    (true, 2) => value as u16 as i16 as i64,
+                    (true, _) => value as u32 as i32 as i64,
+                    (false, _) => value as i64,
+                })
+            }
+            Expr::Not(inner) => flag(self.eval(inner)? == 0),
+            Expr::And(a, b) => flag(truthy(self.eval(a)) && truthy(self.eval(b))),
+            Expr::Or(a, b) => flag(truthy(self.eval(a)) || truthy(self.eval(b))),
+            Expr::Neg(inner) => Some(self.eval(inner)?.wrapping_neg()),
+            Expr::BitNot(inner) => Some(!self.eval(inner)?),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (self.eval(a)?, self.eval(b)?);
+                match op {
+                    Op::Eq => flag(a == b),
+                    Op::Ne => flag(a != b),
+                    Op::Lt => flag(a < b),
+                    Op::Le => flag(a <= b),
+                    Op::Gt => flag(a > b),
+                    Op::Ge => flag(a >= b),
+                    Op::Add => Some(a.wrapping_add(b)),
+                    Op::Sub => Some(a.wrapping_sub(b)),
+                    Op::Mul => Some(a.wrapping_mul(b)),
+                    Op::Div => a.checked_div(b),
+                    Op::Mod => a.checked_rem(b),
+                    Op::BitAnd => Some(a & b),
+                    Op::BitOr => Some(a | b),
+                    Op::BitXor => Some(a ^ b),
+                    Op::Shl => Some(if (0..64).contains(&b) { a << b } else { 0 }),
+                    Op::Shr => Some(if (0..64).contains(&b) { a >> b } else { 0 }),
+                }
+            }
+            Expr::Of { quant, set } => {
+                let selected = resolve_set(self.strings, set);
+                let satisfied = selected.iter().filter(|&&i| !self.matches[i].is_empty()).count();
+                self.quantify(quant, satisfied, selected.len())
+            }
+            Expr::ForOf { quant, set, body } => {
+                let selected = resolve_set(self.strings, set);
+                let saved = self.current;
+                let mut satisfied = 0;
+                for &i in &selected {
+                    self.current = Some(i);
+                    if truthy(self.eval(body)) {
+                        satisfied += 1;
+                    }
+                }
+                self.current = saved;
+                self.quantify(quant, satisfied, selected.len())
+            }
+            Expr::ForIn { quant, var, iter, body } => {
+                let values: Vec<i64> = match iter {
+                    Iter::Range(lo, hi) => {
+                        let (lo, hi) = self.range(lo, hi)?;
+                        // Same cap as matches, `for all i in (0..filesize)` on a memory dump is not sensible.
+                        (lo..=hi).take(MAX_MATCHES).collect()
+                    }
+                    Iter::List(items) => items.iter().map(|item| self.eval(item)).collect::<Option<_>>()?,
+                };
+                let mut satisfied = 0;
+                for &value in &values {
+                    self.vars.push((var.clone(), value));
+                    if truthy(self.eval(body)) {
+                        satisfied += 1;
+                    }
+                    self.vars.pop();
+                }
+                self.quantify(quant, satisfied, values.len())
+            }
+            Expr::Ident(name) => match self.vars.iter().rev().find(|(v, _)| v == name) {
+                Some((_, value)) => Some(*value),
+                None => flag(self.rules.get(name.as_str()).copied().unwrap_or(false)),
+            },
+            Expr::Unsupported(_) => None,
+        }
+    }
+
+    fn quantify(&mut self, quant: &Quant, satisfied: usize, total: usize) -> Option<i64> {
+        let ok = match quant {
+            Quant::All => satisfied == total,
+            Quant::Any => satisfied > 0,
+            Quant::None => satisfied == 0,
+            Quant::Count(n) => satisfied as i64 >= self.eval(n)?,
+            Quant::Percent(p) => satisfied as i64 * 100 >= self.eval(p)? * total as i64,
+        };
+        Some(ok as i64)
+    }
+}
@@ -0,0 +1,887 @@
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// Keywords that may follow a string definition.
+const MODIFIERS: &[&str] = &["nocase", "wide", "ascii", "fullword", "private", "xor", "base64", "base64wide"];
+/// Operators YARA only defines for module strings.
+const STRING_OPERATORS: &[&str] = &[
+    "contains", "icontains", "startswith", "istartswith", "endswith", "iendswith", "iequals", "matches",
+];
+
+#[derive(Debug, Default)]
+pub struct RuleFile {
+    pub imports: Vec<String>,
+    pub rules: Vec<Rule>,
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    pub line: usize,
+    pub private: bool,
+    pub global: bool,
+    pub tags: Vec<String>,
+    pub meta: Vec<(String, MetaValue)>,
+    pub strings: Vec<StringDef>,
+    pub condition: Expr,
+}
+
+#[derive(Debug)]
+pub enum MetaValue {
+    Text(String),
+    Int(i64),
+    Bool(bool),
+}
+
+impl fmt::Display for MetaValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetaValue::Text(s) => write!(f, "{}", s),
+            MetaValue::Int(n) => write!(f, "{}", n),
+            MetaValue::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct StringDef {
+    /// `$name`, or just `$` for anonymous strings.
+    pub id: String,
+    pub pattern: Pattern,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Debug)]
+pub enum Pattern {
+    Text(Vec<u8>),
+    /// Body of a `{ ... }` hex string, translated when the rule is compiled.
+    Hex(String),
+    Regex { source: String, nocase: bool, dotall: bool },
+}
+
+#[derive(Debug, Default)]
+pub struct Modifiers {
+    pub nocase: bool,
+    pub ascii: bool,
+    pub wide: bool,
+    pub fullword: bool,
+    pub private: bool,
+    /// Modifiers that are parsed but not implemented (xor, base64).
+    pub unsupported: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Expr {
+    Bool(bool),
+    Int(i64),
+    Filesize,
+    /// `$a`, `$a at <expr>`, `$a in (<lo>..<hi>)`; `id` is `None` for `$` inside `for ... of`.
+    Match { id: Option<String>, at: Option<Box<Expr>>, range: Option<(Box<Expr>, Box<Expr>)> },
+    Count { id: Option<String>, range: Option<(Box<Expr>, Box<Expr>)> },
+    /// `@a[i]`, 1-based like YARA.
+    Offset { id: Option<String>, index: Box<Expr> },
+    Length { id: Option<String>, index: Box<Expr> },
+    /// `uint16(0)`, `int32be(@a)` and friends.
+    Read { width: usize, signed: bool, big_endian: bool, addr: Box<Expr> },
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    BitNot(Box<Expr>),
+    /// `<quant> of <set>`; `them` is stored as the set `$*`.
+    Of { quant: Quant, set: Vec<String> },
+    ForOf { quant: Quant, set: Vec<String>, body: Box<Expr> },
+    ForIn { quant: Quant, var: String, iter: Iter, body: Box<Expr> },
+    /// A loop variable or a reference to an earlier rule.
+    Ident(String),
+    /// Parsed so the rest of the file still loads, rejected when the rule is compiled.
+    Unsupported(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+#[derive(Debug)]
+pub enum Quant {
+    All,
+    Any,
+    None,
+    Count(Box<Expr>),
+    Percent(Box<Expr>),
+}
+
+#[derive(Debug)]
+pub enum Iter {
+    Range(Box<Expr>, Box<Expr>),
+    List(Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Ident(String),
+    /// `$name`, `$` (None) or `$name*` (wildcard set item).
+    Str(Option<String>, bool),
+    Count(Option<String>),
+    Offset(Option<String>),
+    Length(Option<String>),
+    Text(Vec<u8>),
+    Hex(String),
+    Regex(String, String),
+    Int(i64),
+    Sym(&'static str),
+    Eof,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tok::Ident(s) => write!(f, "'{}'", s),
+            Tok::Str(id, wild) => write!(f, "'${}{}'", id.as_deref().unwrap_or(""), if *wild { "*" } else { "" }),
+            Tok::Count(id) => write!(f, "'#{}'", id.as_deref().unwrap_or("")),
+            Tok::Offset(id) => write!(f, "'@{}'", id.as_deref().unwrap_or("")),
+            Tok::Length(id) => write!(f, "'!{}'", id.as_deref().unwrap_or("")),
+            Tok::Text(_) => write!(f, "text string"),
+            Tok::Hex(_) => write!(f, "hex string"),
+            Tok::Regex(..) => write!(f, "regular expression"),
+            Tok::Int(n) => write!(f, "'{}'", n),
+            Tok::Sym(s) => write!(f, "'{}'", s),
+            Tok::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &[
+    "..", "==", "!=", "<=", ">=", "<<", ">>", "<", ">", "(", ")", "[", "]", "{", "}", ":", ",", "=", "+", "-", "*",
+    "\\", "%", "&", "|", "^", "~", ".",
+];
+
+fn tokenize(src: &str) -> Result<Vec<(Tok, usize)>> {
+    let s = src.as_bytes();
+    let mut out = vec![];
+    let mut i = 0;
+    let mut line = 1;
+
+    while i < s.len() {
+        let c = s[i];
+        if c == b'\n' {
+            line += 1;
+            i += 1;
+            continue;
+        }
+        if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        }
+        if s[i..].starts_with(b"//") {
+            while i < s.len() && s[i] != b'\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if s[i..].starts_with(b"/*") {
+            let Some(end) = find(&s[i + 2..], b"*/") else { bail!("line {}: unterminated comment", line) };
+            line += s[i..i + 2 + end].iter().filter(|&&b| b == b'\n').count();
+            i += end + 4;
+            continue;
+        }
+
+        let start_line = line;
+        if c == b'"' {
+            let (text, used) = lex_text(&s[i + 1..]).map_err(|e| anyhow::anyhow!("line {}: {}", line, e))?;
+            out.push((Tok::Text(text), start_line));
+            i += used + 1;
+        } else if c == b'/' {
+            let (pattern, used) = lex_regex(&s[i + 1..]).map_err(|e| anyhow::anyhow!("line {}: {}", line, e))?;
+            i += used + 1;
+            let flags_start = i;
+            while i < s.len() && s[i].is_ascii_alphabetic() {
+                i += 1;
+            }
+            let flags = String::from_utf8_lossy(&s[flags_start..i]).to_string();
+            out.push((Tok::Regex(pattern, flags), start_line));
+        } else if matches!(c, b'$' | b'#' | b'@') || (c == b'!' && s.get(i + 1) != Some(&b'=')) {
+            i += 1;
+            let name_start = i;
+            while i < s.len() && (s[i].is_ascii_alphanumeric() || s[i] == b'_') {
+                i += 1;
+            }
+            let name = String::from_utf8_lossy(&s[name_start..i]).to_string();
+            let id = (!name.is_empty()).then_some(name);
+            let tok = match c {
+                b'$' => {
+                    let wild = s.get(i) == Some(&b'*');
+                    if wild {
+                        i += 1;
+                    }
+                    Tok::Str(id, wild)
+                }
+                b'#' => Tok::Count(id),
+                b'@' => Tok::Offset(id),
+                _ => Tok::Length(id),
+            };
+            out.push((tok, start_line));
+        } else if c.is_ascii_digit() {
+            let (value, used) = lex_number(&s[i..]).map_err(|e| anyhow::anyhow!("line {}: {}", line, e))?;
+            out.push((Tok::Int(value), start_line));
+            i += used;
+        } else if c.is_ascii_alphabetic() || c == b'_' {
+            let start = i;
+            while i < s.len() && (s[i].is_ascii_alphanumeric() || s[i] == b'_') {
+                i += 1;
+            }
+            out.push((Tok::Ident(String::from_utf8_lossy(&s[start..i]).to_string()), start_line));
+        } else if let Some(sym) = SYMBOLS.iter().find(|sym| s[i..].starts_with(sym.as_bytes())) {
+            i += sym.len();
+            out.push((Tok::Sym(sym), start_line));
+            // A `{` right after `=` opens a hex string, not a block.
+            if *sym == "=" {
+                let mut j = i;
+                while j < s.len() && s[j].is_ascii_whitespace() {
+                    j += 1;
+                }
+                if s.get(j) == Some(&b'{') {
+                    let Some(end) = s[j..].iter().position(|&b| b == b'}') else {
+                        bail!("line {}: unterminated hex string", line)
+                    };
+                    let body = &s[j + 1..j + end];
+                    out.push((Tok::Hex(String::from_utf8_lossy(body).to_string()), line));
+                    line += s[i..j + end].iter().filter(|&&b| b == b'\n').count();
+                    i = j + end + 1;
+                }
+            }
+        } else {
+            bail!("line {}: unexpected character '{}'", line, c as char);
+        }
+    }
+    out.push((Tok::Eof, line));
+    Ok(out)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+/// Text string body after the opening quote; returns the bytes and the input consumed.
+fn lex_text(s: &[u8]) -> Result<(Vec<u8>, usize)> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'"' => return Ok((out, i + 1)),
+            b'\n' => break,
+            b'\\' => {
+                let Some(&esc) = s.get(i + 1) else { break };
+                match esc {
+                    b'"' | b'\\' => out.push(esc),
+                    b't' => out.push(b'\t'),
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b'x' => {
+                        let hex = s.get(i + 2..i + 4).and_then(|h| std::str::from_utf8(h).ok());
+                        let Some(byte) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) else {
+                            bail!("invalid \\x escape in text string")
+                        };
+                        out.push(byte);
+                        i += 2;
+                    }
+                    other => bail!("unknown escape '\\{}' in text string", other as char),
+                }
+                i += 2;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    bail!("unterminated text string")
+}
+
+/// Regex body after the opening slash; escapes are kept for the regex engine except `\/`.
+fn lex_regex(s: &[u8]) -> Result<(String, usize)> {
+    let mut out = vec![];
+    let mut i = 0;
+    while i < s.len() {
+        match s[i] {
+            b'/' => return Ok((String::from_utf8_lossy(&out).to_string(), i + 1)),
+            b'\n' => break,
+            b'\\' if s.get(i + 1) == Some(&b'/') => {
+                out.push(b'/');
+                i += 2;
+            }
+            b'\\' if i + 1 < s.len() => {
+                out.extend_from_slice(&s[i..i + 2]);
+                i += 2;
+            }
+            b => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    bail!("unterminated regular expression")
+}
+
+fn lex_number(s: &[u8]) -> Result<(i64, usize)> {
+    let (digits, radix, skip) = if s.starts_with(b"0x") || s.starts_with(b"0X") {
+        (s[2..].iter().take_while(|b| b.is_ascii_hexdigit()).count(), 16, 2)
+    } else if s.starts_with(b"0o") {
+        (s[2..].iter().take_while(|b| (b'0'..=b'7').contains(b)).count(), 8, 2)
+    } else {
+        (s.iter().take_while(|b| b.is_ascii_digit()).count(), 10, 0)
+    };
+    let text = std::str::from_utf8(&s[skip..skip + digits])?;
+    let mut value = i64::from_str_radix(text, radix).map_err(|_| anyhow::anyhow!("invalid number '{}'", text))?;
+    let mut used = skip + digits;
+    if radix == 10 {
+        if s[used..].starts_with(b"KB") {
+            value *= 1024;
+            used += 2;
+        } else if s[used..].starts_with(b"MB") {
+            value *= 1024 * 1024;
+            used += 2;
+        }
+    }
+    Ok((value, used))
+}
+
+pub fn parse(src: &str) -> Result<RuleFile> {
+    let mut parser = Parser { toks: tokenize(src)?, pos: 0 };
+    let mut file = RuleFile::default();
+    loop {
+        match parser.next() {
+            Tok::Eof => break,
+            Tok::Ident(kw) if kw == "import" => match parser.next() {
+                Tok::Text(name) => file.imports.push(String::from_utf8_lossy(&name).to_string()),
+                other => bail!("line {}: expected module name after import, found {}", parser.line(), other),
+            },
+            Tok::Ident(kw) if kw == "include" => {
+                bail!("line {}: include is not supported, put every rule file in the rules directory", parser.line())
+            }
+            Tok::Ident(kw) if matches!(kw.as_str(), "private" | "global" | "rule") => {
+                let mut private = kw == "private";
+                let mut global = kw == "global";
+                let mut kw = kw;
+                while kw != "rule" {
+                    match parser.next() {
+                        Tok::Ident(next) if next == "private" => private = true,
+                        Tok::Ident(next) if next == "global" => global = true,
+                        Tok::Ident(next) if next == "rule" => kw = next,
+                        other => bail!("line {}: expected 'rule', found {}", parser.line(), other),
+                    }
+                }
+                let mut rule = parser.rule()?;
+                rule.private = private;
+                rule.global = global;
+                file.rules.push(rule);
+            }
+            other => bail!("line {}: expected a rule, found {}", parser.line(), other),
+        }
+    }
+    Ok(file)
+}
+
+struct Parser {
+    toks: Vec<(Tok, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.toks[self.pos].0
+    }
+
+    fn peek_at(&self, ahead: usize) -> &Tok {
+        &self.toks[(self.pos + ahead).min(self.toks.len() - 1)].0
+    }
+
+    fn line(&self) -> usize {
+        self.toks[self.pos.saturating_sub(1)].1
+    }
+
+    fn next(&mut self) -> Tok {
+        let tok = self.toks[self.pos].0.clone();
+        if self.pos < self.toks.len() - 1 {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn eat_sym(&mut self, sym: &'static str) -> bool {
+        if *self.peek() == Tok::Sym(sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_kw(&mut self, kw: &str) -> bool {
+        if matches!(self.peek(), Tok::Ident(s) if s == kw) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_sym(&mut self, sym: &'static str) -> Result<()> {
+        if !self.eat_sym(sym) {
+            bail!("line {}: expected '{}', found {}", self.toks[self.pos].1, sym, self.peek());
+        }
+        Ok(())
+    }
+
+    fn expect_kw(&mut self, kw: &str) -> Result<()> {
+        if !self.eat_kw(kw) {
+            bail!("line {}: expected '{}', found {}", self.toks[self.pos].1, kw, self.peek());
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.next() {
+            Tok::Ident(name) => Ok(name),
+            other => bail!("line {}: expected an identifier, found {}", self.line(), other),
+        }
+    }
+
+    fn is_section(&self, name: &str) -> bool {
+        matches!(self.peek(), Tok::Ident(s) if s == name) && *self.peek_at(1) == Tok::Sym(":")
+    }
+
+    fn rule(&mut self) -> Result<Rule> {
+        let name = self.ident()?;
+        let line = self.line();
+        let mut tags = vec![];
+        if self.eat_sym(":") {
+            while let Tok::Ident(tag) = self.peek().clone() {
+                self.pos += 1;
+                tags.push(tag);
+            }
+        }
+        self.expect_sym("{")?;
+
+        let mut meta = vec![];
+        if self.is_section("meta") {
+            self.pos += 2;
+            while !self.is_section("strings") && !self.is_section("condition") {
+                let key = self.ident()?;
+                self.expect_sym("=")?;
+                let value = match self.next() {
+                    Tok::Text(text) => MetaValue::Text(String::from_utf8_lossy(&text).to_string()),
+                    Tok::Int(n) => MetaValue::Int(n),
+                    Tok::Sym("-") => match self.next() {
+                        Tok::Int(n) => MetaValue::Int(-n),
+                        other => bail!("line {}: invalid meta value {}", self.line(), other),
+                    },
+                    Tok::Ident(b) if b == "true" || b == "false" => MetaValue::Bool(b == "true"),
+                    other => bail!("line {}: invalid meta value {}", self.line(), other),
+                };
+                meta.push((key, value));
+            }
+        }
+
+        let mut strings = vec![];
+        if self.is_section("strings") {
+            self.pos += 2;
+            while let Tok::Str(id, false) = self.peek().clone() {
+                self.pos += 1;
+                self.expect_sym("=")?;
+                let pattern = match self.next() {
+                    Tok::Text(text) => Pattern::Text(text),
+                    Tok::Hex(body) => Pattern::Hex(body),
+                    Tok::Regex(source, flags) => Pattern::Regex {
+                        nocase: flags.contains('i'),
+                        dotall: flags.contains('s'),
+                        source,
+                    },
+                    other => bail!("line {}: expected a string value, found {}", self.line(), other),
+                };
+                let modifiers = self.modifiers()?;
+                strings.push(StringDef { id: format!("${}", id.unwrap_or_default()), pattern, modifiers });
+            }
+        }
+
+        if !self.is_section("condition") {
+            bail!("line {}: rule '{}' has no condition", self.toks[self.pos].1, name);
+        }
+        self.pos += 2;
+        let condition = self.expr()?;
+        self.expect_sym("}")?;
+
+        Ok(Rule { name, line, private: false, global: false, tags, meta, strings, condition })
+    }
+
+    fn modifiers(&mut self) -> Result<Modifiers> {
+        let mut m = Modifiers::default();
+        while let Tok::Ident(name) = self.peek().clone() {
+            if !MODIFIERS.contains(&name.as_str()) {
+                break;
+            }
+            self.pos += 1;
+            match name.as_str() {
+                "nocase" => m.nocase = true,
+                "ascii" => m.ascii = true,
+                "wide" => m.wide = true,
+                "fullword" => m.fullword = true,
+                "private" => m.private = true,
+                _ => {
+                    // xor(0x01-0xff), base64("alphabet")
+                    if *self.peek() == Tok::Sym("(") {
+                        self.skip_group()?;
+                    }
+                    m.unsupported.push(name);
+                }
+            }
+        }
+        Ok(m)
+    }
+
+    /// Skips a balanced `(...)` or `[...]` group.
+    fn skip_group(&mut self) -> Result<()> {
+        let mut depth = 0;
+        loop {
+            match self.next() {
+                Tok::Sym("(") | Tok::Sym("[") => depth += 1,
+                Tok::Sym(")") | Tok::Sym("]") => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(());
+                    }
+                }
+                Tok::Eof => bail!("line {}: unbalanced brackets", self.line()),
+                _ => {}
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut left = self.and_expr()?;
+        while self.eat_kw("or") {
+            left = Expr::Or(Box::new(left), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr> {
+        let mut left = self.not_expr()?;
+        while self.eat_kw("and") {
+            left = Expr::And(Box::new(left), Box::new(self.not_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr> {
+        if self.eat_kw("not") {
+            return Ok(Expr::Not(Box::new(self.not_expr()?)));
+        }
+        if self.eat_kw("defined") {
+            self.not_expr()?;
+            return Ok(Expr::Unsupported("defined".to_string()));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr> {
+        let left = self.binary(0)?;
+        let op = match self.peek() {
+            Tok::Sym("==") => Op::Eq,
+            Tok::Sym("!=") => Op::Ne,
+            Tok::Sym("<") => Op::Lt,
+            Tok::Sym("<=") => Op::Le,
+            Tok::Sym(">") => Op::Gt,
+            Tok::Sym(">=") => Op::Ge,
+            Tok::Ident(name) if STRING_OPERATORS.contains(&name.as_str()) => {
+                let name = name.clone();
+                self.pos += 1;
+                self.binary(0)?;
+                return Ok(Expr::Unsupported(format!("'{}' operator", name)));
+            }
+            _ => return Ok(left),
+        };
+        self.pos += 1;
+        let right = self.binary(0)?;
+        Ok(Expr::Binary(op, Box::new(left), Box::new(right)))
+    }
+
+    /// Arithmetic and bitwise operators, lowest precedence level first.
+    fn binary(&mut self, level: usize) -> Result<Expr> {
+        const LEVELS: &[&[(&str, Op)]] = &[
+            &[("|", Op::BitOr)],
+            &[("^", Op::BitXor)],
+            &[("&", Op::BitAnd)],
+            &[("<<", Op::Shl), (">>", Op::Shr)],
+            &[("+", Op::Add), ("-", Op::Sub)],
+            &[("*", Op::Mul), ("\\", Op::Div), ("%", Op::Mod)],
+        ];
+        if level == LEVELS.len() {
+            return self.unary();
+        }
+        let mut left = self.binary(level + 1)?;
+        'outer: loop {
+            for (sym, op) in LEVELS[level] {
+                if self.eat_sym(sym) {
+                    left = Expr::Binary(*op, Box::new(left), Box::new(self.binary(level + 1)?));
+                    continue 'outer;
+                }
+            }
+            return Ok(left);
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat_sym("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat_sym("~") {
+            return Ok(Expr::BitNot(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next() {
+            Tok::Sym("(") => {
+                let inner = self.expr()?;
+                self.expect_sym(")")?;
+                Ok(inner)
+            }
+            Tok::Int(n) => {
+                if self.eat_sym("%") {
+                    if self.eat_kw("of") {
+                        let set = self.string_set()?;
+                        return Ok(Expr::Of { quant: Quant::Percent(Box::new(Expr::Int(n))), set });
+                    }
+                    // Not a percentage after all, this was a modulo.
+                    self.pos -= 1;
+                } else if self.eat_kw("of") {
+                    let set = self.string_set()?;
+                    return Ok(Expr::Of { quant: Quant::Count(Box::new(Expr::Int(n))), set });
+                }
+                Ok(Expr::Int(n))
+            }
+            Tok::Text(_) => Ok(Expr::Unsupported("string literal in condition".to_string())),
+            Tok::Regex(..) => Ok(Expr::Unsupported("regular expression in condition".to_string())),
+            Tok::Str(id, false) => {
+                if self.eat_kw("at") {
+                    let at = self.binary(0)?;
+                    return Ok(Expr::Match { id, at: Some(Box::new(at)), range: None });
+                }
+                if self.eat_kw("in") {
+                    let range = self.range()?;
+                    return Ok(Expr::Match { id, at: None, range: Some(range) });
+                }
+                Ok(Expr::Match { id, at: None, range: None })
+            }
+            Tok::Count(id) => {
+                let range = if self.eat_kw("in") { Some(self.range()?) } else { None };
+                Ok(Expr::Count { id, range })
+            }
+            Tok::Offset(id) => Ok(Expr::Offset { id, index: Box::new(self.index()?) }),
+            Tok::Length(id) => Ok(Expr::Length { id, index: Box::new(self.index()?) }),
+            Tok::Ident(name) => self.ident_expr(name),
+            other => bail!("line {}: unexpected {} in condition", self.line(), other),
+        }
+    }
+
+    fn ident_expr(&mut self, name: String) -> Result<Expr> {
+        match name.as_str() {
+            "true" => return Ok(Expr::Bool(true)),
+            "false" => return Ok(Expr::Bool(false)),
+            "filesize" => return Ok(Expr::Filesize),
+            "entrypoint" => return Ok(Expr::Unsupported("entrypoint".to_string())),
+            "all" | "any" | "none" => {
+                let quant = match name.as_str() {
+                    "all" => Quant::All,
+                    "any" => Quant::Any,
+                    _ => Quant::None,
+                };
+                self.expect_kw("of")?;
+                let set = self.string_set()?;
+                if self.eat_kw("in") || self.eat_kw("at") {
+                    return Ok(Expr::Unsupported("'of' with 'in' or 'at'".to_string()));
+                }
+                return Ok(Expr::Of { quant, set });
+            }
+            "for" => return self.for_expr(),
+            _ => {}
+        }
+
+        if let Some(read) = parse_read(&name) {
+            self.expect_sym("(")?;
+            let addr = self.expr()?;
+            self.expect_sym(")")?;
+            let (width, signed, big_endian) = read;
+            return Ok(Expr::Read { width, signed, big_endian, addr: Box::new(addr) });
+        }
+
+        // Module fields and functions (pe.imports(...), math.entropy(...)): keep the path for the error.
+        let mut path = name;
+        let mut module = false;
+        loop {
+            match self.peek() {
+                Tok::Sym(".") => {
+                    self.pos += 1;
+                    path.push('.');
+                    path.push_str(&self.ident()?);
+                    module = true;
+                }
+                Tok::Sym("(") | Tok::Sym("[") => {
+                    self.skip_group()?;
+                    module = true;
+                }
+                _ => break,
+            }
+        }
+        if module {
+            let name = path.split('.').next().unwrap_or_default();
+            return Ok(Expr::Unsupported(format!("module '{}' ({})", name, path)));
+        }
+        Ok(Expr::Ident(path))
+    }
+
+    fn for_expr(&mut self) -> Result<Expr> {
+        let quant = match self.peek().clone() {
+            Tok::Ident(q) if q == "all" || q == "any" || q == "none" => {
+                self.pos += 1;
+                match q.as_str() {
+                    "all" => Quant::All,
+                    "any" => Quant::Any,
+                    _ => Quant::None,
+                }
+            }
+            // `for 2 of ($a*)`: take the number before `primary` reads it as `2 of ...`.
+            Tok::Int(n) if matches!(self.peek_at(1), Tok::Ident(kw) if kw == "of") => {
+                self.pos += 1;
+                Quant::Count(Box::new(Expr::Int(n)))
+            }
+            _ => Quant::Count(Box::new(self.binary(0)?)),
+        };
+
+        if self.eat_kw("of") {
+            let set = self.string_set()?;
+            self.expect_sym(":")?;
+            self.expect_sym("(")?;
+            let body = self.expr()?;
+            self.expect_sym(")")?;
+            return Ok(Expr::ForOf { quant, set, body: Box::new(body) });
+        }
+
+        let var = self.ident()?;
+        if *self.peek() == Tok::Sym(",") {
+            bail!("line {}: for loops over several variables are not supported", self.toks[self.pos].1);
+        }
+        self.expect_kw("in")?;
+        let iter = match self.peek() {
+            Tok::Sym("(") => {
+                self.pos += 1;
+                let first = self.expr()?;
+                if self.eat_sym("..") {
+                    let last = self.expr()?;
+                    self.expect_sym(")")?;
+                    Iter::Range(Box::new(first), Box::new(last))
+                } else {
+                    let mut items = vec![first];
+                    while self.eat_sym(",") {
+                        items.push(self.expr()?);
+                    }
+                    self.expect_sym(")")?;
+                    Iter::List(items)
+                }
+            }
+            // Iterating module arrays (pe.sections) is not supported.
+            _ => {
+                let what = self.primary()?;
+                self.expect_sym(":")?;
+                self.skip_group()?;
+                return Ok(match what {
+                    Expr::Unsupported(reason) => Expr::Unsupported(reason),
+                    _ => Expr::Unsupported("for loops over identifiers".to_string()),
+                });
+            }
+        };
+        self.expect_sym(":")?;
+        self.expect_sym("(")?;
+        let body = self.expr()?;
+        self.expect_sym(")")?;
+        Ok(Expr::ForIn { quant, var, iter, body: Box::new(body) })
+    }
+
+    /// `them` or `($a, $b*, ...)`; `them` becomes `$*`.
+    fn string_set(&mut self) -> Result<Vec<String>> {
+        if self.eat_kw("them") {
+            return Ok(vec!["$*".to_string()]);
+        }
+        self.expect_sym("(")?;
+        let mut set = vec![];
+        loop {
+            match self.next() {
+                Tok::Str(id, wild) => set.push(format!("${}{}", id.unwrap_or_default(), if wild { "*" } else { "" })),
+                Tok::Ident(rule) => bail!("line {}: rule sets like ({}, ...) are not supported", self.line(), rule),
+                other => bail!("line {}: expected a string identifier, found {}", self.line(), other),
+            }
+            if !self.eat_sym(",") {
+                break;
+            }
+        }
+        self.expect_sym(")")?;
+        Ok(set)
+    }
+
+    fn range(&mut self) -> Result<(Box<Expr>, Box<Expr>)> {
+        self.expect_sym("(")?;
+        let lo = self.binary(0)?;
+        self.expect_sym("..")?;
+        let hi = self.binary(0)?;
+        self.expect_sym(")")?;
+        Ok((Box::new(lo), Box::new(hi)))
+    }
+
+    /// Optional `[i]` after `@a` / `!a`, defaulting to the first match.
+    fn index(&mut self) -> Result<Expr> {
+        if self.eat_sym("[") {
+            let index = self.expr()?;
+            self.expect_sym("]")?;
+            return Ok(index);
+        }
+        Ok(Expr::Int(1))
+    }
+}
+
+/// `uint16be` -> (2, unsigned, big endian).
+fn parse_read(name: &str) -> Option<(usize, bool, bool)> {
+    let (signed, rest) = match name.strip_prefix("uint") {
+        Some(rest) => (false, rest),
+        None => (true, name.strip_prefix("int")?),
+    };
+    let (bits, big_endian) = match rest.strip_suffix("be") {
+        Some(bits) => (bits, true),
+        None => (rest, false),
+    };
+    let width = match bits {
+        "8" => 1,
+        "16" => 2,
+        "32" => 4,
+        _ => return None,
+    };
+    Some((width, signed, big_endian))
+}
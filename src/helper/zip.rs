@@ -0,0 +1,340 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDateTime;
+use flate2::read::DeflateDecoder;
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::symm::{Cipher, Crypter, Mode};
+use std::io::Read;
+
+// Refuse to inflate single entries past this size; bombs are reported, not expanded.
+const MAX_INFLATE: u64 = 512 * 1024 * 1024;
+
+pub struct Entry {
+    pub name: String,
+    /// 0 = stored, 8 = deflate, 14 = LZMA, 99 = AES...
+    pub method: u16,
+    pub flags: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub size: u64,
+    pub modified: Option<NaiveDateTime>,
+    pub local_header_offset: u64,
+    /// Set when the entry declares AES (WinZip) encryption in its extra field.
+    pub aes: Option<Aes>,
+}
+
+/// WinZip AES extra field (0x9901).
+#[derive(Debug, Clone, Copy)]
+pub struct Aes {
+    /// 1 = AE-1 (CRC kept), 2 = AE-2 (CRC zeroed).
+    pub version: u16,
+    /// 1, 2, 3 for 128, 192, 256-bit keys.
+    pub strength: u8,
+    /// The real compression method, hidden behind method 99.
+    pub method: u16,
+}
+
+impl Aes {
+    pub fn key_bits(&self) -> usize {
+        64 + 64 * self.strength as usize
+    }
+}
+
+impl Entry {
+    pub fn is_encrypted(&self) -> bool {
+        self.flags & 1 != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+}
+
+/// Entries from the central directory, in archive order.
+pub fn entries(data: &[u8]) -> Result<Vec<Entry>> {
+    let eocd = (0..data.len().saturating_sub(21)).rev()
+        .take(65_557)
+        .find(|&i| data[i..].starts_with(b"PK\x05\x06"))
+        .context("End of central directory not found (not a zip file or truncated)")?;
+
+    let mut count = u16_at(data, eocd + 10)? as u64;
+    let mut cd_offset = u32_at(data, eocd + 16)? as u64;
+
+    // Zip64 archives keep the real values in a separate record.
+    if (count == 0xFFFF || cd_offset == 0xFFFF_FFFF) && eocd >= 20 && data[eocd - 20..].starts_with(b"PK\x06\x07") {
+        let record = u64_at(data, eocd - 12)? as usize;
+        if data.get(record..).is_some_and(|r| r.starts_with(b"PK\x06\x06")) {
+            count = u64_at(data, record + 32)?;
+            cd_offset = u64_at(data, record + 48)?;
+        }
+    }
+
+    let mut out = vec![];
+    let mut pos = cd_offset as usize;
+    for _ in 0..count {
+        if !data.get(pos..).is_some_and(|d| d.starts_with(b"PK\x01\x02")) {
+            bail!("Corrupt central directory entry at offset {}", pos);
+        }
+        let flags = u16_at(data, pos + 8)?;
+        let method = u16_at(data, pos + 10)?;
+        let time = u16_at(data, pos + 12)?;
+        let date = u16_at(data, pos + 14)?;
+        let crc32 = u32_at(data, pos + 16)?;
+        let mut compressed_size = u32_at(data, pos + 20)? as u64;
+        let mut size = u32_at(data, pos + 24)? as u64;
+        let name_len = u16_at(data, pos + 28)? as usize;
+        let extra_len = u16_at(data, pos + 30)? as usize;
+        let comment_len = u16_at(data, pos + 32)? as usize;
+        let mut local_header_offset = u32_at(data, pos + 42)? as u64;
+
+        let name_bytes = data.get(pos + 46..pos + 46 + name_len).context("Truncated entry name")?;
+        let name = if flags & 0x800 != 0 {
+            String::from_utf8_lossy(name_bytes).into_owned()
+        } else {
+            name_bytes.iter().map(|&b| b as char).collect()
+        };
+
+        let extra = data.get(pos + 46 + name_len..pos + 46 + name_len + extra_len).unwrap_or_default();
+        let mut aes = None;
+        let mut i = 0;
+        while i + 4 <= extra.len() {
+            let id = u16::from_le_bytes([extra[i], extra[i + 1]]);
+            let len = u16::from_le_bytes([extra[i + 2], extra[i + 3]]) as usize;
+            let field = extra.get(i + 4..i + 4 + len).unwrap_or_default();
+            match id {
+                0x0001 => {
+                    let mut values = field.chunks_exact(8).map(|c| u64::from_le_bytes(c.try_into().unwrap_or_default()));
+                    if size == 0xFFFF_FFFF {
+                        size = values.next().unwrap_or(size);
+                    }
+                    if compressed_size == 0xFFFF_FFFF {
+                        compressed_size = values.next().unwrap_or(compressed_size);
+                    }
+                    if local_header_offset == 0xFFFF_FFFF {
+                        local_header_offset = values.next().unwrap_or(local_header_offset);
+                    }
+                }
+                0x9901 if field.len() >= 7 => {
+                    aes = Some(Aes {
+                        version: u16::from_le_bytes([field[0], field[1]]),
+                        strength: field[4],
+                        method: u16::from_le_bytes([field[5], field[6]]),
+                    });
+                }
+                _ => {}
+            }
+            i += 4 + len;
+        }
+
+        out.push(Entry {
+            name,
+            method,
+            flags,
+            crc32,
+            compressed_size,
+            size,
+            modified: dos_datetime(date, time),
+            local_header_offset,
+            aes,
+        });
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(out)
+}
+
+/// Raw (still compressed) bytes of an entry.
+pub fn raw_data<'a>(data: &'a [u8], entry: &Entry) -> Result<&'a [u8]> {
+    let header = entry.local_header_offset as usize;
+    if !data.get(header..).is_some_and(|d| d.starts_with(b"PK\x03\x04")) {
+        bail!("Local header of '{}' not found", entry.name);
+    }
+    let name_len = u16_at(data, header + 26)? as usize;
+    let extra_len = u16_at(data, header + 28)? as usize;
+    // Zip64 sizes come from the attacker; a near-u64::MAX value must not wrap the range.
+    let start = header + 30 + name_len + extra_len;
+    usize::try_from(entry.compressed_size).ok()
+        .and_then(|size| start.checked_add(size))
+        .and_then(|end| data.get(start..end))
+        .with_context(|| format!("Data of '{}' is truncated", entry.name))
+}
+
+/// Decompressed content of a stored or deflated, unencrypted entry.
+pub fn read(data: &[u8], entry: &Entry) -> Result<Vec<u8>> {
+    if entry.is_encrypted() {
+        bail!("'{}' is encrypted", entry.name);
+    }
+    decompress(entry, entry.method, raw_data(data, entry)?)
+}
+
+/// Like `read`, decrypting ZipCrypto or WinZip AES entries with `password` first.
+pub fn read_with_password(data: &[u8], entry: &Entry, password: &[u8]) -> Result<Vec<u8>> {
+    if !entry.is_encrypted() {
+        return read(data, entry);
+    }
+    let raw = raw_data(data, entry)?;
+    if let Some(aes) = entry.aes {
+        let plain = decrypt_aes(raw, &aes, password).with_context(|| format!("Cannot decrypt '{}'", entry.name))?;
+        return decompress(entry, aes.method, &plain);
+    }
+    if entry.flags & 0x40 != 0 {
+        bail!("'{}' uses PKWARE strong encryption, which is not supported", entry.name);
+    }
+    // With a data descriptor the check byte comes from the modification time instead of the CRC.
+    let check = if entry.flags & 0x08 != 0 {
+        (u16_at(data, entry.local_header_offset as usize + 10)? >> 8) as u8
+    } else {
+        (entry.crc32 >> 24) as u8
+    };
+    let plain = decrypt_zipcrypto(raw, password, check).with_context(|| format!("Cannot decrypt '{}'", entry.name))?;
+    decompress(entry, entry.method, &plain)
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = flate2::Crc::new();
+    crc.update(data);
+    crc.sum()
+}
+
+pub fn method_name(method: u16) -> String {
+    match method {
+        0 => "stored".to_string(),
+        8 => "deflate".to_string(),
+        9 => "deflate64".to_string(),
+        12 => "bzip2".to_string(),
+        14 => "lzma".to_string(),
+        93 => "zstd".to_string(),
+        95 => "xz".to_string(),
+        98 => "ppmd".to_string(),
+        99 => "aes".to_string(),
+        other => format!("method {}", other),
+    }
+}
+
+fn decompress(entry: &Entry, method: u16, raw: &[u8]) -> Result<Vec<u8>> {
+    match method {
+        0 => Ok(raw.to_vec()),
+        8 => {
+            let mut out = Vec::new();
+            DeflateDecoder::new(raw)
+                .take(MAX_INFLATE)
+                .read_to_end(&mut out)
+                .with_context(|| format!("Failed to inflate '{}'", entry.name))?;
+            Ok(out)
+        }
+        other => bail!("'{}' uses unsupported compression method {}", entry.name, other),
+    }
+}
+
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+/// Traditional PKWARE encryption: a 12-byte header, then the stream cipher over the data.
+fn decrypt_zipcrypto(raw: &[u8], password: &[u8], check: u8) -> Result<Vec<u8>> {
+    if raw.len() < 12 {
+        bail!("encryption header is truncated");
+    }
+    let crc = |crc: u32, b: u8| CRC_TABLE[((crc ^ b as u32) & 0xFF) as usize] ^ (crc >> 8);
+    let mut keys = [0x1234_5678u32, 0x2345_6789, 0x3456_7890];
+    let update = |keys: &mut [u32; 3], b: u8| {
+        keys[0] = crc(keys[0], b);
+        keys[1] = keys[1].wrapping_add(keys[0] & 0xFF).wrapping_mul(134_775_813).wrapping_add(1);
+        keys[2] = crc(keys[2], (keys[1] >> 24) as u8);
+    };
+    for &b in password {
+        update(&mut keys, b);
+    }
+    let mut out = Vec::with_capacity(raw.len() - 12);
+    for (i, &c) in raw.iter().enumerate() {
+        let t = (keys[2] | 2) & 0xFFFF;
+        let p = c ^ ((t * (t ^ 1)) >> 8) as u8;
+        update(&mut keys, p);
+        if i == 11 && p != check {
+            bail!("wrong password");
+        }
+        if i >= 12 {
+            out.push(p);
+        }
+    }
+    Ok(out)
+}
+
+/// WinZip AES: PBKDF2-HMAC-SHA1 keys, AES-CTR with a little-endian counter, HMAC-SHA1 tag.
+fn decrypt_aes(raw: &[u8], aes: &Aes, password: &[u8]) -> Result<Vec<u8>> {
+    let key_len = aes.key_bits() / 8;
+    let salt_len = key_len / 2;
+    if !(1..=3).contains(&aes.strength) || raw.len() < salt_len + 2 + 10 {
+        bail!("invalid AES header");
+    }
+    let (salt, rest) = raw.split_at(salt_len);
+    let (verifier, rest) = rest.split_at(2);
+    let (cipher_text, tag) = rest.split_at(rest.len() - 10);
+
+    let mut derived = vec![0u8; key_len * 2 + 2];
+    openssl::pkcs5::pbkdf2_hmac(password, salt, 1000, MessageDigest::sha1(), &mut derived)?;
+    let (key, rest) = derived.split_at(key_len);
+    let (mac_key, expected_verifier) = rest.split_at(key_len);
+    if verifier != expected_verifier {
+        bail!("wrong password");
+    }
+
+    let mac_key = PKey::hmac(mac_key)?;
+    let mut signer = Signer::new(MessageDigest::sha1(), &mac_key)?;
+    signer.update(cipher_text)?;
+    if signer.sign_to_vec()?[..10] != *tag {
+        bail!("authentication code mismatch (corrupt data)");
+    }
+
+    let cipher = match aes.strength {
+        1 => Cipher::aes_128_ecb(),
+        2 => Cipher::aes_192_ecb(),
+        _ => Cipher::aes_256_ecb(),
+    };
+    let mut counters = Vec::with_capacity(cipher_text.len().div_ceil(16) * 16);
+    for block in 1..=cipher_text.len().div_ceil(16) as u128 {
+        counters.extend_from_slice(&block.to_le_bytes());
+    }
+    let mut crypter = Crypter::new(cipher, Mode::Encrypt, key, None)?;
+    crypter.pad(false);
+    let mut stream = vec![0u8; counters.len() + 16];
+    let n = crypter.update(&counters, &mut stream)?;
+    stream.truncate(n);
+    Ok(cipher_text.iter().zip(stream).map(|(c, k)| c ^ k).collect())
+}
+
+pub fn find<'a>(entries: &'a [Entry], name: &str) -> Option<&'a Entry> {
+    entries.iter().find(|e| e.name.eq_ignore_ascii_case(name))
+}
+
+pub fn dos_datetime(date: u16, time: u16) -> Option<NaiveDateTime> {
+    let day = chrono::NaiveDate::from_ymd_opt(1980 + (date >> 9) as i32, ((date >> 5) & 0xF) as u32, (date & 0x1F) as u32)?;
+    day.and_hms_opt((time >> 11) as u32, ((time >> 5) & 0x3F) as u32, ((time & 0x1F) * 2) as u32)
+}
+
+fn u16_at(data: &[u8], pos: usize) -> Result<u16> {
+    let bytes = data.get(pos..pos + 2).context("Unexpected end of zip data")?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn u32_at(data: &[u8], pos: usize) -> Result<u32> {
+    let bytes = data.get(pos..pos + 4).context("Unexpected end of zip data")?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn u64_at(data: &[u8], pos: usize) -> Result<u64> {
+    let bytes = data.get(pos..pos + 8).context("Unexpected end of zip data")?;
+    Ok(u64::from_le_bytes(bytes.try_into()?))
+}
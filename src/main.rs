@@ -2,7 +2,7 @@ use clap::{Parser, Subcommand};
 use anyhow::Result;
 
 extern crate rex;
-use rex::com::{ssl, file, net, reg, domain, diskinfo, carve, hash, bruteforce};
+use rex::com::{ssl, file, net, reg, domain, diskinfo, carve, hash, bruteforce, rules};
 
 #[derive(Parser)]
 #[command(
@@ -69,6 +69,9 @@ enum Commands {
         /// Base output directory for recovered files
         #[arg(long, default_value = "recovered")]
         output: String,
+        /// Scan recovered files with the YARA rules in this directory
+        #[arg(long)]
+        rules: Option<String>,
     },
 }
 
@@ -76,8 +79,88 @@ enum Commands {
 enum SslCommands {
     /// Dump SSL certificates for a domain
     Dump {
+        /// Target domain (or host when using --live)
+        domain: String,
+        /// Perform a live TLS handshake instead of querying crt.sh
+        #[arg(long, conflicts_with_all = ["format", "output", "issuer", "since", "exclude_expired"])]
+        live: bool,
+        /// Port to connect to in live mode (defaults to 443 or the STARTTLS protocol port)
+        #[arg(long)]
+        port: Option<u16>,
+        /// Upgrade the connection with STARTTLS first: smtp, imap, pop3, ftp, xmpp
+        #[arg(long)]
+        starttls: Option<String>,
+        /// Output format for CT results: text, json, ndjson, csv
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Write CT results to a file instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+        /// Only keep certificates whose issuer contains this string
+        #[arg(long)]
+        issuer: Option<String>,
+        /// Only keep certificates logged on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<String>,
+        /// Drop certificates whose not_after is in the past
+        #[arg(long)]
+        exclude_expired: bool,
+    },
+    /// Enumerate subdomains seen in certificate transparency logs
+    Subdomains {
         /// Target domain
         domain: String,
+        /// Resolve each hostname to A/AAAA records
+        #[arg(long)]
+        resolve: bool,
+    },
+    /// Watch CT logs and report certificates issued since the last run
+    Watch {
+        /// Domains to watch
+        #[arg(required = true)]
+        domains: Vec<String>,
+        /// State file storing what was already seen
+        #[arg(long, default_value = "output/ssl/watch_state.json")]
+        state: String,
+        /// Seconds between checks
+        #[arg(long, default_value_t = 3600)]
+        interval: u64,
+        /// Run a single check and exit (cron-friendly)
+        #[arg(long)]
+        once: bool,
+        /// Base URL of the crt.sh compatible endpoint
+        #[arg(long, default_value = "https://crt.sh")]
+        endpoint: String,
+    },
+    /// Inspect a local PEM/DER/PKCS#7/PKCS#12 certificate file
+    Inspect {
+        /// Path to the certificate file
+        path: String,
+        /// Password for PKCS#12 containers
+        #[arg(long, default_value = "")]
+        password: String,
+    },
+    /// Enumerate accepted TLS protocol versions and cipher suites
+    Ciphers {
+        /// Target host
+        host: String,
+        /// Port to connect to
+        #[arg(long, default_value_t = 443)]
+        port: u16,
+    },
+    /// Validate the certificate chain served by a host
+    Check {
+        /// Target host
+        host: String,
+        /// Port to connect to (defaults to 443 or the STARTTLS protocol port)
+        #[arg(long)]
+        port: Option<u16>,
+        /// Upgrade the connection with STARTTLS first: smtp, imap, pop3, ftp, xmpp
+        #[arg(long)]
+        starttls: Option<String>,
+        /// Warn about certificates expiring within this many days
+        #[arg(long, default_value_t = 30)]
+        days: i64,
     },
 }
 
@@ -88,6 +171,99 @@ enum FileCommands {
         #[command(subcommand)]
         command: PdfCommands,
     },
+    /// Extract VBA macros, external links and embedded objects from an Office document
+    Office {
+        /// Path to the Office document (.doc, .docm, .xls, .xlsm, .ppt, .pptm...)
+        path: String,
+        /// Output directory for extracted content
+        #[arg(long, default_value = "output/file/office")]
+        output: String,
+    },
+    /// Triage an ELF or PE binary: sections, imports, signature, packer indicators
+    Exe {
+        /// Path to the executable
+        path: String,
+    },
+    /// Parse a Windows shortcut (.lnk) and flag hidden command lines
+    Lnk {
+        /// Path to the .lnk file
+        path: String,
+    },
+    /// Analyze an email (.eml or Outlook .msg): Received chain, authentication, URLs and attachments
+    Email {
+        /// Path to the RFC 5322 message or Outlook .msg file
+        path: String,
+        /// Output directory for bodies and attachments
+        #[arg(long, default_value = "output/file/email")]
+        output: String,
+    },
+    /// Identify and hash a file, or every file under a directory
+    Info {
+        /// File or directory (directories are walked recursively)
+        path: String,
+        /// Output format: text, json, ndjson, csv
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Write the report to a file instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Group files under a directory that are variants of each other (CTPH and TLSH)
+    Similar {
+        /// Directory to scan recursively
+        path: String,
+        /// Minimum CTPH score (0-100) for two files to be related
+        #[arg(long, default_value_t = 40)]
+        min_score: u32,
+        /// Maximum TLSH distance for two files to be related
+        #[arg(long, default_value_t = 70)]
+        max_distance: u32,
+    },
+    /// List a ZIP, tar, gzip or RAR archive (ratios, encryption, risky names); 7z is identified only
+    Archive {
+        /// Path to the archive
+        path: String,
+        /// Extract entries into the output directory and hash them (ZIP, tar and gzip only)
+        #[arg(long)]
+        extract: bool,
+        /// Password for encrypted ZIP entries (ZipCrypto or AES)
+        #[arg(long)]
+        password: Option<String>,
+        /// Output directory for extracted entries
+        #[arg(long, default_value = "output/file/archive")]
+        output: String,
+    },
+    /// Extract ASCII and UTF-16LE strings and classify indicators in them
+    Strings {
+        /// Path to any file, disk image or memory dump
+        path: String,
+        /// Minimum string length
+        #[arg(long, short = 'n', default_value_t = 4)]
+        min_len: usize,
+        /// Encodings to look for: all, ascii, utf16le
+        #[arg(long, default_value = "all")]
+        encoding: String,
+        /// Offset radix: x, d, o, or none to hide offsets
+        #[arg(long, short = 't', default_value = "x")]
+        radix: String,
+        /// Only list strings containing an indicator (URL, IP, path, wallet...)
+        #[arg(long)]
+        iocs: bool,
+        /// Output format: text, json, ndjson, csv
+        #[arg(long, default_value = "text")]
+        format: String,
+        /// Write the result to a file instead of stdout
+        #[arg(long)]
+        output: Option<String>,
+    },
+    /// Scan a file or directory with YARA rules
+    Scan {
+        /// File or directory to scan recursively
+        path: String,
+        /// Directory (or single file) of .yar/.yara rules
+        #[arg(long)]
+        rules: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -99,6 +275,20 @@ enum PdfCommands {
         /// Output directory for extracted content
         #[arg(long, default_value = "output/file/pdf/extracted")]
         output: String,
+        /// Password for encrypted PDFs (the empty user password is always tried)
+        #[arg(long)]
+        password: Option<String>,
+        /// Scan extracted streams, scripts and attachments with the YARA rules in this directory
+        #[arg(long)]
+        rules: Option<String>,
+    },
+    /// Count risky keywords, follow actions and score a PDF
+    Triage {
+        /// Path to the PDF file
+        path: String,
+        /// Password for encrypted PDFs (the empty user password is always tried)
+        #[arg(long)]
+        password: Option<String>,
     },
 }
 
@@ -106,6 +296,11 @@ enum PdfCommands {
 enum NetCommands {
     /// Capture and log network traffic (requires root)
     Log,
+    /// Decode packets from a capture file
+    Read {
+        /// Path to the .pcap or .pcapng file
+        path: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -206,15 +401,54 @@ async fn main() -> Result<()> {
 
     match cli.command {
         Commands::Ssl { command } => match command {
-            SslCommands::Dump { domain } => ssl::dump(&domain).await?,
+            SslCommands::Dump { domain, live, port, starttls, format, output, issuer, since, exclude_expired } => {
+                if live {
+                    ssl::dump_live(&domain, port, starttls.as_deref())?;
+                } else {
+                    ssl::dump(
+                        &domain,
+                        &format,
+                        output.as_deref(),
+                        issuer.as_deref(),
+                        since.as_deref(),
+                        exclude_expired,
+                    ).await?;
+                }
+            }
+            SslCommands::Subdomains { domain, resolve } => ssl::subdomains(&domain, resolve).await?,
+            SslCommands::Watch { domains, state, interval, once, endpoint } => {
+                ssl::watch(&domains, &state, interval, once, &endpoint).await?;
+            }
+            SslCommands::Inspect { path, password } => ssl::inspect(&path, &password)?,
+            SslCommands::Ciphers { host, port } => ssl::ciphers(&host, port)?,
+            SslCommands::Check { host, port, starttls, days } => {
+                ssl::check(&host, port, starttls.as_deref(), days)?;
+            }
         },
         Commands::File { command } => match command {
             FileCommands::Pdf { command } => match command {
-                PdfCommands::Extract { path, output } => file::extract_pdf(&path, &output)?,
+                PdfCommands::Extract { path, output, password, rules } => {
+                    file::extract_pdf(&path, &output, password.as_deref(), rules.as_deref())?;
+                }
+                PdfCommands::Triage { path, password } => file::triage_pdf(&path, password.as_deref())?,
             },
+            FileCommands::Office { path, output } => file::office(&path, &output)?,
+            FileCommands::Exe { path } => file::exe(&path)?,
+            FileCommands::Lnk { path } => file::lnk(&path)?,
+            FileCommands::Email { path, output } => file::email(&path, &output)?,
+            FileCommands::Info { path, format, output } => file::info(&path, &format, output.as_deref())?,
+            FileCommands::Similar { path, min_score, max_distance } => file::similar(&path, min_score, max_distance)?,
+            FileCommands::Archive { path, extract, password, output } => {
+                file::archive(&path, &output, extract, password.as_deref())?;
+            }
+            FileCommands::Strings { path, min_len, encoding, radix, iocs, format, output } => {
+                file::strings(&path, min_len, &encoding, &radix, iocs, &format, output.as_deref())?;
+            }
+            FileCommands::Scan { path, rules: rules_path } => rules::scan(&rules_path, &path)?,
         },
         Commands::Net { command } => match command {
             NetCommands::Log => net::logs_network()?,
+            NetCommands::Read { path } => net::read_capture(&path)?,
         },
         Commands::Reg { command } => match command {
             RegCommands::Systemd { command } => match command {
@@ -251,8 +485,8 @@ async fn main() -> Result<()> {
             }
         },
         Commands::Diskinfo => diskinfo::run()?,
-        Commands::Carve { image, all, only_deleted, output } => {
-            carve::run(&image, all, only_deleted, &output)?;
+        Commands::Carve { image, all, only_deleted, output, rules } => {
+            carve::run(&image, all, only_deleted, &output, rules.as_deref())?;
         }
     }
 